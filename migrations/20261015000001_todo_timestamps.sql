@@ -0,0 +1,7 @@
+-- Add migration script here
+-- Created by `sqlx migrate add todo_timestamps`
+
+-- Up
+alter table todos
+    add column created_at timestamptz not null default now(),
+    add column updated_at timestamptz not null default now();
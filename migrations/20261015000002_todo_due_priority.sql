@@ -0,0 +1,7 @@
+-- Add migration script here
+-- Created by `sqlx migrate add todo_due_priority`
+
+-- Up
+alter table todos
+    add column due_at timestamptz,
+    add column priority smallint check (priority between 1 and 3);
@@ -0,0 +1,6 @@
+-- Add migration script here
+-- Created by `sqlx migrate add todo_description`
+
+-- Up
+alter table todos
+    add column description text;
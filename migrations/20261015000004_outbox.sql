@@ -0,0 +1,14 @@
+-- Add migration script here
+-- Created by `sqlx migrate add outbox`
+
+-- Up
+create table outbox
+(
+    id           bigserial primary key,
+    topic        text        not null,
+    payload      jsonb       not null,
+    created_at   timestamptz not null default now(),
+    published_at timestamptz
+);
+
+create index outbox_unpublished on outbox (id) where published_at is null;
@@ -0,0 +1,12 @@
+-- Add migration script here
+-- Created by `sqlx migrate add outbox_retries`
+
+-- Up
+alter table outbox
+    add column attempts         int         not null default 0,
+    add column last_error       text,
+    add column next_attempt_at  timestamptz not null default now(),
+    add column dead_lettered_at timestamptz;
+
+drop index outbox_unpublished;
+create index outbox_pending on outbox (id) where published_at is null and dead_lettered_at is null;
@@ -0,0 +1,50 @@
+-- Add migration script here
+-- Created by `sqlx migrate add todo_events`
+
+-- Up
+-- Append-only history of every todo state transition. `todos` and `todo_labels` are a
+-- projection of it and can be rebuilt with `my-todo rebuild-projection`.
+create table todo_events
+(
+    id          bigserial primary key,
+    todo_id     int         not null,
+    kind        text        not null,
+    data        jsonb       not null,
+    occurred_at timestamptz not null default now()
+);
+
+create index todo_events_todo_id on todo_events (todo_id, id);
+
+create function todo_events_append_only() returns trigger as
+$$
+begin
+    raise exception 'todo_events is append-only';
+end;
+$$ language plpgsql;
+
+create trigger todo_events_append_only
+    before update or delete or truncate
+    on todo_events
+    for each statement
+execute function todo_events_append_only();
+
+-- existing todos start their history with a snapshot
+insert into todo_events (todo_id, kind, data, occurred_at)
+select todos.id,
+       'created',
+       jsonb_build_object(
+               'text', todos.text,
+               'description', todos.description,
+               'completed', todos.completed,
+               'due_at', todos.due_at,
+               'priority', case todos.priority when 1 then 'low' when 2 then 'medium' when 3 then 'high' end,
+               'label_ids', coalesce(array_agg(tl.label_id order by tl.label_id)
+                                     filter (where tl.label_id is not null), '{}'),
+               'created_at', todos.created_at,
+               'updated_at', todos.updated_at
+       ),
+       todos.created_at
+from todos
+         left outer join todo_labels tl on todos.id = tl.todo_id
+group by todos.id
+order by todos.id;
@@ -0,0 +1,100 @@
+-- Add migration script here
+-- Created by `sqlx migrate add todo_list_view`
+
+-- Up
+-- Read model behind `GET /todos`: one row per todo with its labels already aggregated, so
+-- listing skips the todos/todo_labels/labels join. Labels are kept as parallel arrays rather
+-- than jsonb because they decode faster (see `TodoWithLabelsRow`).
+-- Triggers keep it in sync within the writing transaction.
+create table todo_list_view
+(
+    id          int primary key,
+    text        text        not null,
+    completed   boolean     not null,
+    created_at  timestamptz not null,
+    updated_at  timestamptz not null,
+    due_at      timestamptz,
+    priority    smallint,
+    description text,
+    label_ids   int[]       not null,
+    label_names text[]      not null
+);
+
+create index todo_list_view_created_at on todo_list_view (created_at);
+create index todo_list_view_updated_at on todo_list_view (updated_at);
+
+create function refresh_todo_list_view(refreshed_id int) returns void as
+$$
+begin
+    delete from todo_list_view where id = refreshed_id;
+    insert into todo_list_view
+    select todos.id,
+           todos.text,
+           todos.completed,
+           todos.created_at,
+           todos.updated_at,
+           todos.due_at,
+           todos.priority,
+           todos.description,
+           coalesce(array_agg(labels.id order by labels.id) filter (where labels.id is not null), '{}'),
+           coalesce(array_agg(labels.name order by labels.id) filter (where labels.id is not null), '{}')
+    from todos
+             left outer join todo_labels tl on todos.id = tl.todo_id
+             left outer join labels on labels.id = tl.label_id
+    where todos.id = refreshed_id
+    group by todos.id;
+end;
+$$ language plpgsql;
+
+create function todos_refresh_list_view() returns trigger as
+$$
+begin
+    if tg_op = 'DELETE' then
+        perform refresh_todo_list_view(old.id);
+    else
+        perform refresh_todo_list_view(new.id);
+    end if;
+    return null;
+end;
+$$ language plpgsql;
+
+create trigger todos_refresh_list_view
+    after insert or update or delete
+    on todos
+    for each row
+execute function todos_refresh_list_view();
+
+create function todo_labels_refresh_list_view() returns trigger as
+$$
+begin
+    if tg_op = 'DELETE' then
+        perform refresh_todo_list_view(old.todo_id);
+    else
+        perform refresh_todo_list_view(new.todo_id);
+    end if;
+    return null;
+end;
+$$ language plpgsql;
+
+create trigger todo_labels_refresh_list_view
+    after insert or delete
+    on todo_labels
+    for each row
+execute function todo_labels_refresh_list_view();
+
+create function labels_refresh_list_view() returns trigger as
+$$
+begin
+    perform refresh_todo_list_view(tl.todo_id) from todo_labels tl where tl.label_id = new.id;
+    return null;
+end;
+$$ language plpgsql;
+
+create trigger labels_refresh_list_view
+    after update of name
+    on labels
+    for each row
+execute function labels_refresh_list_view();
+
+-- existing todos
+select refresh_todo_list_view(id) from todos;
@@ -0,0 +1,11 @@
+-- Add migration script here
+-- Created by `sqlx migrate add todo_nudges`
+
+-- Up
+-- Open todos the stale-todo job found untouched for too long. A nudge only counts while it is
+-- newer than the todo's `updated_at`, so touching the todo clears it.
+create table todo_nudges
+(
+    todo_id   int primary key references todos (id) on delete cascade,
+    nudged_at timestamptz not null
+);
@@ -0,0 +1,24 @@
+-- Add migration script here
+-- Created by `sqlx migrate add jobs`
+
+-- Up
+-- Background jobs run by `jobs::Jobs`. A job is deleted once it succeeded; after too many
+-- failed attempts it stays with `dead_lettered_at` set, clearing that queues it again.
+create table jobs
+(
+    id               bigserial primary key,
+    kind             text        not null,
+    payload          jsonb       not null,
+    -- at most one pending job per key, e.g. for recurring jobs
+    dedupe_key       text,
+    run_at           timestamptz not null default now(),
+    attempts         int         not null default 0,
+    last_error       text,
+    -- a worker owns the job until then; a crashed worker's job runs again afterwards
+    locked_until     timestamptz,
+    dead_lettered_at timestamptz,
+    created_at       timestamptz not null default now()
+);
+
+create index jobs_pending on jobs (run_at, id) where dead_lettered_at is null;
+create unique index jobs_dedupe_key on jobs (dedupe_key) where dead_lettered_at is null;
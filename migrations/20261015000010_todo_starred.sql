@@ -0,0 +1,36 @@
+-- Add migration script here
+-- Created by `sqlx migrate add todo_starred`
+
+-- Up
+alter table todos
+    add column starred boolean not null default false;
+
+alter table todo_list_view
+    add column starred boolean not null default false;
+
+create index todo_list_view_starred on todo_list_view (id) where starred;
+
+create or replace function refresh_todo_list_view(refreshed_id int) returns void as
+$$
+begin
+    delete from todo_list_view where id = refreshed_id;
+    insert into todo_list_view (id, text, completed, created_at, updated_at, due_at, priority,
+                                description, label_ids, label_names, starred)
+    select todos.id,
+           todos.text,
+           todos.completed,
+           todos.created_at,
+           todos.updated_at,
+           todos.due_at,
+           todos.priority,
+           todos.description,
+           coalesce(array_agg(labels.id order by labels.id) filter (where labels.id is not null), '{}'),
+           coalesce(array_agg(labels.name order by labels.id) filter (where labels.id is not null), '{}'),
+           todos.starred
+    from todos
+             left outer join todo_labels tl on todos.id = tl.todo_id
+             left outer join labels on labels.id = tl.label_id
+    where todos.id = refreshed_id
+    group by todos.id;
+end;
+$$ language plpgsql;
@@ -0,0 +1,39 @@
+-- Add migration script here
+-- Created by `sqlx migrate add todo_color_icon`
+
+-- Up
+-- Validated by the API as well (see `CreateTodo`); the checks keep other writers honest.
+alter table todos
+    add column color text check (color ~* '^#[0-9a-f]{6}$'),
+    add column icon  text check (char_length(icon) between 1 and 16);
+
+alter table todo_list_view
+    add column color text,
+    add column icon  text;
+
+create or replace function refresh_todo_list_view(refreshed_id int) returns void as
+$$
+begin
+    delete from todo_list_view where id = refreshed_id;
+    insert into todo_list_view (id, text, completed, created_at, updated_at, due_at, priority,
+                                description, label_ids, label_names, starred, color, icon)
+    select todos.id,
+           todos.text,
+           todos.completed,
+           todos.created_at,
+           todos.updated_at,
+           todos.due_at,
+           todos.priority,
+           todos.description,
+           coalesce(array_agg(labels.id order by labels.id) filter (where labels.id is not null), '{}'),
+           coalesce(array_agg(labels.name order by labels.id) filter (where labels.id is not null), '{}'),
+           todos.starred,
+           todos.color,
+           todos.icon
+    from todos
+             left outer join todo_labels tl on todos.id = tl.todo_id
+             left outer join labels on labels.id = tl.label_id
+    where todos.id = refreshed_id
+    group by todos.id;
+end;
+$$ language plpgsql;
@@ -0,0 +1,20 @@
+-- Add migration script here
+-- Created by `sqlx migrate add label_name_case_insensitive`
+
+-- Up
+-- Labels whose names only differ in case, like "Work" and "work", keep the oldest name and
+-- get their id appended otherwise. Todos keep their labels, so nothing is merged.
+update labels
+set name = labels.name || ' (' || labels.id || ')'
+where exists (select 1
+              from labels oldest
+              where lower(oldest.name) = lower(labels.name)
+                and oldest.id < labels.id);
+
+select refresh_todo_list_view(todo_id)
+from (select distinct todo_labels.todo_id
+      from todo_labels
+               join labels on labels.id = todo_labels.label_id
+      where labels.name ~ ' \([0-9]+\)$') renamed;
+
+create unique index labels_name_lower on labels (lower(name));
@@ -0,0 +1,77 @@
+-- Add migration script here
+-- Created by `sqlx migrate add public_ids`
+
+-- Up
+-- Public identifiers that can't be guessed or enumerated, unlike the serial ids. ULIDs keep
+-- the creation order: 48 bits of milliseconds and 80 random bits in Crockford's base32.
+create function generate_ulid() returns text as
+$$
+declare
+    alphabet constant text := '0123456789ABCDEFGHJKMNPQRSTVWXYZ';
+    millis bigint := floor(extract(epoch from clock_timestamp()) * 1000);
+    -- bytes 7 and 9 of a v4 uuid carry its version and variant, the others are random
+    uuid_bytes bytea := uuid_send(gen_random_uuid());
+    entropy bytea := substring(uuid_bytes from 1 for 6) || substring(uuid_bytes from 11 for 4);
+    chunk bigint;
+    ulid text := '';
+begin
+    for i in 0..9 loop
+        ulid := ulid || substr(alphabet, ((millis >> (45 - 5 * i)) & 31)::int + 1, 1);
+    end loop;
+    -- 40 bits at a time, 8 characters each
+    for half in 0..1 loop
+        chunk := 0;
+        for b in 0..4 loop
+            chunk := (chunk << 8) | get_byte(entropy, half * 5 + b);
+        end loop;
+        for i in 0..7 loop
+            ulid := ulid || substr(alphabet, ((chunk >> (35 - 5 * i)) & 31)::int + 1, 1);
+        end loop;
+    end loop;
+    return ulid;
+end;
+$$ language plpgsql volatile;
+
+alter table todos
+    add column public_id text not null default generate_ulid();
+create unique index todos_public_id on todos (public_id);
+
+alter table labels
+    add column public_id text not null default generate_ulid();
+create unique index labels_public_id on labels (public_id);
+
+alter table todo_list_view
+    add column public_id        text,
+    add column label_public_ids text[] not null default '{}';
+
+create or replace function refresh_todo_list_view(refreshed_id int) returns void as
+$$
+begin
+    delete from todo_list_view where id = refreshed_id;
+    insert into todo_list_view (id, text, completed, created_at, updated_at, due_at, priority,
+                                description, label_ids, label_names, starred, color, icon,
+                                public_id, label_public_ids)
+    select todos.id,
+           todos.text,
+           todos.completed,
+           todos.created_at,
+           todos.updated_at,
+           todos.due_at,
+           todos.priority,
+           todos.description,
+           coalesce(array_agg(labels.id order by labels.id) filter (where labels.id is not null), '{}'),
+           coalesce(array_agg(labels.name order by labels.id) filter (where labels.id is not null), '{}'),
+           todos.starred,
+           todos.color,
+           todos.icon,
+           todos.public_id,
+           coalesce(array_agg(labels.public_id order by labels.id) filter (where labels.id is not null), '{}')
+    from todos
+             left outer join todo_labels tl on todos.id = tl.todo_id
+             left outer join labels on labels.id = tl.label_id
+    where todos.id = refreshed_id
+    group by todos.id;
+end;
+$$ language plpgsql;
+
+select refresh_todo_list_view(id) from todos;
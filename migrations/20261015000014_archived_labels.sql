@@ -0,0 +1,50 @@
+-- Add migration script here
+-- Created by `sqlx migrate add archived_labels`
+
+-- Up
+-- Archived labels are hidden from pickers but stay on the todos that carry them.
+alter table labels
+    add column archived boolean not null default false;
+
+alter table todo_list_view
+    add column label_archived boolean[] not null default '{}';
+
+create or replace function refresh_todo_list_view(refreshed_id int) returns void as
+$$
+begin
+    delete from todo_list_view where id = refreshed_id;
+    insert into todo_list_view (id, text, completed, created_at, updated_at, due_at, priority,
+                                description, label_ids, label_names, starred, color, icon,
+                                public_id, label_public_ids, label_archived)
+    select todos.id,
+           todos.text,
+           todos.completed,
+           todos.created_at,
+           todos.updated_at,
+           todos.due_at,
+           todos.priority,
+           todos.description,
+           coalesce(array_agg(labels.id order by labels.id) filter (where labels.id is not null), '{}'),
+           coalesce(array_agg(labels.name order by labels.id) filter (where labels.id is not null), '{}'),
+           todos.starred,
+           todos.color,
+           todos.icon,
+           todos.public_id,
+           coalesce(array_agg(labels.public_id order by labels.id) filter (where labels.id is not null), '{}'),
+           coalesce(array_agg(labels.archived order by labels.id) filter (where labels.id is not null), '{}')
+    from todos
+             left outer join todo_labels tl on todos.id = tl.todo_id
+             left outer join labels on labels.id = tl.label_id
+    where todos.id = refreshed_id
+    group by todos.id;
+end;
+$$ language plpgsql;
+
+drop trigger labels_refresh_list_view on labels;
+create trigger labels_refresh_list_view
+    after update of name, archived
+    on labels
+    for each row
+execute function labels_refresh_list_view();
+
+select refresh_todo_list_view(id) from todos;
@@ -0,0 +1,11 @@
+-- Add migration script here
+-- Created by `sqlx migrate add search_indexes`
+
+-- Up
+-- GIN indexes for the list filters a btree can't serve: `ilike '%…%'` searches through
+-- trigrams, and the label filter `label_ids && $1` on the array itself.
+create extension if not exists pg_trgm;
+
+create index todo_list_view_text_trgm on todo_list_view using gin (text gin_trgm_ops);
+create index todo_list_view_description_trgm on todo_list_view using gin (description gin_trgm_ops);
+create index todo_list_view_label_ids on todo_list_view using gin (label_ids);
@@ -0,0 +1,44 @@
+-- Add migration script here
+-- Created by `sqlx migrate add todo_estimates`
+
+-- Up
+-- Validated by the API as well (see `CreateTodo`): a minute up to a week.
+alter table todos
+    add column estimate_minutes int check (estimate_minutes between 1 and 10080);
+
+alter table todo_list_view
+    add column estimate_minutes int;
+
+create or replace function refresh_todo_list_view(refreshed_id int) returns void as
+$$
+begin
+    delete from todo_list_view where id = refreshed_id;
+    insert into todo_list_view (id, text, completed, created_at, updated_at, due_at, priority,
+                                description, label_ids, label_names, starred, color, icon,
+                                public_id, label_public_ids, label_archived, estimate_minutes)
+    select todos.id,
+           todos.text,
+           todos.completed,
+           todos.created_at,
+           todos.updated_at,
+           todos.due_at,
+           todos.priority,
+           todos.description,
+           coalesce(array_agg(labels.id order by labels.id) filter (where labels.id is not null), '{}'),
+           coalesce(array_agg(labels.name order by labels.id) filter (where labels.id is not null), '{}'),
+           todos.starred,
+           todos.color,
+           todos.icon,
+           todos.public_id,
+           coalesce(array_agg(labels.public_id order by labels.id) filter (where labels.id is not null), '{}'),
+           coalesce(array_agg(labels.archived order by labels.id) filter (where labels.id is not null), '{}'),
+           todos.estimate_minutes
+    from todos
+             left outer join todo_labels tl on todos.id = tl.todo_id
+             left outer join labels on labels.id = tl.label_id
+    where todos.id = refreshed_id
+    group by todos.id;
+end;
+$$ language plpgsql;
+
+select refresh_todo_list_view(id) from todos;
@@ -0,0 +1,17 @@
+-- Add migration script here
+-- Created by `sqlx migrate add share_links`
+
+-- Up
+-- Read-only links to a todo, or the todos with a label. Only a hash of the token is kept,
+-- and revoking a link deletes it.
+create table share_links
+(
+    token_hash bytea primary key,
+    todo_id    int references todos (id) on delete cascade,
+    label_id   int references labels (id) on delete cascade,
+    created_at timestamptz not null default now(),
+    check ((todo_id is null) <> (label_id is null))
+);
+
+create index share_links_todo_id on share_links (todo_id);
+create index share_links_label_id on share_links (label_id);
@@ -0,0 +1,14 @@
+-- Add migration script here
+-- Created by `sqlx migrate add embed_filters`
+
+-- Up
+-- Share links to a listing filter, for embedding, see `crate::embed`. The filter is a
+-- `GET /todos` query string.
+alter table share_links
+    add column filter text;
+
+alter table share_links
+    drop constraint share_links_check;
+
+alter table share_links
+    add constraint share_links_check check (num_nonnulls(todo_id, label_id, filter) = 1);
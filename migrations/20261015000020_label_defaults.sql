@@ -0,0 +1,9 @@
+-- Add migration script here
+-- Created by `sqlx migrate add label_defaults`
+
+-- Up
+-- Filled in on a todo when the label is attached, where the todo leaves them unset (see
+-- `LabelDefaults`). The due offset is validated by the API as well: a minute up to a year.
+alter table labels
+    add column default_priority    smallint,
+    add column default_due_minutes int check (default_due_minutes between 1 and 525600);
@@ -0,0 +1,14 @@
+-- Add migration script here
+-- Created by `sqlx migrate add ingest_hooks`
+
+-- Up
+-- Webhooks creating todos from the JSON other tools post, see `crate::ingest`. As with share
+-- links, only a hash of the token is kept. The mapping is an `IngestMapping`.
+create table ingest_hooks
+(
+    id         serial primary key,
+    token_hash bytea       not null unique,
+    name       text        not null,
+    mapping    jsonb       not null,
+    created_at timestamptz not null default now()
+);
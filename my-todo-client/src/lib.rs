@@ -0,0 +1,407 @@
+//! Typed async client for the my-todo HTTP API, using the server's own request and response
+//! types.
+
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::header::{CONTENT_TYPE, COOKIE, HOST};
+use hyper::{Method, Request, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use thiserror::Error;
+use tokio::net::TcpStream;
+use url::{Position, Url};
+
+pub use my_todo_core::handlers::label::LabelImport;
+use my_todo_core::middleware::csrf::{CSRF_COOKIE, CSRF_HEADER};
+pub use my_todo_core::quick_add::QuickAdd;
+pub use my_todo_core::repositories::label::{
+    CreateLabel, ExportedLabel, Label, LabelAssignment, LabelDefaults, LabelExport,
+};
+pub use my_todo_core::repositories::todo::{
+    CreateTodo, Priority, SortOrder, TodoEntity, TodoQuery, TodoSortKey, UpdateTodo,
+};
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("unsupported url [{0}], expected http://host[:port]")]
+    UnsupportedUrl(Url),
+    /// The server answered with a non-success status.
+    #[error("{status}: {body}")]
+    Status { status: StatusCode, body: String },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Http(#[from] hyper::Error),
+    #[error(transparent)]
+    Request(#[from] hyper::http::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+impl ClientError {
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ClientError::Status { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ClientError>;
+
+/// Opens a plain HTTP/1.1 connection per request; TLS is not supported.
+///
+/// Mutating requests carry a matching CSRF cookie and header, so they also pass a server
+/// running with `CSRF_PROTECTION=true`.
+#[derive(Debug, Clone)]
+pub struct Client {
+    base_url: Url,
+    csrf_token: String,
+}
+
+impl Client {
+    pub fn new(base_url: Url) -> Result<Self> {
+        if base_url.scheme() != "http" || base_url.host_str().is_none() {
+            return Err(ClientError::UnsupportedUrl(base_url));
+        }
+        let csrf_token = rand::random::<[u8; 16]>()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        Ok(Client {
+            base_url,
+            csrf_token,
+        })
+    }
+
+    pub async fn create_todo(&self, todo: &CreateTodo) -> Result<TodoEntity> {
+        let body = self
+            .send(Method::POST, self.url("/todos"), Some(todo))
+            .await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    pub async fn find_todo(&self, id: i32) -> Result<TodoEntity> {
+        let url = self.url(&format!("/todos/{}", id));
+        let body = self.send(Method::GET, url, None::<&()>).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    pub async fn all_todos(&self, query: &TodoQuery) -> Result<Vec<TodoEntity>> {
+        let mut url = self.url("/todos");
+        {
+            let mut pairs = url.query_pairs_mut();
+            if let Some(limit) = query.limit {
+                pairs.append_pair("limit", &limit.to_string());
+            }
+            if let Some(offset) = query.offset {
+                pairs.append_pair("offset", &offset.to_string());
+            }
+            if let Some(ids) = &query.ids {
+                let ids = ids.iter().map(i32::to_string).collect::<Vec<_>>();
+                pairs.append_pair("ids", &ids.join(","));
+            }
+            if let Some(completed) = query.completed {
+                pairs.append_pair("completed", &completed.to_string());
+            }
+            if let Some(label_id) = query.label_id {
+                pairs.append_pair("label_id", &label_id.to_string());
+            }
+            if let Some(q) = &query.q {
+                pairs.append_pair("q", q);
+            }
+            if let Some(due_after) = query.due_after {
+                pairs.append_pair("due_after", &due_after.to_rfc3339());
+            }
+            if let Some(due_before) = query.due_before {
+                pairs.append_pair("due_before", &due_before.to_rfc3339());
+            }
+            if let Some(stale) = query.stale {
+                pairs.append_pair("stale", &stale.to_string());
+            }
+            if let Some(starred) = query.starred {
+                pairs.append_pair("starred", &starred.to_string());
+            }
+            for (key, value) in [
+                ("sort", serde_json::to_value(query.sort)?),
+                ("order", serde_json::to_value(query.order)?),
+            ] {
+                if let Some(value) = value.as_str() {
+                    pairs.append_pair(key, value);
+                }
+            }
+        }
+        let body = self.send(Method::GET, url, None::<&()>).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// `None` when every todo is completed.
+    pub async fn next_todo(&self) -> Result<Option<TodoEntity>> {
+        match self
+            .send(Method::GET, self.url("/todos/next"), None::<&()>)
+            .await
+        {
+            Ok(body) => Ok(Some(serde_json::from_slice(&body)?)),
+            Err(e) if e.status() == Some(StatusCode::NOT_FOUND) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn quick_add(&self, quick_add: &QuickAdd) -> Result<TodoEntity> {
+        let url = self.url("/todos/quick");
+        let body = self.send(Method::POST, url, Some(quick_add)).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    pub async fn update_todo(&self, id: i32, todo: &UpdateTodo) -> Result<TodoEntity> {
+        let url = self.url(&format!("/todos/{}", id));
+        let body = self.send(Method::PATCH, url, Some(todo)).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    pub async fn delete_todo(&self, id: i32) -> Result<()> {
+        let url = self.url(&format!("/todos/{}", id));
+        self.send(Method::DELETE, url, None::<&()>).await?;
+        Ok(())
+    }
+
+    pub async fn create_label(&self, label: &CreateLabel) -> Result<Label> {
+        let body = self
+            .send(Method::POST, self.url("/label"), Some(label))
+            .await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// The label named `name`, created unless it already exists.
+    pub async fn find_or_create_label(&self, name: &str) -> Result<Label> {
+        let mut url = self.url("/label");
+        url.query_pairs_mut().append_pair("name", name);
+        let body = self.send(Method::PUT, url, None::<&()>).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// The labels that are not archived.
+    pub async fn all_labels(&self) -> Result<Vec<Label>> {
+        let body = self
+            .send(Method::GET, self.url("/label"), None::<&()>)
+            .await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    pub async fn archived_labels(&self) -> Result<Vec<Label>> {
+        let mut url = self.url("/label");
+        url.query_pairs_mut().append_pair("archived", "true");
+        let body = self.send(Method::GET, url, None::<&()>).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Archive the label, or bring it back when `archived` is false.
+    pub async fn archive_label(&self, id: i32, archived: bool) -> Result<Label> {
+        let action = if archived { "archive" } else { "unarchive" };
+        let url = self.url(&format!("/label/{}/{}", id, action));
+        let body = self.send(Method::POST, url, None::<&()>).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    pub async fn label_defaults(&self, id: i32) -> Result<LabelDefaults> {
+        let url = self.url(&format!("/label/{}/defaults", id));
+        let body = self.send(Method::GET, url, None::<&()>).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Replace what tagging a todo with the label fills in.
+    pub async fn set_label_defaults(
+        &self,
+        id: i32,
+        defaults: &LabelDefaults,
+    ) -> Result<LabelDefaults> {
+        let url = self.url(&format!("/label/{}/defaults", id));
+        let body = self.send(Method::PUT, url, Some(defaults)).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Every label, archived ones included, without ids.
+    pub async fn export_labels(&self) -> Result<LabelExport> {
+        let body = self
+            .send(Method::GET, self.url("/label/export"), None::<&()>)
+            .await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Create the labels of `export` that are missing, e.g. from another environment.
+    pub async fn import_labels(&self, export: &LabelExport) -> Result<LabelImport> {
+        let body = self
+            .send(Method::POST, self.url("/label/import"), Some(export))
+            .await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// With `force` the label is detached from its todos instead of failing with 409.
+    pub async fn delete_label(&self, id: i32, force: bool) -> Result<()> {
+        let mut url = self.url(&format!("/label/{}", id));
+        if force {
+            url.query_pairs_mut().append_pair("force", "true");
+        }
+        self.send(Method::DELETE, url, None::<&()>).await?;
+        Ok(())
+    }
+
+    /// Tag all of `todo_ids` with the label, returning the todos that were not tagged yet.
+    pub async fn assign_label(&self, id: i32, todo_ids: Vec<i32>) -> Result<Vec<i32>> {
+        let url = self.url(&format!("/label/{}/assign", id));
+        let body = self
+            .send(Method::POST, url, Some(&LabelAssignment { todo_ids }))
+            .await?;
+        Ok(serde_json::from_slice::<LabelAssignment>(&body)?.todo_ids)
+    }
+
+    /// Untag all of `todo_ids`, returning the todos that carried the label.
+    pub async fn unassign_label(&self, id: i32, todo_ids: Vec<i32>) -> Result<Vec<i32>> {
+        let url = self.url(&format!("/label/{}/unassign", id));
+        let body = self
+            .send(Method::POST, url, Some(&LabelAssignment { todo_ids }))
+            .await?;
+        Ok(serde_json::from_slice::<LabelAssignment>(&body)?.todo_ids)
+    }
+
+    fn url(&self, path: &str) -> Url {
+        let mut url = self.base_url.clone();
+        url.set_path(&format!(
+            "{}{}",
+            self.base_url.path().trim_end_matches('/'),
+            path
+        ));
+        url
+    }
+
+    /// The response body of a successful request.
+    async fn send<B: Serialize>(
+        &self,
+        method: Method,
+        url: Url,
+        body: Option<&B>,
+    ) -> Result<Bytes> {
+        let host = url.host_str().unwrap_or_default();
+        let port = url.port_or_known_default().unwrap_or(80);
+        let stream = TcpStream::connect((host, port)).await?;
+        let (mut sender, conn) =
+            hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+        tokio::spawn(async move {
+            // failures surface through `send_request`
+            let _ = conn.await;
+        });
+
+        let mut builder = Request::builder()
+            .uri(&url[Position::BeforePath..])
+            .header(HOST, &url[Position::BeforeHost..Position::AfterPort]);
+        if !matches!(method, Method::GET | Method::HEAD) {
+            builder = builder
+                .header(COOKIE, format!("{}={}", CSRF_COOKIE, self.csrf_token))
+                .header(&CSRF_HEADER, &self.csrf_token);
+        }
+        let body = match body {
+            Some(body) => {
+                builder = builder.header(CONTENT_TYPE, "application/json");
+                Bytes::from(serde_json::to_vec(body)?)
+            }
+            None => Bytes::new(),
+        };
+        let req = builder.method(method).body(Full::new(body))?;
+
+        let res = sender.send_request(req).await?;
+        let status = res.status();
+        let body = res.into_body().collect().await?.to_bytes();
+        if !status.is_success() {
+            return Err(ClientError::Status {
+                status,
+                body: String::from_utf8_lossy(&body).into_owned(),
+            });
+        }
+        Ok(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use my_todo_core::config::CsrfConfig;
+    use my_todo_core::create_app;
+    use my_todo_core::middleware::csrf;
+    use my_todo_core::repositories::label::test_inmemory_repo::LabelRepositoryForMemory;
+    use my_todo_core::repositories::todo::test_inmemory_repo::TodoRepositoryMemory;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    async fn serve() -> Client {
+        let app = create_app(TodoRepositoryMemory::new(), LabelRepositoryForMemory::new());
+        let app = csrf::protect(
+            app,
+            &CsrfConfig {
+                enabled: true,
+                cookie_secure: false,
+            },
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        Client::new(Url::parse(&format!("http://{}", addr)).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn todo_round_trip() {
+        let client = serve().await;
+
+        let label = client
+            .create_label(&CreateLabel {
+                name: "finance".to_string(),
+            })
+            .await
+            .expect("[create_label] returned Err");
+        assert_eq!(client.all_labels().await.unwrap(), vec![label.clone()]);
+        assert_eq!(
+            client.find_or_create_label(&label.name).await.unwrap(),
+            label
+        );
+        let archived = client.archive_label(label.id, true).await.unwrap();
+        assert!(archived.archived);
+        assert_eq!(client.all_labels().await.unwrap(), vec![]);
+        assert_eq!(client.archived_labels().await.unwrap(), vec![archived]);
+        assert_eq!(client.archive_label(label.id, false).await.unwrap(), label);
+
+        let created = client
+            .create_todo(
+                &CreateTodo::builder("pay rent")
+                    .priority(Priority::High)
+                    .build(),
+            )
+            .await
+            .expect("[create_todo] returned Err");
+        assert_eq!(client.find_todo(created.id).await.unwrap(), created);
+        assert_eq!(client.next_todo().await.unwrap(), Some(created.clone()));
+
+        let updated = client
+            .update_todo(created.id, &UpdateTodo::builder().completed(true).build())
+            .await
+            .expect("[update_todo] returned Err");
+        assert!(updated.completed);
+        assert_eq!(client.next_todo().await.unwrap(), None);
+
+        let todos = client
+            .all_todos(&TodoQuery {
+                completed: Some(true),
+                ..TodoQuery::default()
+            })
+            .await
+            .expect("[all_todos] returned Err");
+        assert_eq!(todos, vec![updated]);
+
+        client.delete_todo(created.id).await.unwrap();
+        let err = client.find_todo(created.id).await.unwrap_err();
+        assert_eq!(err.status(), Some(StatusCode::NOT_FOUND));
+    }
+
+    #[test]
+    fn only_http_urls() {
+        assert!(Client::new(Url::parse("https://todo.example.com").unwrap()).is_err());
+    }
+}
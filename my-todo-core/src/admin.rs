@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::middleware::csrf::constant_time_eq;
+use crate::middleware::read_only::ReadOnlyMode;
+use crate::reload::Reloader;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReadOnlyState {
+    pub enabled: bool,
+}
+
+/// Operator endpoints under `/admin`, all requiring `Authorization: Bearer <ADMIN_TOKEN>`.
+/// `more` adds those of other modules, e.g. [`crate::purge::routes`].
+pub fn routes(
+    admin_token: String,
+    read_only: Arc<ReadOnlyMode>,
+    reloader: Arc<Reloader>,
+    more: Router,
+) -> Router {
+    let admin = Router::new()
+        .route("/read-only", get(read_only_state).put(set_read_only))
+        .route("/reload", post(reload))
+        .merge(more)
+        .layer(Extension(read_only))
+        .layer(Extension(reloader))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(admin_token),
+            require_admin,
+        ));
+    Router::new().nest("/admin", admin)
+}
+
+async fn require_admin(State(token): State<Arc<String>>, req: Request, next: Next) -> Response {
+    let authorized = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|bearer| constant_time_eq(bearer.as_bytes(), token.as_bytes()));
+    if !authorized {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    next.run(req).await
+}
+
+async fn read_only_state(Extension(mode): Extension<Arc<ReadOnlyMode>>) -> Json<ReadOnlyState> {
+    Json(ReadOnlyState {
+        enabled: mode.is_enabled(),
+    })
+}
+
+async fn set_read_only(
+    Extension(mode): Extension<Arc<ReadOnlyMode>>,
+    Json(state): Json<ReadOnlyState>,
+) -> Json<ReadOnlyState> {
+    mode.set(state.enabled);
+    tracing::warn!("read-only mode set to {}", state.enabled);
+    Json(state)
+}
+
+/// Same as sending SIGHUP, but reports an invalid configuration to the caller.
+async fn reload(Extension(reloader): Extension<Arc<Reloader>>) -> Response {
+    match reloader.reload() {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::header::CONTENT_TYPE;
+    use axum::http::Method;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::reload::tests::config;
+    use crate::reload::LiveConfig;
+
+    fn reloader(
+        mode: Arc<ReadOnlyMode>,
+        pairs: &'static [(&'static str, &'static str)],
+    ) -> Arc<Reloader> {
+        let live = Arc::new(LiveConfig::new(config(&[]).unwrap()));
+        Arc::new(Reloader::new(move || config(pairs), live, mode, |_| Ok(())))
+    }
+
+    #[tokio::test]
+    async fn toggle_read_only_requires_token() {
+        let mode = Arc::new(ReadOnlyMode::default());
+        let app = routes(
+            "secret".to_string(),
+            mode.clone(),
+            reloader(mode.clone(), &[]),
+            Router::new(),
+        );
+        let put = |token: &str| {
+            Request::builder()
+                .uri("/admin/read-only")
+                .method(Method::PUT)
+                .header(AUTHORIZATION, format!("Bearer {}", token))
+                .header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(r#"{"enabled": true}"#))
+                .unwrap()
+        };
+
+        let res = app.clone().oneshot(put("wrong")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert!(!mode.is_enabled());
+
+        let res = app.oneshot(put("secret")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(mode.is_enabled());
+    }
+
+    #[tokio::test]
+    async fn reload_reports_invalid_config() {
+        let post = || {
+            Request::builder()
+                .uri("/admin/reload")
+                .method(Method::POST)
+                .header(AUTHORIZATION, "Bearer secret")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let mode = Arc::new(ReadOnlyMode::default());
+
+        let app = routes(
+            "secret".to_string(),
+            mode.clone(),
+            reloader(mode.clone(), &[("READ_ONLY", "true")]),
+            Router::new(),
+        );
+        let res = app.oneshot(post()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert!(mode.is_enabled());
+
+        let app = routes(
+            "secret".to_string(),
+            mode.clone(),
+            reloader(mode, &[("READ_ONLY", "maybe")]),
+            Router::new(),
+        );
+        let res = app.oneshot(post()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
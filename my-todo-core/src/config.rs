@@ -0,0 +1,1476 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+use std::{env, fs};
+
+use axum::http::HeaderValue;
+use regex::Regex;
+use thiserror::Error;
+use url::Url;
+
+use crate::cron::Cron;
+use crate::handlers::pagination::PageSizes;
+use crate::jobs::Schedule;
+use crate::middleware::concurrency::{self, LimitedRoute};
+use crate::middleware::dedupe::{self, DedupedRoute};
+use crate::middleware::load_shed::{self, LoadShedding};
+use crate::middleware::rate_limit::RateLimit;
+use crate::repositories::cipher::EncryptionKey;
+use crate::repositories::label_cache::DEFAULT_LABEL_CACHE_TTL;
+use crate::repositories::todo::{
+    NextTodoCriterion, DEFAULT_MAX_SEARCH_MATCHES, DEFAULT_NEXT_TODO_SCORING,
+};
+use crate::repositories::DEFAULT_SLOW_QUERY_THRESHOLD;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    #[error("{0} must be set")]
+    Missing(&'static str),
+    #[error("Invalid value for {key}: {message}")]
+    Invalid { key: &'static str, message: String },
+    #[error("{}", several(.0))]
+    Several(Vec<ConfigError>),
+}
+
+fn several(errors: &[ConfigError]) -> String {
+    let mut report = format!("{} problems", errors.len());
+    for error in errors {
+        report.push_str("\n  - ");
+        report.push_str(&error.to_string());
+    }
+    report
+}
+
+const DEFAULT_LISTEN_ADDR: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8078);
+const DEFAULT_DB_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_METRICS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+pub const DEFAULT_LOG_LEVEL: &str = "info";
+const DEFAULT_PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+const DEFAULT_STALE_CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+const DEFAULT_SLO_WINDOW: Duration = Duration::from_secs(3600);
+const DEFAULT_SLO_TARGET_PERCENT: f64 = 99.9;
+const DEFAULT_SLO_LATENCY_TARGET: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    pub database_url: String,
+    /// `/admin/reports` connect with this, best as a role that can only read. Without it the
+    /// saved reports share the main connection, still in read-only transactions, and ad hoc
+    /// queries are refused.
+    pub report_database_url: Option<String>,
+    /// `DB_CONNECT_TIMEOUT_SECS`: how long to keep retrying a database that isn't up yet at
+    /// startup, e.g. when started along with it. Zero tries once.
+    pub db_connect_timeout: Duration,
+    pub cors: CorsConfig,
+    pub csrf: CsrfConfig,
+    pub slow_query_threshold: Duration,
+    /// `MAX_SEARCH_MATCHES`, where searches of encrypted todos stop, see
+    /// [`crate::repositories::todo::TodoRepositoryForDb::with_max_search_matches`].
+    pub max_search_matches: usize,
+    pub health_check_interval: Duration,
+    /// How often the todo and label counts exported at `/metrics` are recounted.
+    pub metrics_refresh_interval: Duration,
+    /// `DEFAULT_PAGE_SIZE` and `MAX_PAGE_SIZE` of `GET /todos`.
+    pub page_sizes: PageSizes,
+    /// How long the label list is cached, see
+    /// [`crate::repositories::label_cache::CachedLabelRepository`]. Zero turns the cache off.
+    pub label_cache_ttl: Duration,
+    /// `RATE_LIMIT_PER_MINUTE`, reported to clients in `X-RateLimit-*` headers but not
+    /// enforced. No headers without it.
+    pub rate_limit: Option<RateLimit>,
+    /// `POST` routes whose double submits are collapsed into one, see
+    /// [`crate::middleware::dedupe::collapse`].
+    pub dedupe_routes: Vec<DedupedRoute>,
+    /// Routes with a cap on requests in flight, see
+    /// [`crate::middleware::concurrency::limit`].
+    pub concurrency_limits: Vec<LimitedRoute>,
+    /// Shedding low-priority routes while the pool is saturated, only with
+    /// `LOAD_SHED_WAIT_MS`. See [`crate::middleware::load_shed::shed`].
+    pub load_shedding: Option<LoadShedding>,
+    pub read_only: bool,
+    /// `/admin` endpoints are only mounted when a token is configured.
+    pub admin_token: Option<String>,
+    /// `POST /inbound/email` is only mounted when a token is configured.
+    pub inbound_email_token: Option<String>,
+    /// Change events are recorded and relayed to this broker, e.g. `nats://localhost:4222`.
+    /// Without it no events are recorded.
+    pub nats_url: Option<Url>,
+    pub outbox: OutboxConfig,
+    /// Ranking used by `GET /todos/next`, e.g. `NEXT_TODO_SCORING=overdue,priority,age`.
+    pub next_todo_scoring: Vec<NextTodoCriterion>,
+    /// The built frontend, served for every path the API doesn't handle.
+    pub static_dir: Option<PathBuf>,
+    /// Mount the server-rendered pages under `/ui`.
+    pub ui_enabled: bool,
+    /// `LISTEN_ADDR`, where the API is served unless a socket is passed in `LISTEN_FDS`.
+    pub listen_addr: SocketAddr,
+    /// `OPS_LISTEN_ADDR`, e.g. `127.0.0.1:9090`, moves `/metrics`, `/healthz` and `/readyz`
+    /// off the API onto a listener of their own.
+    pub ops_listen_addr: Option<SocketAddr>,
+    /// `RUST_LOG` directives, e.g. `info,my_todo=debug`.
+    pub log_level: String,
+    pub purge: PurgeConfig,
+    pub stale: StaleConfig,
+    pub jobs: JobsConfig,
+    pub slo: SloConfig,
+    /// Todo text and descriptions are stored encrypted with this key, see
+    /// [`crate::repositories::cipher::FieldCipher`].
+    pub encryption_key: Option<EncryptionKey>,
+}
+
+impl AppConfig {
+    /// Reads the process environment. Secrets can also be given as files, see [`SECRETS`].
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let mut problems = Problems::default();
+        let mut secrets = HashMap::new();
+        for &(key, file_key) in SECRETS {
+            let secret = read_secret(|key| env::var(key).ok(), key, file_key);
+            if let Some(value) = problems.check(secret).flatten() {
+                secrets.insert(key, value);
+            }
+        }
+        let config =
+            Self::from_lookup(|key| env::var(key).ok().or_else(|| secrets.get(key).cloned()));
+        match config {
+            Ok(config) if problems.0.is_empty() => Ok(config),
+            Ok(_) => Err(problems.into_error()),
+            Err(e) => {
+                problems.push(e);
+                Err(problems.into_error())
+            }
+        }
+    }
+
+    /// Build the config from any key/value source, so tests don't have to touch the process env.
+    ///
+    /// Every setting is checked before giving up, so one error reports all problems at once.
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let mut problems = Problems::default();
+        let database_url =
+            problems.check(lookup("DATABASE_URL").ok_or(ConfigError::Missing("DATABASE_URL")));
+        let report_database_url = lookup("REPORT_DATABASE_URL").filter(|url| !url.is_empty());
+        let db_connect_timeout = problems.check(
+            parse_optional::<u64>(&lookup, "DB_CONNECT_TIMEOUT_SECS").map(|secs| {
+                secs.map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_DB_CONNECT_TIMEOUT)
+            }),
+        );
+        let cors = problems.check(CorsConfig::from_lookup(&lookup));
+        let csrf = problems.check(CsrfConfig::from_lookup(&lookup));
+        let slow_query_threshold = problems.check(
+            parse_optional::<u64>(&lookup, "SLOW_QUERY_THRESHOLD_MS").map(|ms| {
+                ms.map(Duration::from_millis)
+                    .unwrap_or(DEFAULT_SLOW_QUERY_THRESHOLD)
+            }),
+        );
+        let max_search_matches = problems.check(
+            parse_optional::<usize>(&lookup, "MAX_SEARCH_MATCHES").and_then(|max| match max {
+                Some(0) => Err(ConfigError::Invalid {
+                    key: "MAX_SEARCH_MATCHES",
+                    message: "must be at least 1".to_string(),
+                }),
+                max => Ok(max.unwrap_or(DEFAULT_MAX_SEARCH_MATCHES)),
+            }),
+        );
+        let health_check_interval = problems.check(
+            parse_optional::<u64>(&lookup, "HEALTH_CHECK_INTERVAL_SECS").map(|secs| {
+                secs.map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_HEALTH_CHECK_INTERVAL)
+            }),
+        );
+        let metrics_refresh_interval = problems.check(
+            parse_optional::<u64>(&lookup, "METRICS_REFRESH_INTERVAL_SECS").map(|secs| {
+                secs.map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_METRICS_REFRESH_INTERVAL)
+            }),
+        );
+        let page_sizes = problems.check(parse_page_sizes(&lookup));
+        let label_cache_ttl = problems.check(
+            parse_optional::<u64>(&lookup, "LABEL_CACHE_TTL_SECS").map(|secs| {
+                secs.map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_LABEL_CACHE_TTL)
+            }),
+        );
+        let rate_limit = problems.check(
+            match parse_optional::<u64>(&lookup, "RATE_LIMIT_PER_MINUTE") {
+                Ok(Some(0)) => Err(ConfigError::Invalid {
+                    key: "RATE_LIMIT_PER_MINUTE",
+                    message: "must be at least 1".to_string(),
+                }),
+                limit => limit.map(|limit| {
+                    limit.map(|limit| RateLimit {
+                        limit,
+                        window: Duration::from_secs(60),
+                    })
+                }),
+            },
+        );
+        let dedupe_routes = problems.check(parse_dedupe_routes(&lookup));
+        let concurrency_limits = problems.check(parse_concurrency_limits(&lookup));
+        let load_shedding = problems.check(parse_load_shedding(&lookup));
+        let read_only = problems.check(
+            parse_optional::<bool>(&lookup, "READ_ONLY")
+                .map(|read_only| read_only.unwrap_or(false)),
+        );
+        let admin_token = lookup("ADMIN_TOKEN").filter(|token| !token.is_empty());
+        let inbound_email_token = lookup("INBOUND_EMAIL_TOKEN").filter(|token| !token.is_empty());
+        let nats_url = problems.check(parse_optional::<Url>(&lookup, "NATS_URL"));
+        let outbox = problems.check(OutboxConfig::from_lookup(&lookup));
+        let next_todo_scoring = problems.check(match lookup("NEXT_TODO_SCORING") {
+            Some(value) => value
+                .split(',')
+                .map(|criterion| {
+                    criterion
+                        .parse::<NextTodoCriterion>()
+                        .map_err(|message| ConfigError::Invalid {
+                            key: "NEXT_TODO_SCORING",
+                            message,
+                        })
+                })
+                .collect::<Result<Vec<NextTodoCriterion>, ConfigError>>(),
+            None => Ok(DEFAULT_NEXT_TODO_SCORING.to_vec()),
+        });
+        let static_dir = lookup("STATIC_DIR")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from);
+        let ui_enabled = problems.check(
+            parse_optional::<bool>(&lookup, "UI_ENABLED").map(|enabled| enabled.unwrap_or(false)),
+        );
+        let listen_addr = problems.check(
+            parse_optional::<SocketAddr>(&lookup, "LISTEN_ADDR")
+                .map(|addr| addr.unwrap_or(DEFAULT_LISTEN_ADDR)),
+        );
+        let ops_listen_addr =
+            problems.check(parse_optional::<SocketAddr>(&lookup, "OPS_LISTEN_ADDR"));
+        let purge = problems.check(PurgeConfig::from_lookup(&lookup));
+        let stale = problems.check(StaleConfig::from_lookup(&lookup));
+        let jobs = problems.check(JobsConfig::from_lookup(&lookup));
+        let slo = problems.check(SloConfig::from_lookup(&lookup));
+        // not parse_optional, which would echo the key in its error
+        let encryption_key = problems.check(
+            lookup("TODO_ENCRYPTION_KEY")
+                .filter(|key| !key.is_empty())
+                .map(|key| {
+                    key.parse::<EncryptionKey>()
+                        .map_err(|message| ConfigError::Invalid {
+                            key: "TODO_ENCRYPTION_KEY",
+                            message,
+                        })
+                })
+                .transpose(),
+        );
+        let log_level = lookup("RUST_LOG")
+            .filter(|level| !level.is_empty())
+            .unwrap_or_else(|| DEFAULT_LOG_LEVEL.to_string());
+        // every `None` left a problem behind
+        let config = (|| {
+            Some(AppConfig {
+                database_url: database_url?,
+                report_database_url,
+                db_connect_timeout: db_connect_timeout?,
+                cors: cors?,
+                csrf: csrf?,
+                slow_query_threshold: slow_query_threshold?,
+                max_search_matches: max_search_matches?,
+                health_check_interval: health_check_interval?,
+                metrics_refresh_interval: metrics_refresh_interval?,
+                page_sizes: page_sizes?,
+                label_cache_ttl: label_cache_ttl?,
+                rate_limit: rate_limit?,
+                dedupe_routes: dedupe_routes?,
+                concurrency_limits: concurrency_limits?,
+                load_shedding: load_shedding?,
+                read_only: read_only?,
+                admin_token,
+                inbound_email_token,
+                nats_url: nats_url?,
+                outbox: outbox?,
+                next_todo_scoring: next_todo_scoring?,
+                static_dir,
+                ui_enabled: ui_enabled?,
+                listen_addr: listen_addr?,
+                ops_listen_addr: ops_listen_addr?,
+                log_level,
+                purge: purge?,
+                stale: stale?,
+                jobs: jobs?,
+                slo: slo?,
+                encryption_key: encryption_key?,
+            })
+        })();
+        config.ok_or_else(|| problems.into_error())
+    }
+}
+
+/// Settings that may be given as a file named by the `_FILE` variant instead, as with Docker
+/// secrets. Setting both is an error.
+pub const SECRETS: &[(&str, &str)] = &[
+    ("DATABASE_URL", "DATABASE_URL_FILE"),
+    ("REPORT_DATABASE_URL", "REPORT_DATABASE_URL_FILE"),
+    ("ADMIN_TOKEN", "ADMIN_TOKEN_FILE"),
+    ("INBOUND_EMAIL_TOKEN", "INBOUND_EMAIL_TOKEN_FILE"),
+    ("NATS_URL", "NATS_URL_FILE"),
+    ("TODO_ENCRYPTION_KEY", "TODO_ENCRYPTION_KEY_FILE"),
+];
+
+/// `key` from the environment, or from the file its `_FILE` variant names when it is one of
+/// the [`SECRETS`].
+pub fn env_secret(key: &'static str) -> Result<Option<String>, ConfigError> {
+    let lookup = |key: &str| env::var(key).ok();
+    match SECRETS.iter().find(|(secret, _)| *secret == key) {
+        Some(&(key, file_key)) => Ok(read_secret(lookup, key, file_key)?.or_else(|| lookup(key))),
+        None => Ok(lookup(key)),
+    }
+}
+
+/// The contents of the file `file_key` names, without the trailing newline editors add. `None`
+/// when `file_key` isn't set.
+pub fn read_secret(
+    lookup: impl Fn(&str) -> Option<String>,
+    key: &'static str,
+    file_key: &'static str,
+) -> Result<Option<String>, ConfigError> {
+    let Some(path) = lookup(file_key) else {
+        return Ok(None);
+    };
+    if lookup(key).is_some() {
+        return Err(ConfigError::Invalid {
+            key: file_key,
+            message: format!("can't be combined with {}", key),
+        });
+    }
+    let contents = fs::read_to_string(&path).map_err(|e| ConfigError::Invalid {
+        key: file_key,
+        message: format!("can't read {}: {}", path, e),
+    })?;
+    Ok(Some(contents.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+/// Collects configuration errors instead of stopping at the first one.
+#[derive(Default)]
+struct Problems(Vec<ConfigError>);
+
+impl Problems {
+    fn check<T>(&mut self, result: Result<T, ConfigError>) -> Option<T> {
+        result.map_err(|e| self.push(e)).ok()
+    }
+
+    fn push(&mut self, error: ConfigError) {
+        match error {
+            ConfigError::Several(errors) => self.0.extend(errors),
+            error => self.0.push(error),
+        }
+    }
+
+    /// A single problem is returned as it is.
+    fn into_error(mut self) -> ConfigError {
+        match self.0.len() {
+            1 => self.0.remove(0),
+            _ => ConfigError::Several(self.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum OriginMatcher {
+    Exact(HeaderValue),
+    Pattern(Regex),
+}
+
+impl OriginMatcher {
+    /// `https://app.example.com` matches exactly,
+    /// `https://*.vercel.app` matches any (sub)domain in place of `*`,
+    /// `regex:^https://pr-\d+\.example\.com$` is used as a regular expression.
+    fn parse(origin: &str) -> Result<Self, ConfigError> {
+        let invalid = |message: String| ConfigError::Invalid {
+            key: "CLIENT_URL",
+            message,
+        };
+        if let Some(pattern) = origin.strip_prefix("regex:") {
+            let regex = Regex::new(pattern).map_err(|e| invalid(e.to_string()))?;
+            return Ok(OriginMatcher::Pattern(regex));
+        }
+        if origin.contains('*') {
+            let pattern = origin
+                .split('*')
+                .map(regex::escape)
+                .collect::<Vec<String>>()
+                .join("[A-Za-z0-9.-]+");
+            let regex =
+                Regex::new(&format!("^{}$", pattern)).map_err(|e| invalid(e.to_string()))?;
+            return Ok(OriginMatcher::Pattern(regex));
+        }
+        origin
+            .parse::<HeaderValue>()
+            .map(OriginMatcher::Exact)
+            .map_err(|_| invalid(format!("invalid origin [{}]", origin)))
+    }
+
+    pub fn matches(&self, origin: &HeaderValue) -> bool {
+        match self {
+            OriginMatcher::Exact(expected) => expected == origin,
+            OriginMatcher::Pattern(regex) => origin.to_str().is_ok_and(|o| regex.is_match(o)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    pub allow_origins: Vec<OriginMatcher>,
+    pub max_age: Option<Duration>,
+    pub allow_credentials: bool,
+}
+
+impl CorsConfig {
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        // CLIENT_URL is a comma-separated list of origins.
+        let allow_origins = lookup("CLIENT_URL")
+            .ok_or(ConfigError::Missing("CLIENT_URL"))?
+            .split(',')
+            .map(str::trim)
+            .filter(|origin| !origin.is_empty())
+            .map(OriginMatcher::parse)
+            .collect::<Result<Vec<OriginMatcher>, ConfigError>>()?;
+        if allow_origins.is_empty() {
+            return Err(ConfigError::Invalid {
+                key: "CLIENT_URL",
+                message: "at least one origin is required".to_string(),
+            });
+        }
+        let max_age = parse_optional::<u64>(&lookup, "CORS_MAX_AGE")?.map(Duration::from_secs);
+        let allow_credentials =
+            parse_optional::<bool>(&lookup, "CORS_ALLOW_CREDENTIALS")?.unwrap_or(false);
+        Ok(CorsConfig {
+            allow_origins,
+            max_age,
+            allow_credentials,
+        })
+    }
+}
+
+/// CSRF protection is only needed when the browser authenticates with cookies,
+/// so it is opt-in via `CSRF_PROTECTION=true`.
+#[derive(Debug, Clone)]
+pub struct CsrfConfig {
+    pub enabled: bool,
+    pub cookie_secure: bool,
+}
+
+impl CsrfConfig {
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        Ok(CsrfConfig {
+            enabled: parse_optional::<bool>(&lookup, "CSRF_PROTECTION")?.unwrap_or(false),
+            cookie_secure: parse_optional::<bool>(&lookup, "CSRF_COOKIE_SECURE")?.unwrap_or(true),
+        })
+    }
+}
+
+/// How the outbox relay publishes events, see [`crate::events::relay`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutboxConfig {
+    pub relay_interval: Duration,
+    /// Failed publishes of one event before it is dead-lettered.
+    pub max_attempts: i32,
+    /// How long published events are kept.
+    pub retention: Duration,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        OutboxConfig {
+            relay_interval: Duration::from_secs(1),
+            max_attempts: 10,
+            retention: Duration::from_secs(7 * 24 * 3600),
+        }
+    }
+}
+
+impl OutboxConfig {
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let default = OutboxConfig::default();
+        let max_attempts =
+            parse_optional::<i32>(&lookup, "OUTBOX_MAX_ATTEMPTS")?.unwrap_or(default.max_attempts);
+        if max_attempts < 1 {
+            return Err(ConfigError::Invalid {
+                key: "OUTBOX_MAX_ATTEMPTS",
+                message: "must be at least 1".to_string(),
+            });
+        }
+        Ok(OutboxConfig {
+            relay_interval: parse_optional::<u64>(&lookup, "OUTBOX_RELAY_INTERVAL_MS")?
+                .map(Duration::from_millis)
+                .unwrap_or(default.relay_interval),
+            max_attempts,
+            retention: parse_optional::<u64>(&lookup, "OUTBOX_RETENTION_HOURS")?
+                .map(|hours| Duration::from_secs(hours * 3600))
+                .unwrap_or(default.retention),
+        })
+    }
+}
+
+/// The background purge archiving completed todos, see [`crate::purge::schedule`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PurgeConfig {
+    /// `PURGE_INTERVAL_SECS`, or a cron expression in `PURGE_SCHEDULE`.
+    pub schedule: Schedule,
+    /// Todos completed this long ago are archived. `None` keeps them listed forever.
+    pub completed_after: Option<Duration>,
+}
+
+impl PurgeConfig {
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let completed_after_days = parse_optional::<u64>(&lookup, "PURGE_COMPLETED_AFTER_DAYS")?;
+        if completed_after_days == Some(0) {
+            return Err(ConfigError::Invalid {
+                key: "PURGE_COMPLETED_AFTER_DAYS",
+                message: "must be at least 1".to_string(),
+            });
+        }
+        Ok(PurgeConfig {
+            schedule: parse_schedule(
+                &lookup,
+                "PURGE_INTERVAL_SECS",
+                "PURGE_SCHEDULE",
+                DEFAULT_PURGE_INTERVAL,
+            )?,
+            completed_after: completed_after_days.map(|days| Duration::from_secs(days * 24 * 3600)),
+        })
+    }
+}
+
+/// The stale-todo job, see [`crate::nudge::schedule`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleConfig {
+    /// `STALE_CHECK_INTERVAL_SECS`, or a cron expression in `STALE_CHECK_SCHEDULE`.
+    pub schedule: Schedule,
+    /// Open todos untouched for this long are nudged. `None` disables the job.
+    pub after: Option<Duration>,
+}
+
+impl StaleConfig {
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let after_days = parse_optional::<u64>(&lookup, "STALE_AFTER_DAYS")?;
+        if after_days == Some(0) {
+            return Err(ConfigError::Invalid {
+                key: "STALE_AFTER_DAYS",
+                message: "must be at least 1".to_string(),
+            });
+        }
+        Ok(StaleConfig {
+            schedule: parse_schedule(
+                &lookup,
+                "STALE_CHECK_INTERVAL_SECS",
+                "STALE_CHECK_SCHEDULE",
+                DEFAULT_STALE_CHECK_INTERVAL,
+            )?,
+            after: after_days.map(|days| Duration::from_secs(days * 24 * 3600)),
+        })
+    }
+}
+
+/// What `GET /admin/slo` measures against, see [`crate::slo::SloTracker`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SloConfig {
+    /// `SLO_WINDOW_MINUTES`, how far back the rates and percentiles go.
+    pub window: Duration,
+    /// `SLO_TARGET_PERCENT`, the share of requests that should succeed, e.g. `99.9`.
+    pub target_percent: f64,
+    /// `SLO_LATENCY_MS`, what counts as answered fast enough.
+    pub latency_target: Duration,
+}
+
+impl SloConfig {
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let window_minutes = parse_optional::<u64>(&lookup, "SLO_WINDOW_MINUTES")?;
+        if window_minutes == Some(0) {
+            return Err(ConfigError::Invalid {
+                key: "SLO_WINDOW_MINUTES",
+                message: "must be at least 1".to_string(),
+            });
+        }
+        let target_percent = parse_optional::<f64>(&lookup, "SLO_TARGET_PERCENT")?
+            .unwrap_or(DEFAULT_SLO_TARGET_PERCENT);
+        // a target of 100% leaves no error budget to report
+        if !(target_percent > 0.0 && target_percent < 100.0) {
+            return Err(ConfigError::Invalid {
+                key: "SLO_TARGET_PERCENT",
+                message: "must be above 0 and below 100".to_string(),
+            });
+        }
+        Ok(SloConfig {
+            window: window_minutes
+                .map(|minutes| Duration::from_secs(minutes * 60))
+                .unwrap_or(DEFAULT_SLO_WINDOW),
+            target_percent,
+            latency_target: parse_optional::<u64>(&lookup, "SLO_LATENCY_MS")?
+                .map(Duration::from_millis)
+                .unwrap_or(DEFAULT_SLO_LATENCY_TARGET),
+        })
+    }
+}
+
+/// The background job workers, see [`crate::jobs::Jobs`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobsConfig {
+    pub workers: usize,
+    /// How long an idle worker waits before looking for due jobs again.
+    pub poll_interval: Duration,
+    /// Failed attempts of one job before it is dead-lettered.
+    pub max_attempts: i32,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        JobsConfig {
+            workers: 2,
+            poll_interval: Duration::from_secs(1),
+            max_attempts: 5,
+        }
+    }
+}
+
+impl JobsConfig {
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let default = JobsConfig::default();
+        let workers = parse_optional::<usize>(&lookup, "JOB_WORKERS")?.unwrap_or(default.workers);
+        let max_attempts =
+            parse_optional::<i32>(&lookup, "JOB_MAX_ATTEMPTS")?.unwrap_or(default.max_attempts);
+        for (key, value) in [
+            ("JOB_WORKERS", workers as i64),
+            ("JOB_MAX_ATTEMPTS", max_attempts as i64),
+        ] {
+            if value < 1 {
+                return Err(ConfigError::Invalid {
+                    key,
+                    message: "must be at least 1".to_string(),
+                });
+            }
+        }
+        Ok(JobsConfig {
+            workers,
+            poll_interval: parse_optional::<u64>(&lookup, "JOB_POLL_INTERVAL_MS")?
+                .map(Duration::from_millis)
+                .unwrap_or(default.poll_interval),
+            max_attempts,
+        })
+    }
+}
+
+/// A job runs either every `interval_key` seconds or on the cron expression in `cron_key`.
+fn parse_schedule(
+    lookup: impl Fn(&str) -> Option<String>,
+    interval_key: &'static str,
+    cron_key: &'static str,
+    default_interval: Duration,
+) -> Result<Schedule, ConfigError> {
+    let interval = parse_optional::<u64>(&lookup, interval_key)?.map(Duration::from_secs);
+    let Some(cron) = lookup(cron_key) else {
+        return Ok(Schedule::Every(interval.unwrap_or(default_interval)));
+    };
+    if interval.is_some() {
+        return Err(ConfigError::Invalid {
+            key: cron_key,
+            message: format!("can't be combined with {}", interval_key),
+        });
+    }
+    cron.parse::<Cron>()
+        .map(Schedule::Cron)
+        .map_err(|message| ConfigError::Invalid {
+            key: cron_key,
+            message,
+        })
+}
+
+/// `DEDUPE_ROUTES=/todos=5000,/todos/quick`: paths, each with its window in milliseconds or
+/// [`dedupe::DEFAULT_DEDUPE_WINDOW`]. Off unless set, as it turns identical creates into one.
+fn parse_dedupe_routes(
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<DedupedRoute>, ConfigError> {
+    let Some(routes) = lookup("DEDUPE_ROUTES") else {
+        return Ok(vec![]);
+    };
+    let invalid = |message: String| ConfigError::Invalid {
+        key: "DEDUPE_ROUTES",
+        message,
+    };
+    routes
+        .split(',')
+        .map(str::trim)
+        .filter(|route| !route.is_empty())
+        .map(|route| {
+            let (path, window) = match route.split_once('=') {
+                Some((path, ms)) => {
+                    let ms = ms
+                        .parse::<u64>()
+                        .map_err(|e| invalid(format!("{}: {}", route, e)))?;
+                    (path, Duration::from_millis(ms))
+                }
+                None => (route, dedupe::DEFAULT_DEDUPE_WINDOW),
+            };
+            if !path.starts_with('/') {
+                return Err(invalid(format!("{} is not a path", path)));
+            }
+            Ok(DedupedRoute {
+                path: path.to_string(),
+                window,
+            })
+        })
+        .collect()
+}
+
+/// `CONCURRENCY_LIMITS=/todos?q=8,/todos/stream`: paths, optionally with the query parameter
+/// a request has to carry to count, each with its limit or
+/// [`concurrency::DEFAULT_CONCURRENCY_LIMIT`]. Empty turns the limits off.
+fn parse_concurrency_limits(
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<LimitedRoute>, ConfigError> {
+    let Some(routes) = lookup("CONCURRENCY_LIMITS") else {
+        return Ok(concurrency::default_routes());
+    };
+    let invalid = |message: String| ConfigError::Invalid {
+        key: "CONCURRENCY_LIMITS",
+        message,
+    };
+    routes
+        .split(',')
+        .map(str::trim)
+        .filter(|route| !route.is_empty())
+        .map(|route| {
+            let (route_path, max_in_flight) = match route.split_once('=') {
+                Some((path, limit)) => match limit.parse::<usize>() {
+                    Ok(limit) if limit > 0 => (path, limit),
+                    _ => return Err(invalid(format!("{}: not a positive limit", route))),
+                },
+                None => (route, concurrency::DEFAULT_CONCURRENCY_LIMIT),
+            };
+            let (path, param) = match route_path.split_once('?') {
+                Some((path, param)) => (path, Some(param.to_string())),
+                None => (route_path, None),
+            };
+            if !path.starts_with('/') {
+                return Err(invalid(format!("{} is not a path", path)));
+            }
+            Ok(LimitedRoute {
+                path: path.to_string(),
+                param,
+                max_in_flight,
+            })
+        })
+        .collect()
+}
+
+/// `LOAD_SHED_WAIT_MS=250` turns shedding on, for the path prefixes in
+/// `LOAD_SHED_ROUTES=/todos/stream,/reports` or [`load_shed::default_routes`].
+fn parse_load_shedding(
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Option<LoadShedding>, ConfigError> {
+    let Some(max_wait_ms) = parse_optional::<u64>(&lookup, "LOAD_SHED_WAIT_MS")? else {
+        return Ok(None);
+    };
+    if max_wait_ms == 0 {
+        return Err(ConfigError::Invalid {
+            key: "LOAD_SHED_WAIT_MS",
+            message: "must be at least 1".to_string(),
+        });
+    }
+    let routes = match lookup("LOAD_SHED_ROUTES") {
+        Some(routes) => routes
+            .split(',')
+            .map(str::trim)
+            .filter(|route| !route.is_empty())
+            .map(|route| match route.starts_with('/') {
+                true => Ok(route.to_string()),
+                false => Err(ConfigError::Invalid {
+                    key: "LOAD_SHED_ROUTES",
+                    message: format!("{} is not a path", route),
+                }),
+            })
+            .collect::<Result<Vec<String>, ConfigError>>()?,
+        None => load_shed::default_routes(),
+    };
+    Ok(Some(LoadShedding {
+        max_wait: Duration::from_millis(max_wait_ms),
+        routes,
+    }))
+}
+
+fn parse_page_sizes(lookup: impl Fn(&str) -> Option<String>) -> Result<PageSizes, ConfigError> {
+    let defaults = PageSizes::default();
+    let max = parse_optional::<i64>(&lookup, "MAX_PAGE_SIZE")?.unwrap_or(defaults.max);
+    if max < 1 {
+        return Err(ConfigError::Invalid {
+            key: "MAX_PAGE_SIZE",
+            message: "must be at least 1".to_string(),
+        });
+    }
+    let default = match parse_optional::<i64>(&lookup, "DEFAULT_PAGE_SIZE")? {
+        Some(default) if !(1..=max).contains(&default) => {
+            return Err(ConfigError::Invalid {
+                key: "DEFAULT_PAGE_SIZE",
+                message: format!("must be between 1 and MAX_PAGE_SIZE ({})", max),
+            })
+        }
+        Some(default) => default,
+        None => defaults.default.min(max),
+    };
+    Ok(PageSizes { default, max })
+}
+
+fn parse_optional<T>(
+    lookup: impl Fn(&str) -> Option<String>,
+    key: &'static str,
+) -> Result<Option<T>, ConfigError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    lookup(key)
+        .map(|value| {
+            value.trim().parse::<T>().map_err(|e| ConfigError::Invalid {
+                key,
+                message: format!("[{}] {}", value, e),
+            })
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn lookup_from(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let map: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |key| map.get(key).cloned()
+    }
+
+    #[test]
+    fn parse_cors_config() {
+        let lookup = lookup_from(&[
+            ("DATABASE_URL", "postgres://localhost/todos"),
+            (
+                "CLIENT_URL",
+                "http://localhost:3000, https://*.vercel.app,regex:^https://pr-\\d+\\.example\\.com$",
+            ),
+            ("CORS_MAX_AGE", "600"),
+            ("CORS_ALLOW_CREDENTIALS", "true"),
+        ]);
+        let config = AppConfig::from_lookup(lookup).expect("failed to parse config");
+        let cors = config.cors;
+        assert_eq!(cors.allow_origins.len(), 3);
+        assert_eq!(cors.max_age, Some(Duration::from_secs(600)));
+        assert!(cors.allow_credentials);
+
+        let allowed = |origin: &'static str| {
+            let origin = HeaderValue::from_static(origin);
+            cors.allow_origins.iter().any(|m| m.matches(&origin))
+        };
+        assert!(allowed("http://localhost:3000"));
+        assert!(allowed("https://my-app-git-feature.vercel.app"));
+        assert!(allowed("https://pr-42.example.com"));
+        assert!(!allowed("http://localhost:3001"));
+        assert!(!allowed("https://vercel.app.evil.com"));
+        assert!(!allowed("https://pr-x.example.com"));
+    }
+
+    #[test]
+    fn parse_slow_query_threshold() {
+        let base = [
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "http://localhost:3000"),
+        ];
+        let config = AppConfig::from_lookup(lookup_from(&base)).unwrap();
+        assert_eq!(config.slow_query_threshold, DEFAULT_SLOW_QUERY_THRESHOLD);
+
+        let config = AppConfig::from_lookup(lookup_from(
+            &[&base[..], &[("SLOW_QUERY_THRESHOLD_MS", "50")]].concat(),
+        ))
+        .unwrap();
+        assert_eq!(config.slow_query_threshold, Duration::from_millis(50));
+    }
+
+    #[test]
+    fn parse_max_search_matches() {
+        let base = [
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "http://localhost:3000"),
+        ];
+        let config = AppConfig::from_lookup(lookup_from(&base)).unwrap();
+        assert_eq!(config.max_search_matches, DEFAULT_MAX_SEARCH_MATCHES);
+
+        let config = AppConfig::from_lookup(lookup_from(
+            &[&base[..], &[("MAX_SEARCH_MATCHES", "500")]].concat(),
+        ))
+        .unwrap();
+        assert_eq!(config.max_search_matches, 500);
+
+        let config = AppConfig::from_lookup(lookup_from(
+            &[&base[..], &[("MAX_SEARCH_MATCHES", "0")]].concat(),
+        ));
+        assert!(matches!(
+            config,
+            Err(ConfigError::Invalid {
+                key: "MAX_SEARCH_MATCHES",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn parse_db_connect_timeout() {
+        let base = [
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "http://localhost:3000"),
+        ];
+        let config = AppConfig::from_lookup(lookup_from(&base)).unwrap();
+        assert_eq!(config.db_connect_timeout, DEFAULT_DB_CONNECT_TIMEOUT);
+
+        let config = AppConfig::from_lookup(lookup_from(
+            &[&base[..], &[("DB_CONNECT_TIMEOUT_SECS", "0")]].concat(),
+        ))
+        .unwrap();
+        assert_eq!(config.db_connect_timeout, Duration::ZERO);
+    }
+
+    #[test]
+    fn parse_listen_addrs() {
+        let base = [
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "http://localhost:3000"),
+        ];
+        let config = AppConfig::from_lookup(lookup_from(&base)).unwrap();
+        assert_eq!(config.listen_addr, DEFAULT_LISTEN_ADDR);
+        assert_eq!(config.ops_listen_addr, None);
+
+        let config = AppConfig::from_lookup(lookup_from(
+            &[
+                &base[..],
+                &[
+                    ("LISTEN_ADDR", "0.0.0.0:8078"),
+                    ("OPS_LISTEN_ADDR", "127.0.0.1:9090"),
+                ],
+            ]
+            .concat(),
+        ))
+        .unwrap();
+        assert_eq!(config.listen_addr, "0.0.0.0:8078".parse().unwrap());
+        assert_eq!(
+            config.ops_listen_addr,
+            Some("127.0.0.1:9090".parse().unwrap())
+        );
+
+        let config = AppConfig::from_lookup(lookup_from(
+            &[&base[..], &[("OPS_LISTEN_ADDR", "9090")]].concat(),
+        ));
+        assert!(matches!(
+            config,
+            Err(ConfigError::Invalid {
+                key: "OPS_LISTEN_ADDR",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn parse_page_sizes() {
+        let base = [
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "http://localhost:3000"),
+        ];
+        let config = AppConfig::from_lookup(lookup_from(&base)).unwrap();
+        assert_eq!(config.page_sizes, PageSizes::default());
+
+        let config = AppConfig::from_lookup(lookup_from(
+            &[&base[..], &[("MAX_PAGE_SIZE", "20")]].concat(),
+        ))
+        .unwrap();
+        assert_eq!(
+            config.page_sizes,
+            PageSizes {
+                default: 20,
+                max: 20
+            }
+        );
+
+        let invalid = AppConfig::from_lookup(lookup_from(
+            &[
+                &base[..],
+                &[("DEFAULT_PAGE_SIZE", "100"), ("MAX_PAGE_SIZE", "20")],
+            ]
+            .concat(),
+        ));
+        assert!(matches!(
+            invalid,
+            Err(ConfigError::Invalid {
+                key: "DEFAULT_PAGE_SIZE",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn parse_dedupe_routes() {
+        let base = [
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "http://localhost:3000"),
+        ];
+        let config = AppConfig::from_lookup(lookup_from(&base)).unwrap();
+        assert!(config.dedupe_routes.is_empty());
+
+        let config = AppConfig::from_lookup(lookup_from(
+            &[&base[..], &[("DEDUPE_ROUTES", "/todos=500, /label")]].concat(),
+        ))
+        .unwrap();
+        assert_eq!(
+            config.dedupe_routes,
+            vec![
+                DedupedRoute {
+                    path: "/todos".to_string(),
+                    window: Duration::from_millis(500)
+                },
+                DedupedRoute {
+                    path: "/label".to_string(),
+                    window: dedupe::DEFAULT_DEDUPE_WINDOW
+                },
+            ]
+        );
+        let config =
+            AppConfig::from_lookup(lookup_from(&[&base[..], &[("DEDUPE_ROUTES", "")]].concat()))
+                .unwrap();
+        assert!(config.dedupe_routes.is_empty());
+
+        for invalid in ["todos", "/todos=soon"] {
+            let config = AppConfig::from_lookup(lookup_from(
+                &[&base[..], &[("DEDUPE_ROUTES", invalid)]].concat(),
+            ));
+            assert!(matches!(
+                config,
+                Err(ConfigError::Invalid {
+                    key: "DEDUPE_ROUTES",
+                    ..
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn parse_concurrency_limits() {
+        let base = [
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "http://localhost:3000"),
+        ];
+        let config = AppConfig::from_lookup(lookup_from(&base)).unwrap();
+        assert_eq!(config.concurrency_limits, concurrency::default_routes());
+
+        let config = AppConfig::from_lookup(lookup_from(
+            &[
+                &base[..],
+                &[("CONCURRENCY_LIMITS", "/todos?q=8, /todos/stream")],
+            ]
+            .concat(),
+        ))
+        .unwrap();
+        assert_eq!(
+            config.concurrency_limits,
+            vec![
+                LimitedRoute {
+                    path: "/todos".to_string(),
+                    param: Some("q".to_string()),
+                    max_in_flight: 8
+                },
+                LimitedRoute {
+                    path: "/todos/stream".to_string(),
+                    param: None,
+                    max_in_flight: concurrency::DEFAULT_CONCURRENCY_LIMIT
+                },
+            ]
+        );
+        let config = AppConfig::from_lookup(lookup_from(
+            &[&base[..], &[("CONCURRENCY_LIMITS", "")]].concat(),
+        ))
+        .unwrap();
+        assert!(config.concurrency_limits.is_empty());
+
+        for invalid in ["todos", "/todos=0", "/todos=many"] {
+            let config = AppConfig::from_lookup(lookup_from(
+                &[&base[..], &[("CONCURRENCY_LIMITS", invalid)]].concat(),
+            ));
+            assert!(matches!(
+                config,
+                Err(ConfigError::Invalid {
+                    key: "CONCURRENCY_LIMITS",
+                    ..
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn parse_load_shedding() {
+        let base = [
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "http://localhost:3000"),
+        ];
+        let config = AppConfig::from_lookup(lookup_from(&base)).unwrap();
+        assert_eq!(config.load_shedding, None);
+
+        let config = AppConfig::from_lookup(lookup_from(
+            &[&base[..], &[("LOAD_SHED_WAIT_MS", "250")]].concat(),
+        ))
+        .unwrap();
+        assert_eq!(
+            config.load_shedding,
+            Some(LoadShedding {
+                max_wait: Duration::from_millis(250),
+                routes: load_shed::default_routes(),
+            })
+        );
+        let config = AppConfig::from_lookup(lookup_from(
+            &[
+                &base[..],
+                &[
+                    ("LOAD_SHED_WAIT_MS", "250"),
+                    ("LOAD_SHED_ROUTES", "/reports, /todos/stream"),
+                ],
+            ]
+            .concat(),
+        ))
+        .unwrap();
+        assert_eq!(
+            config.load_shedding.unwrap().routes,
+            vec!["/reports", "/todos/stream"]
+        );
+
+        for (key, invalid) in [
+            ("LOAD_SHED_WAIT_MS", "0"),
+            ("LOAD_SHED_WAIT_MS", "soon"),
+            ("LOAD_SHED_ROUTES", "reports"),
+        ] {
+            let config = AppConfig::from_lookup(lookup_from(
+                &[&base[..], &[("LOAD_SHED_WAIT_MS", "250"), (key, invalid)]].concat(),
+            ));
+            assert!(
+                matches!(config, Err(ConfigError::Invalid { key: k, .. }) if k == key),
+                "{}={}",
+                key,
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn parse_rate_limit() {
+        let base = [
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "http://localhost:3000"),
+        ];
+        let config = AppConfig::from_lookup(lookup_from(&base)).unwrap();
+        assert_eq!(config.rate_limit, None);
+
+        let config = AppConfig::from_lookup(lookup_from(
+            &[&base[..], &[("RATE_LIMIT_PER_MINUTE", "120")]].concat(),
+        ))
+        .unwrap();
+        assert_eq!(
+            config.rate_limit,
+            Some(RateLimit {
+                limit: 120,
+                window: Duration::from_secs(60)
+            })
+        );
+
+        let invalid = AppConfig::from_lookup(lookup_from(
+            &[&base[..], &[("RATE_LIMIT_PER_MINUTE", "0")]].concat(),
+        ));
+        assert!(matches!(
+            invalid,
+            Err(ConfigError::Invalid {
+                key: "RATE_LIMIT_PER_MINUTE",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn parse_next_todo_scoring() {
+        let base = [
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "http://localhost:3000"),
+        ];
+        let config = AppConfig::from_lookup(lookup_from(&base)).unwrap();
+        assert_eq!(config.next_todo_scoring, DEFAULT_NEXT_TODO_SCORING.to_vec());
+
+        let config = AppConfig::from_lookup(lookup_from(
+            &[&base[..], &[("NEXT_TODO_SCORING", "priority, due")]].concat(),
+        ))
+        .unwrap();
+        assert_eq!(
+            config.next_todo_scoring,
+            vec![NextTodoCriterion::Priority, NextTodoCriterion::Due]
+        );
+
+        let invalid = AppConfig::from_lookup(lookup_from(
+            &[&base[..], &[("NEXT_TODO_SCORING", "priority,urgency")]].concat(),
+        ));
+        assert!(matches!(
+            invalid.unwrap_err(),
+            ConfigError::Invalid {
+                key: "NEXT_TODO_SCORING",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn parse_static_dir() {
+        let base = [
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "http://localhost:3000"),
+        ];
+        let config =
+            AppConfig::from_lookup(lookup_from(&[&base[..], &[("STATIC_DIR", "")]].concat()))
+                .unwrap();
+        assert_eq!(config.static_dir, None);
+
+        let config = AppConfig::from_lookup(lookup_from(
+            &[&base[..], &[("STATIC_DIR", "web/dist")]].concat(),
+        ))
+        .unwrap();
+        assert_eq!(config.static_dir, Some(PathBuf::from("web/dist")));
+    }
+
+    #[test]
+    fn invalid_encryption_key_is_not_echoed() {
+        let err = AppConfig::from_lookup(lookup_from(&[
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "http://localhost:3000"),
+            ("TODO_ENCRYPTION_KEY", "c2VjcmV0"),
+        ]))
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                key: "TODO_ENCRYPTION_KEY",
+                ..
+            }
+        ));
+        assert!(!err.to_string().contains("c2VjcmV0"));
+    }
+
+    #[test]
+    fn parse_slo_config() {
+        let base = [
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "http://localhost:3000"),
+        ];
+        let config = AppConfig::from_lookup(lookup_from(&base)).unwrap();
+        assert_eq!(
+            config.slo,
+            SloConfig {
+                window: DEFAULT_SLO_WINDOW,
+                target_percent: DEFAULT_SLO_TARGET_PERCENT,
+                latency_target: DEFAULT_SLO_LATENCY_TARGET,
+            }
+        );
+
+        let config = AppConfig::from_lookup(lookup_from(
+            &[
+                &base[..],
+                &[
+                    ("SLO_WINDOW_MINUTES", "15"),
+                    ("SLO_TARGET_PERCENT", "99.5"),
+                    ("SLO_LATENCY_MS", "250"),
+                ],
+            ]
+            .concat(),
+        ))
+        .unwrap();
+        assert_eq!(
+            config.slo,
+            SloConfig {
+                window: Duration::from_secs(15 * 60),
+                target_percent: 99.5,
+                latency_target: Duration::from_millis(250),
+            }
+        );
+
+        for (key, invalid) in [
+            ("SLO_WINDOW_MINUTES", "0"),
+            ("SLO_TARGET_PERCENT", "100"),
+            ("SLO_TARGET_PERCENT", "NaN"),
+            ("SLO_LATENCY_MS", "fast"),
+        ] {
+            let config =
+                AppConfig::from_lookup(lookup_from(&[&base[..], &[(key, invalid)]].concat()));
+            assert!(
+                matches!(config, Err(ConfigError::Invalid { key: k, .. }) if k == key),
+                "{}={}",
+                key,
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn parse_purge_config() {
+        let base = [
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "http://localhost:3000"),
+        ];
+        let config = AppConfig::from_lookup(lookup_from(&base)).unwrap();
+        assert_eq!(config.purge.completed_after, None);
+
+        let config = AppConfig::from_lookup(lookup_from(
+            &[&base[..], &[("PURGE_COMPLETED_AFTER_DAYS", "30")]].concat(),
+        ))
+        .unwrap();
+        assert_eq!(
+            config.purge,
+            PurgeConfig {
+                schedule: Schedule::Every(DEFAULT_PURGE_INTERVAL),
+                completed_after: Some(Duration::from_secs(30 * 24 * 3600)),
+            }
+        );
+
+        let config = AppConfig::from_lookup(lookup_from(
+            &[&base[..], &[("PURGE_SCHEDULE", "30 3 * * *")]].concat(),
+        ))
+        .unwrap();
+        assert_eq!(
+            config.purge.schedule,
+            Schedule::Cron("30 3 * * *".parse().unwrap())
+        );
+
+        for invalid in [
+            &[("PURGE_SCHEDULE", "every night")][..],
+            &[
+                ("PURGE_SCHEDULE", "30 3 * * *"),
+                ("PURGE_INTERVAL_SECS", "60"),
+            ],
+        ] {
+            let invalid = AppConfig::from_lookup(lookup_from(&[&base[..], invalid].concat()));
+            assert!(matches!(
+                invalid.unwrap_err(),
+                ConfigError::Invalid {
+                    key: "PURGE_SCHEDULE",
+                    ..
+                }
+            ));
+        }
+
+        let invalid = AppConfig::from_lookup(lookup_from(
+            &[&base[..], &[("PURGE_COMPLETED_AFTER_DAYS", "0")]].concat(),
+        ));
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn parse_jobs_config() {
+        let base = [
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "http://localhost:3000"),
+        ];
+        let config = AppConfig::from_lookup(lookup_from(&base)).unwrap();
+        assert_eq!(config.jobs, JobsConfig::default());
+
+        let config = AppConfig::from_lookup(lookup_from(
+            &[
+                &base[..],
+                &[
+                    ("JOB_WORKERS", "4"),
+                    ("JOB_POLL_INTERVAL_MS", "250"),
+                    ("JOB_MAX_ATTEMPTS", "3"),
+                ],
+            ]
+            .concat(),
+        ))
+        .unwrap();
+        assert_eq!(
+            config.jobs,
+            JobsConfig {
+                workers: 4,
+                poll_interval: Duration::from_millis(250),
+                max_attempts: 3,
+            }
+        );
+
+        let invalid =
+            AppConfig::from_lookup(lookup_from(&[&base[..], &[("JOB_WORKERS", "0")]].concat()));
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn invalid_cors_config_is_an_error() {
+        let missing = AppConfig::from_lookup(lookup_from(&[("DATABASE_URL", "db")]));
+        assert_eq!(missing.unwrap_err(), ConfigError::Missing("CLIENT_URL"));
+
+        let bad_regex = AppConfig::from_lookup(lookup_from(&[
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "regex:(unclosed"),
+        ]));
+        assert!(matches!(
+            bad_regex.unwrap_err(),
+            ConfigError::Invalid {
+                key: "CLIENT_URL",
+                ..
+            }
+        ));
+
+        let bad_max_age = AppConfig::from_lookup(lookup_from(&[
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "http://localhost:3000"),
+            ("CORS_MAX_AGE", "ten minutes"),
+        ]));
+        assert!(matches!(
+            bad_max_age.unwrap_err(),
+            ConfigError::Invalid {
+                key: "CORS_MAX_AGE",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn secrets_from_files() {
+        let path = env::temp_dir().join(format!("my-todo-admin-token-{}", std::process::id()));
+        fs::write(&path, "s3cret\n").unwrap();
+        let path = path.to_string_lossy().to_string();
+        let secret = |pairs: &[(&str, &str)]| {
+            read_secret(lookup_from(pairs), "ADMIN_TOKEN", "ADMIN_TOKEN_FILE")
+        };
+
+        assert_eq!(secret(&[]), Ok(None));
+        assert_eq!(
+            secret(&[("ADMIN_TOKEN_FILE", &path)]),
+            Ok(Some("s3cret".to_string()))
+        );
+        assert!(secret(&[("ADMIN_TOKEN_FILE", &path), ("ADMIN_TOKEN", "other")]).is_err());
+        assert!(secret(&[("ADMIN_TOKEN_FILE", "/nonexistent/admin-token")]).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let err = AppConfig::from_lookup(lookup_from(&[
+            ("SLOW_QUERY_THRESHOLD_MS", "fast"),
+            ("JOB_WORKERS", "0"),
+        ]))
+        .unwrap_err();
+        let ConfigError::Several(problems) = &err else {
+            panic!("expected several problems, got {:?}", err);
+        };
+        assert_eq!(problems.len(), 4);
+        assert!(problems.contains(&ConfigError::Missing("DATABASE_URL")));
+        assert!(problems.contains(&ConfigError::Missing("CLIENT_URL")));
+        let report = err.to_string();
+        assert!(report.starts_with("4 problems"));
+        assert!(report.contains("JOB_WORKERS"));
+    }
+}
@@ -0,0 +1,197 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, DurationRound, Timelike, Utc};
+
+/// How far ahead [`Cron::next_after`] looks before giving up on an expression that never
+/// fires, like `0 0 30 2 *`.
+const MAX_YEARS_AHEAD: i32 = 5;
+
+/// A standard five-field cron expression (`minute hour day-of-month month day-of-week`),
+/// evaluated in UTC.
+///
+/// Fields take `*`, numbers, ranges (`1-5`), steps (`*/15`, `8-18/2`) and lists of those
+/// (`0,30`). Day-of-week counts from Sunday as 0, and 7 is Sunday as well. As in cron, when
+/// both day fields are restricted a day matching either one fires.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl Cron {
+    /// The first time strictly after `after` the expression fires, at a whole minute.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let limit = after.year() + MAX_YEARS_AHEAD;
+        let mut t = after.duration_trunc(Duration::minutes(1)).ok()? + Duration::minutes(1);
+        while t.year() <= limit {
+            if !bit(self.months, t.month()) {
+                t = start_of_next_month(t)?;
+            } else if !self.matches_day(t) {
+                t = start_of_day(t) + Duration::days(1);
+            } else if !bit(self.hours, t.hour()) {
+                t = t.duration_trunc(Duration::hours(1)).ok()? + Duration::hours(1);
+            } else if !bit(self.minutes, t.minute()) {
+                t += Duration::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, t: DateTime<Utc>) -> bool {
+        let day_of_month = bit(self.days_of_month, t.day());
+        let day_of_week = bit(self.days_of_week, t.weekday().num_days_from_sunday());
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (true, true) => true,
+            (false, true) => day_of_month,
+            (true, false) => day_of_week,
+            (false, false) => day_of_month || day_of_week,
+        }
+    }
+}
+
+impl FromStr for Cron {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+            return Err(format!("expected 5 fields, got {}", fields.len()));
+        };
+        let mut days_of_week_bits = parse_field(days_of_week, 0, 7, "day of week")?;
+        // 7 is another name for Sunday
+        if bit(days_of_week_bits, 7) {
+            days_of_week_bits |= 1;
+        }
+        Ok(Cron {
+            source: fields.join(" "),
+            minutes: parse_field(minutes, 0, 59, "minute")?,
+            hours: parse_field(hours, 0, 23, "hour")?,
+            days_of_month: parse_field(days_of_month, 1, 31, "day of month")?,
+            months: parse_field(months, 1, 12, "month")?,
+            days_of_week: days_of_week_bits,
+            any_day_of_month: days_of_month == "*",
+            any_day_of_week: days_of_week == "*",
+        })
+    }
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn bit(bits: u64, n: u32) -> bool {
+    bits & (1 << n) != 0
+}
+
+/// A bit per allowed value.
+fn parse_field(field: &str, min: u32, max: u32, name: &str) -> Result<u64, String> {
+    let number = |s: &str| {
+        s.parse::<u32>()
+            .ok()
+            .filter(|n| (min..=max).contains(n))
+            .ok_or_else(|| format!("invalid {} [{}], must be {}-{}", name, s, min, max))
+    };
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(format!("invalid step in {} [{}]", name, part)),
+            },
+            None => (part, 1),
+        };
+        let (first, last) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((first, last)) => (number(first)?, number(last)?),
+                None => (number(range)?, number(range)?),
+            },
+        };
+        if first > last {
+            return Err(format!("invalid range in {} [{}]", name, part));
+        }
+        for n in (first..=last).step_by(step as usize) {
+            bits |= 1 << n;
+        }
+    }
+    Ok(bits)
+}
+
+fn start_of_day(t: DateTime<Utc>) -> DateTime<Utc> {
+    t.date_naive()
+        .and_hms_opt(0, 0, 0)
+        .expect("midnight exists")
+        .and_utc()
+}
+
+fn start_of_next_month(t: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let (year, month) = match t.month() {
+        12 => (t.year() + 1, 1),
+        month => (t.year(), month + 1),
+    };
+    Some(
+        chrono::NaiveDate::from_ymd_opt(year, month, 1)?
+            .and_hms_opt(0, 0, 0)?
+            .and_utc(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    fn next(expr: &str, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        expr.parse::<Cron>().unwrap().next_after(after)
+    }
+
+    #[test]
+    fn next_fire_time() {
+        let now = at(2026, 10, 15, 9, 41) + Duration::seconds(30);
+        assert_eq!(next("* * * * *", now), Some(at(2026, 10, 15, 9, 42)));
+        assert_eq!(next("*/15 * * * *", now), Some(at(2026, 10, 15, 9, 45)));
+        assert_eq!(next("30 3 * * *", now), Some(at(2026, 10, 16, 3, 30)));
+        // 2026-10-15 is a Thursday
+        assert_eq!(next("0 8 * * 1-5", now), Some(at(2026, 10, 16, 8, 0)));
+        assert_eq!(next("0 8 * * 0,6", now), Some(at(2026, 10, 17, 8, 0)));
+        assert_eq!(next("0 8 * * 7", now), Some(at(2026, 10, 18, 8, 0)));
+        assert_eq!(next("0 0 1 1 *", now), Some(at(2027, 1, 1, 0, 0)));
+        assert_eq!(next("0 0 29 2 *", now), Some(at(2028, 2, 29, 0, 0)));
+        // either day field matches
+        assert_eq!(next("0 0 20 * 6", now), Some(at(2026, 10, 17, 0, 0)));
+        assert_eq!(next("0 0 30 2 *", now), None);
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for expr in [
+            "* * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "daily",
+        ] {
+            assert!(expr.parse::<Cron>().is_err(), "{} should be rejected", expr);
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use axum::extract::Query;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::types::Json as SqlJson;
+use sqlx::PgPool;
+
+use crate::handlers::pagination::DEFAULT_PAGE_SIZE;
+use crate::repositories::todo::{TodoQuery, TodoRepositoryForDb};
+
+/// The indexes of `todo_list_view` the list queries are written for. One dropped by hand, or
+/// never created because a migration was skipped, is reported as missing.
+const EXPECTED_INDEXES: [&str; 6] = [
+    "todo_list_view_created_at",
+    "todo_list_view_updated_at",
+    "todo_list_view_starred",
+    "todo_list_view_text_trgm",
+    "todo_list_view_description_trgm",
+    "todo_list_view_label_ids",
+];
+
+/// Sequential scans reading fewer rows are cheap, and the planner rightly prefers them on
+/// small tables even when an index exists.
+const SEQ_SCAN_HINT_ROWS: f64 = 1_000.0;
+
+/// What `GET /admin/explain` reports.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostics {
+    pub plans: Vec<QueryPlan>,
+    pub missing_indexes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryPlan {
+    /// `list`, `search` or `label`.
+    pub name: String,
+    pub execution_ms: f64,
+    /// The output of `explain (analyze, format json)` as Postgres writes it.
+    pub plan: Value,
+    pub hints: Vec<String>,
+}
+
+/// The search text and label the canned queries use, `?q=…&label_id=…`.
+#[derive(Debug, Deserialize)]
+struct ExplainQuery {
+    q: Option<String>,
+    label_id: Option<i32>,
+}
+
+/// `GET /explain` runs the list queries behind `GET /todos` with `explain analyze`, for
+/// troubleshooting a slow instance. Mounted under `/admin`.
+///
+/// `explain analyze` runs the queries for real. They only read, but on a large instance they
+/// take as long as the listings they stand for.
+pub fn routes(pool: PgPool) -> Router {
+    Router::new()
+        .route("/explain", get(explain))
+        .layer(Extension(pool))
+}
+
+async fn explain(
+    Extension(pool): Extension<PgPool>,
+    Query(params): Query<ExplainQuery>,
+) -> Result<Json<Diagnostics>, StatusCode> {
+    diagnose(&pool, params).await.map(Json).map_err(|e| {
+        tracing::error!("failed to explain the list queries: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+async fn diagnose(pool: &PgPool, params: ExplainQuery) -> anyhow::Result<Diagnostics> {
+    let list = TodoQuery {
+        limit: Some(DEFAULT_PAGE_SIZE),
+        ..TodoQuery::default()
+    };
+    let canned = [
+        ("list", list.clone()),
+        (
+            "search",
+            TodoQuery {
+                q: Some(params.q.unwrap_or_else(|| "todo".to_string())),
+                ..list.clone()
+            },
+        ),
+        (
+            "label",
+            TodoQuery {
+                label_id: Some(params.label_id.unwrap_or(1)),
+                ..list
+            },
+        ),
+    ];
+    let mut plans = vec![];
+    for (name, query) in canned {
+        let (SqlJson(plan),) = TodoRepositoryForDb::explain_list_query(&query)
+            .build_query_as::<(SqlJson<Value>,)>()
+            .fetch_one(pool)
+            .await?;
+        let execution_ms = plan[0]["Execution Time"].as_f64().unwrap_or_default();
+        let mut hints = vec![];
+        seq_scan_hints(&plan[0]["Plan"], &mut hints);
+        plans.push(QueryPlan {
+            name: name.to_string(),
+            execution_ms,
+            plan,
+            hints,
+        });
+    }
+
+    let existing = sqlx::query_scalar::<_, String>(
+        r#"select indexname::text from pg_indexes where tablename = 'todo_list_view'"#,
+    )
+    .fetch_all(pool)
+    .await?;
+    let missing_indexes = EXPECTED_INDEXES
+        .iter()
+        .filter(|index| !existing.iter().any(|name| name == *index))
+        .map(|index| index.to_string())
+        .collect();
+    Ok(Diagnostics {
+        plans,
+        missing_indexes,
+    })
+}
+
+/// A hint for every sequential scan in `node` and below that filters many rows.
+fn seq_scan_hints(node: &Value, hints: &mut Vec<String>) {
+    if node["Node Type"] == "Seq Scan" {
+        if let Some(filter) = node["Filter"].as_str() {
+            let loops = node["Actual Loops"].as_f64().unwrap_or(1.0);
+            let kept = node["Actual Rows"].as_f64().unwrap_or_default();
+            let removed = node["Rows Removed by Filter"].as_f64().unwrap_or_default();
+            let read = (kept + removed) * loops;
+            if read >= SEQ_SCAN_HINT_ROWS {
+                hints.push(format!(
+                    "sequential scan of {} read {} rows to keep {}, an index on the columns of \
+                     {} may help",
+                    node["Relation Name"].as_str().unwrap_or("?"),
+                    read,
+                    kept * loops,
+                    filter
+                ));
+            }
+        }
+    }
+    for child in node["Plans"].as_array().into_iter().flatten() {
+        seq_scan_hints(child, hints);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn hint_at_large_seq_scans() {
+        let plan = json!({
+            "Node Type": "Limit",
+            "Plans": [{
+                "Node Type": "Sort",
+                "Plans": [{
+                    "Node Type": "Seq Scan",
+                    "Relation Name": "todo_list_view",
+                    "Filter": "(text ~~* '%milk%'::text)",
+                    "Actual Rows": 12,
+                    "Actual Loops": 1,
+                    "Rows Removed by Filter": 4988
+                }, {
+                    "Node Type": "Seq Scan",
+                    "Relation Name": "labels",
+                    "Filter": "(id = 1)",
+                    "Actual Rows": 1,
+                    "Actual Loops": 1,
+                    "Rows Removed by Filter": 20
+                }]
+            }]
+        });
+        let mut hints = vec![];
+        seq_scan_hints(&plan, &mut hints);
+        assert_eq!(
+            hints,
+            vec![
+                "sequential scan of todo_list_view read 5000 rows to keep 12, an index on the \
+                 columns of (text ~~* '%milk%'::text) may help"
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "db-test")]
+mod test_psql_repo {
+    use super::*;
+    use crate::repositories::test_db::TestDb;
+
+    #[tokio::test]
+    async fn explain_the_list_queries() {
+        let db = TestDb::new().await;
+        let params = ExplainQuery {
+            q: Some("milk".to_string()),
+            label_id: None,
+        };
+        let diagnostics = diagnose(&db.pool, params)
+            .await
+            .expect("[diagnose] returned Err");
+        let names = diagnostics
+            .plans
+            .iter()
+            .map(|plan| plan.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["list", "search", "label"]);
+        assert!(diagnostics.plans[0].plan[0]["Plan"]["Node Type"].is_string());
+        assert!(diagnostics.missing_indexes.is_empty());
+
+        sqlx::query("drop index todo_list_view_label_ids")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let params = ExplainQuery {
+            q: None,
+            label_id: None,
+        };
+        let diagnostics = diagnose(&db.pool, params).await.unwrap();
+        assert_eq!(diagnostics.missing_indexes, ["todo_list_view_label_ids"]);
+    }
+}
@@ -0,0 +1,386 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::async_trait;
+use serde::Serialize;
+use sqlx::types::Json;
+use sqlx::{FromRow, PgConnection, PgPool};
+
+use crate::config::OutboxConfig;
+use crate::health::Health;
+use crate::repositories::label::Label;
+use crate::repositories::todo::Todo;
+use crate::repositories::RepositoryError;
+
+pub mod nats;
+
+const RELAY_BATCH_SIZE: i64 = 100;
+/// Arbitrary key of the advisory lock held while relaying.
+const RELAY_LOCK_KEY: i64 = 0x6f7574626f78;
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(300);
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Change notifications for downstream consumers.
+///
+/// Repositories write them to the `outbox` table in the same transaction as the change,
+/// and [`relay`] hands them to the broker afterwards, so an event exists iff its change was
+/// committed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    TodoCreated {
+        todo: Todo,
+        label_ids: Vec<i32>,
+    },
+    TodoUpdated {
+        todo: Todo,
+        /// `None` when the labels were left as they were.
+        label_ids: Option<Vec<i32>>,
+    },
+    TodoDeleted {
+        id: i32,
+    },
+    /// An open todo went untouched for longer than `STALE_AFTER_DAYS`.
+    TodoStale {
+        id: i32,
+    },
+    LabelCreated {
+        label: Label,
+    },
+    /// Also sent after a forced delete detached the label from its todos.
+    LabelDeleted {
+        id: i32,
+    },
+    /// The label was put on the todos of `todo_ids` at once.
+    LabelAssigned {
+        id: i32,
+        todo_ids: Vec<i32>,
+    },
+    /// The label was taken off the todos of `todo_ids` at once.
+    LabelUnassigned {
+        id: i32,
+        todo_ids: Vec<i32>,
+    },
+    LabelArchived {
+        id: i32,
+    },
+    LabelUnarchived {
+        id: i32,
+    },
+}
+
+impl Event {
+    pub fn topic(&self) -> &'static str {
+        match self {
+            Event::TodoCreated { .. } => "todo.created",
+            Event::TodoUpdated { .. } => "todo.updated",
+            Event::TodoDeleted { .. } => "todo.deleted",
+            Event::TodoStale { .. } => "todo.stale",
+            Event::LabelCreated { .. } => "label.created",
+            Event::LabelDeleted { .. } => "label.deleted",
+            Event::LabelAssigned { .. } => "label.assigned",
+            Event::LabelUnassigned { .. } => "label.unassigned",
+            Event::LabelArchived { .. } => "label.archived",
+            Event::LabelUnarchived { .. } => "label.unarchived",
+        }
+    }
+}
+
+/// Append `event` to the outbox on `conn`, which should be the transaction making the change.
+pub(crate) async fn record(conn: &mut PgConnection, event: &Event) -> Result<(), RepositoryError> {
+    sqlx::query(r#"insert into outbox (topic, payload) values ($1, $2)"#)
+        .bind(event.topic())
+        .bind(Json(event))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// An outbox row handed to a [`Publisher`]; `id` identifies redeliveries of the same event.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct OutboxMessage {
+    pub id: i64,
+    pub topic: String,
+    pub payload: String,
+}
+
+/// Where relayed events go, e.g. [`nats::NatsPublisher`].
+#[async_trait]
+pub trait Publisher: Send + Sync + 'static {
+    /// Must only return `Ok` once the broker has accepted the message.
+    async fn publish(&self, message: &OutboxMessage) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, FromRow)]
+struct OutboxRow {
+    #[sqlx(flatten)]
+    message: OutboxMessage,
+    attempts: i32,
+    due: bool,
+}
+
+/// Publish outbox events in insertion order every `config.relay_interval`.
+///
+/// An event is marked as published only after the broker accepted it, so a crash in between
+/// publishes it again: consumers get every event at least once and can tell redeliveries
+/// apart by [`OutboxMessage::id`].
+///
+/// A failing event is retried with exponential backoff, holding back the ones after it.
+/// After `config.max_attempts` it is dead-lettered: it stays in the table with
+/// `dead_lettered_at` and `last_error` set, and the relay moves on. Clearing
+/// `dead_lettered_at` queues it again.
+pub async fn relay(
+    pool: PgPool,
+    publisher: Arc<dyn Publisher>,
+    config: OutboxConfig,
+    health: Arc<Health>,
+) {
+    const WORKER: &str = "outbox_relay";
+    health.register_worker(WORKER, config.relay_interval);
+    let mut ticker = tokio::time::interval(config.relay_interval);
+    let mut last_cleanup = Instant::now();
+    loop {
+        ticker.tick().await;
+        health.tick(WORKER);
+        if let Err(e) = relay_batch(&pool, publisher.as_ref(), &config).await {
+            tracing::error!("outbox relay failed: {}", e);
+        }
+        if last_cleanup.elapsed() >= CLEANUP_INTERVAL {
+            last_cleanup = Instant::now();
+            if let Err(e) = delete_published(&pool, config.retention).await {
+                tracing::error!("outbox cleanup failed: {}", e);
+            }
+        }
+    }
+}
+
+async fn relay_batch(
+    pool: &PgPool,
+    publisher: &dyn Publisher,
+    config: &OutboxConfig,
+) -> anyhow::Result<usize> {
+    let mut conn = pool.acquire().await?;
+    // Only one relay across all instances, otherwise events could overtake each other.
+    // A session lock, so it is released with the connection if the process dies.
+    let locked: bool = sqlx::query_scalar(r#"select pg_try_advisory_lock($1)"#)
+        .bind(RELAY_LOCK_KEY)
+        .fetch_one(&mut *conn)
+        .await?;
+    if !locked {
+        return Ok(0);
+    }
+    let result = relay_locked(&mut conn, publisher, config).await;
+    sqlx::query(r#"select pg_advisory_unlock($1)"#)
+        .bind(RELAY_LOCK_KEY)
+        .execute(&mut *conn)
+        .await?;
+    result
+}
+
+async fn relay_locked(
+    conn: &mut PgConnection,
+    publisher: &dyn Publisher,
+    config: &OutboxConfig,
+) -> anyhow::Result<usize> {
+    let rows = sqlx::query_as::<_, OutboxRow>(
+        r#"
+        select id, topic, payload::text as payload, attempts, next_attempt_at <= now() as due
+        from outbox
+        where published_at is null and dead_lettered_at is null
+        order by id
+        limit $1
+        "#,
+    )
+    .bind(RELAY_BATCH_SIZE)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut published = 0;
+    for row in rows {
+        if !row.due {
+            break;
+        }
+        let message = row.message;
+        let error = match publisher.publish(&message).await {
+            Ok(()) => {
+                sqlx::query(r#"update outbox set published_at = now() where id = $1"#)
+                    .bind(message.id)
+                    .execute(&mut *conn)
+                    .await?;
+                metrics::counter!("outbox_published_total").increment(1);
+                published += 1;
+                continue;
+            }
+            Err(e) => e.to_string(),
+        };
+
+        metrics::counter!("outbox_publish_failures_total").increment(1);
+        let attempts = row.attempts + 1;
+        if attempts < config.max_attempts {
+            let backoff = RETRY_BACKOFF
+                .saturating_mul(2u32.saturating_pow(row.attempts as u32))
+                .min(MAX_RETRY_BACKOFF);
+            tracing::warn!(
+                "publishing outbox event {} failed (attempt {}), retrying in {:?}: {}",
+                message.id,
+                attempts,
+                backoff,
+                error
+            );
+            sqlx::query(
+                r#"
+                update outbox
+                set attempts = $2, last_error = $3, next_attempt_at = now() + make_interval(secs => $4)
+                where id = $1
+                "#,
+            )
+            .bind(message.id)
+            .bind(attempts)
+            .bind(&error)
+            .bind(backoff.as_secs_f64())
+            .execute(&mut *conn)
+            .await?;
+            break;
+        }
+
+        tracing::error!(
+            "dead-lettering outbox event {} ({}) after {} attempts: {}",
+            message.id,
+            message.topic,
+            attempts,
+            error
+        );
+        sqlx::query(
+            r#"
+            update outbox set attempts = $2, last_error = $3, dead_lettered_at = now()
+            where id = $1
+            "#,
+        )
+        .bind(message.id)
+        .bind(attempts)
+        .bind(&error)
+        .execute(&mut *conn)
+        .await?;
+        metrics::counter!("outbox_dead_letters_total").increment(1);
+    }
+    Ok(published)
+}
+
+async fn delete_published(pool: &PgPool, retention: Duration) -> anyhow::Result<()> {
+    let result =
+        sqlx::query(r#"delete from outbox where published_at < now() - make_interval(secs => $1)"#)
+            .bind(retention.as_secs_f64())
+            .execute(pool)
+            .await?;
+    tracing::debug!("deleted {} published outbox events", result.rows_affected());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn event_payload_is_tagged() {
+        let event = Event::LabelCreated {
+            label: Label {
+                id: 1,
+                public_id: "01ARZ3NDEKTSV4RRFFQ69G5FAV".to_string(),
+                name: "finance".to_string(),
+                archived: false,
+            },
+        };
+        assert_eq!(event.topic(), "label.created");
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({
+                "type": "label_created",
+                "label": {
+                    "id": 1,
+                    "public_id": "01ARZ3NDEKTSV4RRFFQ69G5FAV",
+                    "name": "finance",
+                    "archived": false,
+                },
+            })
+        );
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "db-test")]
+mod test_psql_repo {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::repositories::test_db::TestDb;
+
+    const POISON_TOPIC: &str = "test.poison";
+
+    /// Accepts everything except messages on [`POISON_TOPIC`].
+    #[derive(Default)]
+    struct RecordingPublisher(Mutex<Vec<OutboxMessage>>);
+
+    #[async_trait]
+    impl Publisher for RecordingPublisher {
+        async fn publish(&self, message: &OutboxMessage) -> anyhow::Result<()> {
+            if message.topic == POISON_TOPIC {
+                anyhow::bail!("rejected");
+            }
+            self.0.lock().unwrap().push(message.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn relay_publishes_and_dead_letters() {
+        let db = TestDb::new().await;
+        let pool = db.pool.clone();
+
+        let poison_id: i64 = sqlx::query_scalar(
+            r#"insert into outbox (topic, payload) values ($1, '{}') returning id"#,
+        )
+        .bind(POISON_TOPIC)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let event = Event::TodoDeleted { id: i32::MAX };
+        let mut tx = pool.begin().await.unwrap();
+        record(&mut tx, &event)
+            .await
+            .expect("[record] returned Err");
+        tx.commit().await.unwrap();
+
+        let config = OutboxConfig {
+            max_attempts: 1,
+            ..OutboxConfig::default()
+        };
+        let publisher = RecordingPublisher::default();
+        while relay_batch(&pool, &publisher, &config)
+            .await
+            .expect("[relay] returned Err")
+            > 0
+        {}
+
+        // the poison message does not block the event recorded after it
+        let published = publisher.0.lock().unwrap().clone();
+        assert!(published
+            .iter()
+            .any(|message| message.topic == "todo.deleted"
+                && serde_json::from_str::<serde_json::Value>(&message.payload).unwrap()
+                    == serde_json::to_value(&event).unwrap()));
+
+        let (attempts, last_error, dead_lettered): (i32, Option<String>, bool) = sqlx::query_as(
+            r#"select attempts, last_error, dead_lettered_at is not null from outbox where id = $1"#,
+        )
+        .bind(poison_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(attempts, 1);
+        assert_eq!(last_error.as_deref(), Some("rejected"));
+        assert!(dead_lettered);
+    }
+}
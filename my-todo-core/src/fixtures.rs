@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::repositories::label::{CreateLabel, Label, LabelRepository};
+use crate::repositories::todo::{CreateTodo, Priority, TodoEntity, TodoRepository, UpdateTodo};
+
+/// Labels and todos to load into a pair of repositories, e.g. `fixtures/demo.json`.
+/// Todos refer to labels by name.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fixture {
+    #[serde(default)]
+    pub labels: Vec<CreateLabel>,
+    #[serde(default)]
+    pub todos: Vec<TodoFixture>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TodoFixture {
+    pub text: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub completed: bool,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub due_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    #[serde(default)]
+    pub starred: bool,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub estimate_minutes: Option<i32>,
+}
+
+/// What [`load`] left in the repositories.
+#[derive(Debug, Default, Serialize)]
+pub struct Loaded {
+    /// By name.
+    pub labels: HashMap<String, Label>,
+    pub todos: Vec<TodoEntity>,
+}
+
+impl Fixture {
+    pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read fixture {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("invalid fixture {}", path.display()))
+    }
+}
+
+/// Create the fixture's labels and todos. Labels that already exist are reused, so loading
+/// twice only duplicates the todos.
+pub async fn load<TR, LR>(
+    fixture: &Fixture,
+    todo_repo: &TR,
+    label_repo: &LR,
+) -> anyhow::Result<Loaded>
+where
+    TR: TodoRepository,
+    LR: LabelRepository,
+{
+    let mut loaded = Loaded::default();
+    for label in &fixture.labels {
+        label.validate()?;
+        let (label, _) = label_repo.find_or_create(label.clone()).await?;
+        loaded.labels.insert(label.name.clone(), label);
+    }
+
+    for todo in &fixture.todos {
+        let labels = todo
+            .labels
+            .iter()
+            .map(|name| match loaded.labels.get(name) {
+                Some(label) => Ok(label.id),
+                None => bail!("todo [{}] refers to undeclared label [{}]", todo.text, name),
+            })
+            .collect::<anyhow::Result<Vec<i32>>>()?;
+        let create_todo = CreateTodo {
+            text: todo.text.clone(),
+            description: todo.description.clone(),
+            labels,
+            due_at: todo.due_at,
+            priority: todo.priority,
+            starred: todo.starred,
+            color: todo.color.clone(),
+            icon: todo.icon.clone(),
+            estimate_minutes: todo.estimate_minutes,
+        };
+        create_todo.validate()?;
+        let mut created = todo_repo.create(create_todo).await?;
+        if todo.completed {
+            let complete = UpdateTodo::builder().completed(true).build();
+            created = todo_repo.update(created.id, complete).await?;
+        }
+        loaded.todos.push(created);
+    }
+    Ok(loaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repositories::label::test_inmemory_repo::LabelRepositoryForMemory;
+    use crate::repositories::todo::test_inmemory_repo::TodoRepositoryMemory;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn load_demo_fixture() {
+        let fixture = Fixture::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../fixtures/demo.json"
+        ))
+        .expect("failed to read demo fixture");
+        let todo_repo = TodoRepositoryMemory::new();
+        let label_repo = LabelRepositoryForMemory::new();
+
+        let loaded = load(&fixture, &todo_repo, &label_repo)
+            .await
+            .expect("failed to load demo fixture");
+        assert_eq!(loaded.labels.len(), 3);
+        assert_eq!(loaded.todos.len(), 4);
+        assert!(loaded.todos[3].completed);
+
+        // labels are reused on a second load
+        let loaded = load(&fixture, &todo_repo, &label_repo)
+            .await
+            .expect("failed to load demo fixture twice");
+        assert_eq!(label_repo.all().await.unwrap().len(), 3);
+        assert_eq!(loaded.labels["work"].id, 2);
+    }
+
+    #[tokio::test]
+    async fn undeclared_label_is_rejected() {
+        let fixture: Fixture =
+            serde_json::from_str(r#"{"todos": [{"text": "x", "labels": ["missing"]}]}"#).unwrap();
+        let res = load(
+            &fixture,
+            &TodoRepositoryMemory::new(),
+            &LabelRepositoryForMemory::new(),
+        )
+        .await;
+        assert!(res.is_err());
+    }
+}
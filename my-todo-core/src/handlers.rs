@@ -0,0 +1,174 @@
+use axum::extract::{FromRequest, Request};
+use axum::http::header::CONTENT_LANGUAGE;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::{async_trait, Json};
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Deserializer};
+use validator::{Validate, ValidationErrors};
+
+use crate::i18n::{Locale, Message};
+use crate::public_id;
+use crate::repositories::label::LabelRepository;
+use crate::repositories::todo::TodoRepository;
+use crate::repositories::RepositoryError;
+
+pub mod cache;
+pub mod label;
+pub mod pagination;
+pub mod todo;
+
+#[derive(Debug)]
+pub struct ValidatedJson<T>(pub(crate) T);
+
+#[async_trait] // Rustのtraitでasync関数を実装できないためマクロを使用する。
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    // B: HttpBody + Send + 'static,
+    // B::Data: Send,
+    // B::Error: Into<BoxError>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let locale = Locale::from_headers(req.headers());
+        let Json(value) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(|rejection| {
+                let message = format!("{}: [{}]", Message::JsonParseError.text(locale), rejection);
+                bad_request(locale, message)
+            })?;
+        value
+            .validate()
+            .map_err(|rejection| validation_error(locale, &rejection))?;
+        Ok(ValidatedJson(value))
+    }
+}
+
+/// The same 400 `ValidatedJson` rejects with, for payloads validated after extraction.
+pub(crate) fn validation_error(locale: Locale, errors: &ValidationErrors) -> Response {
+    let message = format!(
+        "{}: [{}]",
+        Message::ValidationError.text(locale),
+        validation_message(errors, locale)
+    );
+    bad_request(locale, message)
+}
+
+fn bad_request(locale: Locale, message: String) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        [(CONTENT_LANGUAGE, locale.tag())],
+        message,
+    )
+        .into_response()
+}
+
+/// `field: message` for every failed validation, translated by error code when known.
+fn validation_message(errors: &ValidationErrors, locale: Locale) -> String {
+    let mut field_errors = errors.field_errors().into_iter().collect::<Vec<_>>();
+    field_errors.sort_by_key(|(field, _)| *field);
+    field_errors
+        .into_iter()
+        .flat_map(|(field, errors)| {
+            errors.iter().map(move |error| {
+                let text = Message::from_code(&error.code)
+                    .map(|message| message.text(locale).to_string())
+                    .or_else(|| error.message.as_ref().map(|message| message.to_string()))
+                    .unwrap_or_else(|| error.code.to_string());
+                format!("{}: {}", field, text)
+            })
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// The `:id` of a todo or label route: its [`public_id`], or the serial id, which paths keep
+/// accepting while clients move over. Anything else is refused with `400`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathId {
+    Id(i32),
+    Public(String),
+}
+
+impl<'de> Deserialize<'de> for PathId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        if let Ok(id) = value.parse() {
+            Ok(PathId::Id(id))
+        } else if public_id::is_valid(&value) {
+            Ok(PathId::Public(value))
+        } else {
+            Err(D::Error::custom(format!("not an id: {}", value)))
+        }
+    }
+}
+
+impl PathId {
+    pub(crate) async fn todo(self, repo: &impl TodoRepository) -> Result<i32, StatusCode> {
+        match self {
+            PathId::Id(id) => Ok(id),
+            PathId::Public(public_id) => found(repo.resolve(&public_id).await),
+        }
+    }
+
+    pub(crate) async fn label(self, repo: &impl LabelRepository) -> Result<i32, StatusCode> {
+        match self {
+            PathId::Id(id) => Ok(id),
+            PathId::Public(public_id) => found(repo.resolve(&public_id).await),
+        }
+    }
+}
+
+fn found(resolved: anyhow::Result<Option<i32>>) -> Result<i32, StatusCode> {
+    resolved
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Status for repository errors the client can act on, `fallback` for anything else.
+pub(crate) fn error_status(err: &anyhow::Error, fallback: StatusCode) -> StatusCode {
+    match err.downcast_ref::<RepositoryError>() {
+        Some(RepositoryError::Conflict(_))
+        | Some(RepositoryError::DuplicatedLabel(_))
+        | Some(RepositoryError::LabelInUse { .. }) => StatusCode::CONFLICT,
+        Some(RepositoryError::InvalidReference(_)) => StatusCode::UNPROCESSABLE_ENTITY,
+        Some(RepositoryError::NotFound(_)) => StatusCode::NOT_FOUND,
+        Some(RepositoryError::InvalidQuery(_)) => StatusCode::BAD_REQUEST,
+        _ => fallback,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repository_errors_to_status() {
+        let status =
+            |err: RepositoryError| error_status(&err.into(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            status(RepositoryError::Conflict("dup".to_string())),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            status(RepositoryError::DuplicatedLabel(1)),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            status(RepositoryError::InvalidReference("fk".to_string())),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(status(RepositoryError::NotFound(1)), StatusCode::NOT_FOUND);
+        assert_eq!(
+            status(RepositoryError::InvalidQuery("syntax".to_string())),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(RepositoryError::Unexpected("boom".to_string())),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use std::time::SystemTime;
+
+use axum::http::header::{CACHE_CONTROL, IF_MODIFIED_SINCE, LAST_MODIFIED};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+
+/// Caches may store the response, but have to revalidate it before reuse.
+pub const REVALIDATE: &str = "no-cache";
+
+/// Attach `Last-Modified`/`Cache-Control` to `body`, or answer `304 Not Modified`
+/// when the client's `If-Modified-Since` is not older than `last_modified`.
+pub fn conditional(
+    headers: &HeaderMap,
+    last_modified: DateTime<Utc>,
+    body: impl IntoResponse,
+) -> Response {
+    // HTTP dates only have second precision.
+    let last_modified = SystemTime::UNIX_EPOCH
+        + std::time::Duration::from_secs(last_modified.timestamp().max(0) as u64);
+    let cache_headers = [
+        (CACHE_CONTROL, HeaderValue::from_static(REVALIDATE)),
+        (
+            LAST_MODIFIED,
+            HeaderValue::from_str(&httpdate::fmt_http_date(last_modified))
+                .expect("http date is a valid header value"),
+        ),
+    ];
+
+    let not_modified = headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok())
+        .is_some_and(|since| last_modified <= since);
+    if not_modified {
+        return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+    }
+    (cache_headers, body).into_response()
+}
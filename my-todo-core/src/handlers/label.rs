@@ -0,0 +1,258 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, Query};
+use axum::http::header::CACHE_CONTROL;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::handlers::{cache, error_status, validation_error, PathId, ValidatedJson};
+use crate::i18n::Locale;
+use crate::repositories::label::{
+    CreateLabel, ExportedLabel, LabelAssignment, LabelDefaults, LabelExport, LabelRepository,
+};
+use crate::repositories::RepositoryError;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteLabelQuery {
+    /// Detach the label from its todos instead of refusing the deletion.
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LabelListQuery {
+    /// List the archived labels instead of the others.
+    #[serde(default)]
+    archived: bool,
+}
+
+/// The outcome of `POST /label/import`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LabelImport {
+    pub created: usize,
+    /// Labels that existed already; they take the archived state of the import.
+    pub existing: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct LabelInUse {
+    message: String,
+    todo_count: i64,
+}
+
+pub async fn create_label<R: LabelRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    ValidatedJson(payload): ValidatedJson<CreateLabel>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let label = repo
+        .create(payload)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((StatusCode::CREATED, Json(label)))
+}
+
+/// `PUT /label?name=...` returns the label with that name, `201` when it had to be created.
+pub async fn find_or_create_label<R: LabelRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    headers: HeaderMap,
+    Query(payload): Query<CreateLabel>,
+) -> Result<impl IntoResponse, Response> {
+    payload
+        .validate()
+        .map_err(|e| validation_error(Locale::from_headers(&headers), &e))?;
+    let (label, created) = repo
+        .find_or_create(payload)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR).into_response())?;
+    let status = if created {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+    Ok((status, Json(label)))
+}
+
+/// `GET /label` lists the labels to pick from, `GET /label?archived=true` the archived ones.
+pub async fn all_label<R: LabelRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    Query(query): Query<LabelListQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let mut labels = repo
+        .all()
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    labels.retain(|label| label.archived == query.archived);
+    Ok((
+        StatusCode::OK,
+        [(CACHE_CONTROL, cache::REVALIDATE)],
+        Json(labels),
+    ))
+}
+
+pub async fn delete_label<R: LabelRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    Path(id): Path<PathId>,
+    Query(query): Query<DeleteLabelQuery>,
+) -> Response {
+    let id = match id.label(&*repo).await {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    match repo.delete(id, query.force).await {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => match e.downcast_ref::<RepositoryError>() {
+            Some(RepositoryError::LabelInUse { todo_count, .. }) => (
+                StatusCode::CONFLICT,
+                Json(LabelInUse {
+                    message: e.to_string(),
+                    todo_count: *todo_count,
+                }),
+            )
+                .into_response(),
+            _ => error_status(&e, StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        },
+    }
+}
+
+/// `POST /label/:id/assign` tags every todo in the payload at once and answers with those
+/// that were not tagged yet.
+pub async fn assign_label<R: LabelRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    Path(id): Path<PathId>,
+    ValidatedJson(payload): ValidatedJson<LabelAssignment>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let id = id.label(&*repo).await?;
+    let todo_ids = repo
+        .assign(id, payload.todo_ids)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok(Json(LabelAssignment { todo_ids }))
+}
+
+/// `POST /label/:id/unassign`, the reverse of [`assign_label`].
+pub async fn unassign_label<R: LabelRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    Path(id): Path<PathId>,
+    ValidatedJson(payload): ValidatedJson<LabelAssignment>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let id = id.label(&*repo).await?;
+    let todo_ids = repo
+        .unassign(id, payload.todo_ids)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok(Json(LabelAssignment { todo_ids }))
+}
+
+/// `POST /label/:id/archive` hides the label from `GET /label`; todos keep it.
+pub async fn archive_label<R: LabelRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    Path(id): Path<PathId>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let id = id.label(&*repo).await?;
+    let label = repo
+        .archive(id, true)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok(Json(label))
+}
+
+/// `POST /label/:id/unarchive`, the reverse of [`archive_label`].
+pub async fn unarchive_label<R: LabelRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    Path(id): Path<PathId>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let id = id.label(&*repo).await?;
+    let label = repo
+        .archive(id, false)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok(Json(label))
+}
+
+/// `GET /label/:id/defaults`, what tagging a todo with the label fills in.
+pub async fn label_defaults<R: LabelRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    Path(id): Path<PathId>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let id = id.label(&*repo).await?;
+    let defaults = repo
+        .defaults(id)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok(Json(defaults))
+}
+
+/// `PUT /label/:id/defaults` replaces them; defaults left out are cleared.
+pub async fn set_label_defaults<R: LabelRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    Path(id): Path<PathId>,
+    ValidatedJson(payload): ValidatedJson<LabelDefaults>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let id = id.label(&*repo).await?;
+    let defaults = repo
+        .set_defaults(id, payload)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok(Json(defaults))
+}
+
+/// `GET /label/export`, every label including the archived ones, by name.
+pub async fn export_labels<R: LabelRepository>(
+    Extension(repo): Extension<Arc<R>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let mut labels = repo
+        .all()
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    labels.sort_by(|a, b| a.name.cmp(&b.name));
+    let labels = labels
+        .into_iter()
+        .map(|label| ExportedLabel {
+            name: label.name,
+            archived: label.archived,
+        })
+        .collect();
+    Ok(Json(LabelExport { labels }))
+}
+
+/// `POST /label/import` creates the labels of an export that are missing. Names are checked
+/// before anything is written; importing the same export again changes nothing.
+pub async fn import_labels<R: LabelRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    headers: HeaderMap,
+    ValidatedJson(payload): ValidatedJson<LabelExport>,
+) -> Result<impl IntoResponse, Response> {
+    let locale = Locale::from_headers(&headers);
+    for label in &payload.labels {
+        let name = label.name.clone();
+        CreateLabel { name }
+            .validate()
+            .map_err(|e| validation_error(locale, &e))?;
+    }
+    let error = |e: anyhow::Error| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR);
+    let mut outcome = LabelImport {
+        created: 0,
+        existing: 0,
+    };
+    for imported in payload.labels {
+        let name = imported.name;
+        let (label, created) = repo
+            .find_or_create(CreateLabel { name })
+            .await
+            .map_err(|e| error(e).into_response())?;
+        if created {
+            outcome.created += 1;
+        } else {
+            outcome.existing += 1;
+        }
+        if label.archived != imported.archived {
+            repo.archive(label.id, imported.archived)
+                .await
+                .map_err(|e| error(e).into_response())?;
+        }
+    }
+    Ok(Json(outcome))
+}
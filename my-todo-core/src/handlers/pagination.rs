@@ -0,0 +1,72 @@
+use axum::async_trait;
+use axum::extract::{FromRequestParts, Query};
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+/// Page sizes of listings, `DEFAULT_PAGE_SIZE` and `MAX_PAGE_SIZE` in the config. The server
+/// adds them as an [`axum::Extension`]; without one the constants of this module apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageSizes {
+    pub default: i64,
+    pub max: i64,
+}
+
+impl Default for PageSizes {
+    fn default() -> Self {
+        PageSizes {
+            default: DEFAULT_PAGE_SIZE,
+            max: MAX_PAGE_SIZE,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PageQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// `limit` and `offset` of a listing. A `limit` beyond [`PageSizes::max`] is refused with
+/// `422` rather than quietly cut down, so a client pulling "everything" learns it did not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Page {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub sizes: PageSizes,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Page {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let sizes = parts
+            .extensions
+            .get::<PageSizes>()
+            .copied()
+            .unwrap_or_default();
+        let Query(query) = Query::<PageQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        if let Some(limit) = query.limit.filter(|limit| !(1..=sizes.max).contains(limit)) {
+            return Err(out_of_range("limit", limit, sizes));
+        }
+        Ok(Page {
+            limit: query.limit,
+            offset: query.offset.map(|offset| offset.max(0)),
+            sizes,
+        })
+    }
+}
+
+/// The `422` for a page of `count` items when [`PageSizes::max`] is the most there can be,
+/// whether a `limit` or a batch of `ids` asked for them.
+pub(crate) fn out_of_range(what: &str, count: i64, sizes: PageSizes) -> Response {
+    let message = format!("{} [{}] is not between 1 and {}", what, count, sizes.max);
+    (StatusCode::UNPROCESSABLE_ENTITY, message).into_response()
+}
@@ -0,0 +1,340 @@
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::{Path, Query};
+use axum::http::header::{CACHE_CONTROL, CONTENT_TYPE};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use chrono::{DateTime, Days, FixedOffset, NaiveDate, NaiveTime, TimeZone, Utc};
+use futures_util::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::handlers::pagination::{self, Page};
+use crate::handlers::{cache, error_status, validation_error, PathId, ValidatedJson};
+use crate::i18n::Locale;
+use crate::quick_add::{self, QuickAdd};
+use crate::repositories::label::{CreateLabel, LabelRepository};
+use crate::repositories::todo::{CreateTodo, TodoCounts, TodoQuery, TodoRepository, UpdateTodo};
+
+const NDJSON: &str = "application/x-ndjson";
+
+/// The [`TodoCounts`] of a `GET /todos` filter, whatever the page: the todos it matches, how
+/// many of them are completed and how many overdue.
+pub static X_TOTAL_COUNT: HeaderName = HeaderName::from_static("x-total-count");
+pub static X_TOTAL_COMPLETED: HeaderName = HeaderName::from_static("x-total-completed");
+pub static X_TOTAL_OVERDUE: HeaderName = HeaderName::from_static("x-total-overdue");
+/// `true` when a search stopped at its cap, so the page and counts may be missing matches.
+pub static X_TRUNCATED: HeaderName = HeaderName::from_static("x-truncated");
+
+/// `GET /todos/workload?date=2024-06-03&days=7`, read in `utc_offset_minutes` like quick add.
+#[derive(Debug, Clone, Deserialize, Validate)]
+pub struct WorkloadQuery {
+    /// The first day, today by default.
+    pub date: Option<NaiveDate>,
+    #[serde(default = "WorkloadQuery::default_days")]
+    #[validate(range(min = 1, max = 31))]
+    pub days: u64,
+    #[serde(default)]
+    #[validate(range(min = -1439, max = 1439))]
+    pub utc_offset_minutes: i32,
+    /// A day with more minutes than this estimated is overloaded.
+    #[serde(default = "WorkloadQuery::default_capacity")]
+    #[validate(range(min = 1))]
+    pub capacity_minutes: i64,
+}
+
+impl WorkloadQuery {
+    fn default_days() -> u64 {
+        7
+    }
+
+    /// A working day of eight hours.
+    fn default_capacity() -> i64 {
+        8 * 60
+    }
+}
+
+/// The open todos due on a day and the sum of their estimates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DayWorkload {
+    pub date: NaiveDate,
+    pub todos: usize,
+    pub estimate_minutes: i64,
+    /// Todos without an estimate, which the sum leaves out.
+    pub unestimated: usize,
+    pub overloaded: bool,
+}
+
+pub async fn create_todo<R: TodoRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    ValidatedJson(create_todo): ValidatedJson<CreateTodo>,
+) -> anyhow::Result<impl IntoResponse, StatusCode> {
+    let todo = repo
+        .create(create_todo)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::NOT_FOUND))?;
+    Ok((StatusCode::CREATED, Json(todo)))
+}
+
+/// Create a todo from free text, creating any `#label` that does not exist yet.
+pub async fn quick_add_todo<TR: TodoRepository, LR: LabelRepository>(
+    Extension(todo_repo): Extension<Arc<TR>>,
+    Extension(label_repo): Extension<Arc<LR>>,
+    headers: HeaderMap,
+    ValidatedJson(payload): ValidatedJson<QuickAdd>,
+) -> Result<impl IntoResponse, Response> {
+    let locale = Locale::from_headers(&headers);
+    let offset = FixedOffset::east_opt(payload.utc_offset_minutes * 60)
+        .expect("utc_offset_minutes is validated to be within a day");
+    let parsed = quick_add::parse(&payload.text, Utc::now().with_timezone(&offset));
+
+    // validate everything up front, so a bad todo does not leave new labels behind
+    let labels = parsed
+        .labels
+        .into_iter()
+        .map(|name| CreateLabel { name })
+        .collect::<Vec<CreateLabel>>();
+    for label in &labels {
+        label.validate().map_err(|e| validation_error(locale, &e))?;
+    }
+    let mut create_todo = CreateTodo {
+        text: parsed.text,
+        description: None,
+        labels: vec![],
+        due_at: parsed.due_at,
+        priority: parsed.priority,
+        starred: false,
+        color: None,
+        icon: None,
+        estimate_minutes: None,
+    };
+    create_todo
+        .validate()
+        .map_err(|e| validation_error(locale, &e))?;
+
+    for label in labels {
+        let (label, _) = label_repo
+            .find_or_create(label)
+            .await
+            .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR).into_response())?;
+        create_todo.labels.push(label.id);
+    }
+    let todo = todo_repo
+        .create(create_todo)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR).into_response())?;
+    Ok((StatusCode::CREATED, Json(todo)))
+}
+
+pub async fn find_todo<R: TodoRepository>(
+    Path(id): Path<PathId>,
+    Extension(repo): Extension<Arc<R>>,
+    headers: HeaderMap,
+) -> anyhow::Result<impl IntoResponse, StatusCode> {
+    let id = id.todo(&*repo).await?;
+    let todo = repo
+        .find(id)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    let updated_at = todo.updated_at;
+    Ok(cache::conditional(&headers, updated_at, Json(todo)))
+}
+
+pub async fn all_todo<R: TodoRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    page: Page,
+    Query(query): Query<TodoQuery>,
+) -> Result<Response, StatusCode> {
+    // a batch lookup by ids returns all of them in one page
+    let default_limit = match &query.ids {
+        Some(ids) if ids.len() as i64 > page.sizes.max => {
+            return Ok(pagination::out_of_range(
+                "ids",
+                ids.len() as i64,
+                page.sizes,
+            ));
+        }
+        Some(ids) => ids.len().max(1) as i64,
+        None => page.sizes.default,
+    };
+    let query = TodoQuery {
+        limit: Some(page.limit.unwrap_or(default_limit)),
+        offset: page.offset,
+        ..query
+    };
+    let first_page = query.offset.unwrap_or(0) == 0;
+    // fused, as an empty listing has already ended when it is chained below
+    let mut todos = repo.stream_counted(query).fuse();
+    // Most failures, like the database being down, surface on the first item, which still
+    // allows a proper status. A failure later on ends the response early with invalid JSON.
+    let (first, counts) = match todos.next().await {
+        Some(Ok((todo, counts))) => (Some(todo), Some(counts)),
+        Some(Err(e)) => {
+            tracing::error!("listing todos failed: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        // an empty page past the end says nothing about the todos before it
+        None => (None, first_page.then(TodoCounts::default)),
+    };
+    let mut headers = HeaderMap::new();
+    if let Some(counts) = counts {
+        headers.insert(X_TOTAL_COUNT.clone(), HeaderValue::from(counts.total));
+        headers.insert(
+            X_TOTAL_COMPLETED.clone(),
+            HeaderValue::from(counts.total_completed),
+        );
+        headers.insert(
+            X_TOTAL_OVERDUE.clone(),
+            HeaderValue::from(counts.total_overdue),
+        );
+        if counts.truncated {
+            headers.insert(X_TRUNCATED.clone(), HeaderValue::from_static("true"));
+        }
+    }
+    let todos = todos.map(|todo| todo.map(|(todo, _)| todo));
+    let items = stream::iter(first.map(Ok)).chain(todos).enumerate();
+    let body = stream::once(async { anyhow::Ok(b"[".to_vec()) })
+        .chain(items.map(|(i, todo)| {
+            let todo = todo.inspect_err(|e| tracing::error!("listing todos failed: {}", e))?;
+            let mut chunk = if i == 0 { vec![] } else { vec![b','] };
+            serde_json::to_writer(&mut chunk, &todo)?;
+            anyhow::Ok(chunk)
+        }))
+        .chain(stream::once(async { anyhow::Ok(b"]".to_vec()) }));
+    // No Last-Modified here: deleting a todo does not move max(updated_at) forward.
+    Ok((
+        StatusCode::OK,
+        [
+            (CONTENT_TYPE, mime::APPLICATION_JSON.as_ref()),
+            (CACHE_CONTROL, cache::REVALIDATE),
+        ],
+        headers,
+        Body::from_stream(body),
+    )
+        .into_response())
+}
+
+/// `GET /todos/workload`: every day from `date` on, with nothing due or not, so a day that
+/// is overloaded shows up before it comes.
+pub async fn todo_workload<R: TodoRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    headers: HeaderMap,
+    Query(query): Query<WorkloadQuery>,
+) -> Result<Json<Vec<DayWorkload>>, Response> {
+    let locale = Locale::from_headers(&headers);
+    query.validate().map_err(|e| validation_error(locale, &e))?;
+    let offset = FixedOffset::east_opt(query.utc_offset_minutes * 60)
+        .expect("utc_offset_minutes is validated to be within a day");
+    let first = query
+        .date
+        .unwrap_or_else(|| Utc::now().with_timezone(&offset).date_naive());
+    let start_of = |date: NaiveDate| -> DateTime<Utc> {
+        offset
+            .from_local_datetime(&date.and_time(NaiveTime::MIN))
+            .unwrap()
+            .with_timezone(&Utc)
+    };
+    let dates = (0..query.days)
+        .map(|i| first + Days::new(i))
+        .collect::<Vec<NaiveDate>>();
+    let todos = repo
+        .all(TodoQuery {
+            completed: Some(false),
+            due_after: Some(start_of(first)),
+            due_before: Some(start_of(first + Days::new(query.days))),
+            ..TodoQuery::default()
+        })
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR).into_response())?;
+
+    let mut workload = dates
+        .into_iter()
+        .map(|date| DayWorkload {
+            date,
+            todos: 0,
+            estimate_minutes: 0,
+            unestimated: 0,
+            overloaded: false,
+        })
+        .collect::<Vec<DayWorkload>>();
+    for todo in todos {
+        let Some(due_at) = todo.due_at else { continue };
+        let day = (due_at.with_timezone(&offset).date_naive() - first).num_days() as usize;
+        let Some(day) = workload.get_mut(day) else {
+            continue;
+        };
+        day.todos += 1;
+        match todo.estimate_minutes {
+            Some(minutes) => day.estimate_minutes += i64::from(minutes),
+            None => day.unestimated += 1,
+        }
+    }
+    for day in &mut workload {
+        day.overloaded = day.estimate_minutes > query.capacity_minutes;
+    }
+    Ok(Json(workload))
+}
+
+/// `GET /todos/starred`: the shortlist, paged and filtered like `GET /todos`.
+pub async fn starred_todos<R: TodoRepository>(
+    repo: Extension<Arc<R>>,
+    page: Page,
+    Query(query): Query<TodoQuery>,
+) -> anyhow::Result<impl IntoResponse, StatusCode> {
+    let query = TodoQuery {
+        starred: Some(true),
+        ..query
+    };
+    all_todo(repo, page, Query(query)).await
+}
+
+/// `GET /todos/stream`: every todo as newline-delimited JSON, streamed as it is read.
+/// A failure midway ends the response early, leaving an incomplete last line.
+pub async fn stream_todos<R: TodoRepository>(Extension(repo): Extension<Arc<R>>) -> Response {
+    let lines = repo.stream(TodoQuery::default()).map(|todo| {
+        let todo = todo.inspect_err(|e| tracing::error!("streaming todos failed: {}", e))?;
+        let mut line = serde_json::to_vec(&todo)?;
+        line.push(b'\n');
+        anyhow::Ok(line)
+    });
+    ([(CONTENT_TYPE, NDJSON)], Body::from_stream(lines)).into_response()
+}
+
+pub async fn next_todo<R: TodoRepository>(
+    Extension(repo): Extension<Arc<R>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let todo = repo
+        .next()
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    todo.map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+pub async fn update_todo<R: TodoRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    Path(id): Path<PathId>,
+    ValidatedJson(update_todo): ValidatedJson<UpdateTodo>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let id = id.todo(&*repo).await?;
+    let todo = repo
+        .update(id, update_todo)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::NOT_FOUND))?;
+    Ok((StatusCode::CREATED, Json(todo)))
+}
+
+pub async fn delete_todo<R: TodoRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    Path(id): Path<PathId>,
+) -> StatusCode {
+    let id = match id.todo(&*repo).await {
+        Ok(id) => id,
+        Err(status) => return status,
+    };
+    repo.delete(id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .unwrap_or_else(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))
+}
@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+/// A worker missing this many of its ticks is reported down.
+const STALE_AFTER_TICKS: u32 = 3;
+const MIN_STALE_AFTER: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub struct Health {
+    db_up: AtomicBool,
+    db_check: Mutex<DbCheck>,
+    workers: Mutex<BTreeMap<&'static str, Worker>>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct DbCheck {
+    latency: Option<Duration>,
+    migration_version: Option<i64>,
+    checked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+struct Worker {
+    interval: Duration,
+    last_tick: DateTime<Utc>,
+}
+
+impl Worker {
+    fn is_alive(&self, now: DateTime<Utc>) -> bool {
+        let stale_after = (self.interval * STALE_AFTER_TICKS).max(MIN_STALE_AFTER);
+        (now - self.last_tick).to_std().unwrap_or_default() <= stale_after
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Up,
+    Down,
+}
+
+impl From<bool> for Status {
+    fn from(up: bool) -> Self {
+        if up {
+            Status::Up
+        } else {
+            Status::Down
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Details {
+    status: Status,
+    database: DatabaseDetails,
+    workers: BTreeMap<&'static str, WorkerDetails>,
+}
+
+#[derive(Debug, Serialize)]
+struct DatabaseDetails {
+    status: Status,
+    latency_ms: Option<f64>,
+    /// The latest applied migration.
+    migration_version: Option<i64>,
+    checked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+struct WorkerDetails {
+    status: Status,
+    checked_at: DateTime<Utc>,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        // the server only starts after connecting to the database
+        Health {
+            db_up: AtomicBool::new(true),
+            db_check: Mutex::default(),
+            workers: Mutex::default(),
+        }
+    }
+}
+
+impl Health {
+    pub fn is_ready(&self) -> bool {
+        self.db_up.load(Ordering::Relaxed)
+    }
+
+    /// Returns the previous state.
+    fn set_db_up(&self, up: bool) -> bool {
+        self.db_up.swap(up, Ordering::Relaxed)
+    }
+
+    /// Track a background task that calls [`Health::tick`] every `interval`.
+    pub fn register_worker(&self, name: &'static str, interval: Duration) {
+        self.workers.lock().unwrap().insert(
+            name,
+            Worker {
+                interval,
+                last_tick: Utc::now(),
+            },
+        );
+    }
+
+    pub fn tick(&self, name: &'static str) {
+        if let Some(worker) = self.workers.lock().unwrap().get_mut(name) {
+            worker.last_tick = Utc::now();
+        }
+    }
+
+    fn details(&self) -> Details {
+        let now = Utc::now();
+        let db_check = self.db_check.lock().unwrap().clone();
+        let database = DatabaseDetails {
+            status: self.is_ready().into(),
+            latency_ms: db_check
+                .latency
+                .map(|latency| latency.as_secs_f64() * 1000.0),
+            migration_version: db_check.migration_version,
+            checked_at: db_check.checked_at,
+        };
+        let workers: BTreeMap<_, _> = self
+            .workers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, worker)| {
+                let details = WorkerDetails {
+                    status: worker.is_alive(now).into(),
+                    checked_at: worker.last_tick,
+                };
+                (*name, details)
+            })
+            .collect();
+        let up = database.status == Status::Up
+            && workers.values().all(|worker| worker.status == Status::Up);
+        Details {
+            status: up.into(),
+            database,
+            workers,
+        }
+    }
+}
+
+/// `GET /healthz` answers as long as the process serves requests,
+/// `GET /readyz` only while the database is reachable.
+/// `GET /healthz/details` reports each dependency and background worker for dashboards.
+pub fn routes(router: Router, health: Arc<Health>) -> Router {
+    router
+        .route("/healthz", get(|| async { StatusCode::OK }))
+        .route("/healthz/details", get(details))
+        .route("/readyz", get(readiness))
+        .layer(Extension(health))
+}
+
+async fn details(Extension(health): Extension<Arc<Health>>) -> impl IntoResponse {
+    let details = health.details();
+    let status = match details.status {
+        Status::Up => StatusCode::OK,
+        Status::Down => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (status, Json(details))
+}
+
+async fn readiness(Extension(health): Extension<Arc<Health>>) -> StatusCode {
+    if health.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+/// Ping the database every `interval`, publish pool statistics and flip readiness
+/// when connectivity changes.
+pub async fn monitor(pool: PgPool, health: Arc<Health>, interval: Duration) {
+    const WORKER: &str = "health_monitor";
+    health.register_worker(WORKER, interval);
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        health.tick(WORKER);
+        let started = Instant::now();
+        let result = tokio::time::timeout(interval, sqlx::query("select 1").execute(&pool)).await;
+        let elapsed = started.elapsed();
+
+        metrics::gauge!("db_pool_size").set(pool.size() as f64);
+        metrics::gauge!("db_pool_idle").set(pool.num_idle() as f64);
+        // acquiring a connection is part of the ping, so this also reflects pool wait time
+        metrics::histogram!("db_ping_duration_seconds").record(elapsed.as_secs_f64());
+
+        let up = matches!(result, Ok(Ok(_)));
+        if !up {
+            metrics::counter!("db_ping_failures_total").increment(1);
+        }
+        let migration_version = if up {
+            sqlx::query_scalar::<_, Option<i64>>(
+                "select max(version) from _sqlx_migrations where success",
+            )
+            .fetch_one(&pool)
+            .await
+            .ok()
+            .flatten()
+        } else {
+            None
+        };
+        *health.db_check.lock().unwrap() = DbCheck {
+            latency: up.then_some(elapsed),
+            migration_version,
+            checked_at: Some(Utc::now()),
+        };
+        let was_up = health.set_db_up(up);
+        match (was_up, up, result) {
+            (true, false, Ok(Err(e))) => tracing::error!("database became unreachable: {}", e),
+            (true, false, _) => tracing::error!("database ping timed out after {:?}", interval),
+            (false, true, _) => tracing::info!("database connectivity restored"),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::extract::Request;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn readiness_follows_database_state() {
+        let health = Arc::new(Health::default());
+        let app = routes(Router::new(), health.clone());
+        let readyz = || {
+            Request::builder()
+                .uri("/readyz")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let res = app.clone().oneshot(readyz()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        health.set_db_up(false);
+        let res = app.clone().oneshot(readyz()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let req = Request::builder()
+            .uri("/healthz")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn details_report_stale_workers() {
+        let health = Arc::new(Health::default());
+        health.register_worker("outbox_relay", Duration::from_secs(1));
+        let app = routes(Router::new(), health.clone());
+        let details = || {
+            Request::builder()
+                .uri("/healthz/details")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let res = app.clone().oneshot(details()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        health
+            .workers
+            .lock()
+            .unwrap()
+            .get_mut("outbox_relay")
+            .unwrap()
+            .last_tick -= chrono::Duration::minutes(5);
+        let res = app.oneshot(details()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["database"]["status"], "up");
+        assert_eq!(body["workers"]["outbox_relay"]["status"], "down");
+    }
+}
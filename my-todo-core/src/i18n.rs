@@ -0,0 +1,153 @@
+use axum::http::header::ACCEPT_LANGUAGE;
+use axum::http::HeaderMap;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    Ja,
+}
+
+impl Locale {
+    /// Pick the supported language with the highest `q` from `Accept-Language`, English otherwise.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let Some(accept_language) = headers
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return Locale::default();
+        };
+        accept_language
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.trim().split(';');
+                let tag = parts.next()?.trim().to_ascii_lowercase();
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                let locale = match tag.split('-').next()? {
+                    "en" => Locale::En,
+                    "ja" => Locale::Ja,
+                    _ => return None,
+                };
+                Some((locale, quality))
+            })
+            .filter(|(_, quality)| *quality > 0.0)
+            // max_by keeps the last of equal elements, so reverse to prefer the first listed
+            .rev()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(locale, _)| locale)
+            .unwrap_or_default()
+    }
+
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Ja => "ja",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    JsonParseError,
+    ValidationError,
+    TextLength,
+    DescriptionLength,
+    NameLength,
+    ColorFormat,
+    IconFormat,
+    EstimateRange,
+    DueOffsetRange,
+    TodoIdsLength,
+    LabelsLength,
+    ReadOnly,
+    Overloaded,
+    CsrfInvalid,
+}
+
+impl Message {
+    /// Validator error codes used in `#[validate(..., code = "...")]`.
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "text_length" => Some(Message::TextLength),
+            "description_length" => Some(Message::DescriptionLength),
+            "name_length" => Some(Message::NameLength),
+            "color_format" => Some(Message::ColorFormat),
+            "icon_format" => Some(Message::IconFormat),
+            "estimate_range" => Some(Message::EstimateRange),
+            "due_offset_range" => Some(Message::DueOffsetRange),
+            "todo_ids_length" => Some(Message::TodoIdsLength),
+            "labels_length" => Some(Message::LabelsLength),
+            _ => None,
+        }
+    }
+
+    pub fn text(&self, locale: Locale) -> &'static str {
+        match (self, locale) {
+            (Message::JsonParseError, Locale::En) => "Json parse error",
+            (Message::JsonParseError, Locale::Ja) => "JSONの解析に失敗しました",
+            (Message::ValidationError, Locale::En) => "Validation error",
+            (Message::ValidationError, Locale::Ja) => "入力内容が正しくありません",
+            (Message::TextLength, Locale::En) => "The text length is from 1 to 288 characters",
+            (Message::TextLength, Locale::Ja) => "テキストは1文字以上288文字以下で入力してください",
+            (Message::DescriptionLength, Locale::En) => "The description is up to 10000 characters",
+            (Message::DescriptionLength, Locale::Ja) => "説明は10000文字以下で入力してください",
+            (Message::NameLength, Locale::En) => "The name length is from 1 to 255 characters",
+            (Message::NameLength, Locale::Ja) => "名前は1文字以上255文字以下で入力してください",
+            (Message::ColorFormat, Locale::En) => "The color is a hex code like #ff8800",
+            (Message::ColorFormat, Locale::Ja) => "色は#ff8800のような16進数で入力してください",
+            (Message::IconFormat, Locale::En) => "The icon is a single emoji",
+            (Message::IconFormat, Locale::Ja) => "アイコンは絵文字1つで入力してください",
+            (Message::EstimateRange, Locale::En) => "The estimate is from 1 to 10080 minutes",
+            (Message::EstimateRange, Locale::Ja) => {
+                "見積もりは1分以上10080分以下で入力してください"
+            }
+            (Message::DueOffsetRange, Locale::En) => "The due offset is from 1 to 525600 minutes",
+            (Message::DueOffsetRange, Locale::Ja) => {
+                "期限までの時間は1分以上525600分以下で入力してください"
+            }
+            (Message::TodoIdsLength, Locale::En) => "Between 1 and 200 todos at a time",
+            (Message::TodoIdsLength, Locale::Ja) => {
+                "TODOは一度に1件以上200件以下で指定してください"
+            }
+            (Message::LabelsLength, Locale::En) => "At most 500 labels at a time",
+            (Message::LabelsLength, Locale::Ja) => "ラベルは一度に500件までです",
+            (Message::ReadOnly, Locale::En) => "Service is in read-only mode",
+            (Message::ReadOnly, Locale::Ja) => "メンテナンス中のため読み取り専用です",
+            (Message::Overloaded, Locale::En) => {
+                "Too many requests like this one, try again shortly"
+            }
+            (Message::Overloaded, Locale::Ja) => {
+                "混み合っています。しばらくしてから再度お試しください"
+            }
+            (Message::CsrfInvalid, Locale::En) => "CSRF token missing or invalid",
+            (Message::CsrfInvalid, Locale::Ja) => "CSRFトークンがないか、正しくありません",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn locale_of(accept_language: &'static str) -> Locale {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static(accept_language));
+        Locale::from_headers(&headers)
+    }
+
+    #[test]
+    fn negotiate_locale() {
+        assert_eq!(Locale::from_headers(&HeaderMap::new()), Locale::En);
+        assert_eq!(locale_of("ja"), Locale::Ja);
+        assert_eq!(locale_of("ja-JP,ja;q=0.9,en;q=0.8"), Locale::Ja);
+        assert_eq!(locale_of("en-US,ja;q=0.5"), Locale::En);
+        assert_eq!(locale_of("fr,ja;q=0.7,en;q=0.3"), Locale::Ja);
+        assert_eq!(locale_of("ja;q=0,de"), Locale::En);
+        assert_eq!(locale_of("en,ja"), Locale::En);
+    }
+}
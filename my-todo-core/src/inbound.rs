@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::extract::{DefaultBodyLimit, FromRequest, Multipart, Query, Request, State};
+use axum::http::header::{AUTHORIZATION, CONTENT_TYPE};
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Extension, Form, Json, Router};
+use serde::Deserialize;
+
+use crate::handlers::error_status;
+use crate::middleware::csrf::constant_time_eq;
+use crate::repositories::todo::{CreateTodo, TodoRepository};
+
+const NO_SUBJECT: &str = "(no subject)";
+pub(crate) const MAX_TEXT_CHARS: usize = 288;
+pub(crate) const MAX_DESCRIPTION_CHARS: usize = 10000;
+/// SendGrid accepts messages up to 30MB including attachments.
+const MAX_EMAIL_BYTES: usize = 30 * 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+/// Inbound mail webhooks (SendGrid Inbound Parse, Mailgun routes) under `/inbound`.
+/// Providers can't add headers to their requests, so besides `Authorization: Bearer <token>`
+/// the shared secret is accepted as `?token=<token>` in the configured webhook URL.
+pub fn routes<R: TodoRepository>(token: String, todo_repo: Arc<R>) -> Router {
+    let inbound = Router::new()
+        .route("/email", post(email::<R>))
+        .layer(Extension(todo_repo))
+        .layer(DefaultBodyLimit::max(MAX_EMAIL_BYTES))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(token),
+            require_token,
+        ));
+    Router::new().nest("/inbound", inbound)
+}
+
+async fn require_token(
+    State(token): State<Arc<String>>,
+    Query(query): Query<TokenQuery>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let bearer = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let authorized = bearer
+        .or(query.token.as_deref())
+        .is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes()));
+    if !authorized {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    next.run(req).await
+}
+
+/// The subject becomes the todo text, the plain text body its description.
+async fn email<R: TodoRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    req: Request,
+) -> Result<impl IntoResponse, StatusCode> {
+    let fields = form_fields(req).await?;
+    let todo = repo
+        .create(email_to_todo(&fields))
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((StatusCode::CREATED, Json(todo)))
+}
+
+/// SendGrid always posts `multipart/form-data`, Mailgun either that or a urlencoded form.
+async fn form_fields(req: Request) -> Result<HashMap<String, String>, StatusCode> {
+    let multipart = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("multipart/form-data"));
+    if !multipart {
+        let Form(fields) = Form::<HashMap<String, String>>::from_request(req, &())
+            .await
+            .map_err(|_| StatusCode::BAD_REQUEST)?;
+        return Ok(fields);
+    }
+
+    let mut multipart = Multipart::from_request(req, &())
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let mut fields = HashMap::new();
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?
+    {
+        // attachments are dropped
+        if field.file_name().is_some() {
+            continue;
+        }
+        let Some(name) = field.name().map(str::to_string) else {
+            continue;
+        };
+        let value = field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+        fields.insert(name, value);
+    }
+    Ok(fields)
+}
+
+fn email_to_todo(fields: &HashMap<String, String>) -> CreateTodo {
+    let subject = fields
+        .get("subject")
+        .map(|subject| subject.trim())
+        .filter(|subject| !subject.is_empty())
+        .unwrap_or(NO_SUBJECT);
+    // Mailgun's `stripped-text` leaves out quoted replies and the signature,
+    // `body-plain` is Mailgun's full body and `text` SendGrid's.
+    let body = ["stripped-text", "body-plain", "text"]
+        .iter()
+        .filter_map(|key| fields.get(*key))
+        .map(|body| body.trim())
+        .find(|body| !body.is_empty());
+    CreateTodo {
+        text: truncate(subject, MAX_TEXT_CHARS),
+        description: body.map(|body| truncate(body, MAX_DESCRIPTION_CHARS)),
+        labels: vec![],
+        due_at: None,
+        priority: None,
+        starred: false,
+        color: None,
+        icon: None,
+        estimate_minutes: None,
+    }
+}
+
+pub(crate) fn truncate(value: &str, max_chars: usize) -> String {
+    value.chars().take(max_chars).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::Method;
+    use tower::ServiceExt;
+
+    use crate::repositories::todo::test_inmemory_repo::TodoRepositoryMemory;
+
+    use super::*;
+
+    #[test]
+    fn prefer_stripped_text_for_description() {
+        let fields = HashMap::from([
+            ("subject".to_string(), "  Renew passport ".to_string()),
+            ("body-plain".to_string(), "Book it\n> quoted".to_string()),
+            ("stripped-text".to_string(), "Book it".to_string()),
+        ]);
+        let todo = email_to_todo(&fields);
+        assert_eq!(todo.text, "Renew passport");
+        assert_eq!(todo.description, Some("Book it".to_string()));
+
+        let todo = email_to_todo(&HashMap::from([("subject".to_string(), "x".repeat(300))]));
+        assert_eq!(todo.text.len(), MAX_TEXT_CHARS);
+        assert_eq!(todo.description, None);
+
+        assert_eq!(email_to_todo(&HashMap::new()).text, NO_SUBJECT);
+    }
+
+    #[tokio::test]
+    async fn email_requires_token() {
+        let repo = TodoRepositoryMemory::new();
+        let app = routes("secret".to_string(), Arc::new(repo.clone()));
+        let post = |uri: &str, content_type: &str, body: &'static str| {
+            Request::builder()
+                .uri(uri)
+                .method(Method::POST)
+                .header(CONTENT_TYPE, content_type)
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let res = app
+            .clone()
+            .oneshot(post(
+                "/inbound/email?token=wrong",
+                "application/x-www-form-urlencoded",
+                "subject=Pay+rent",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = app
+            .clone()
+            .oneshot(post(
+                "/inbound/email?token=secret",
+                "application/x-www-form-urlencoded",
+                "subject=Pay+rent&body-plain=before+the+1st",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+
+        let multipart = "--b\r\n\
+            Content-Disposition: form-data; name=\"subject\"\r\n\r\n\
+            Call the bank\r\n\
+            --b\r\n\
+            Content-Disposition: form-data; name=\"text\"\r\n\r\n\
+            about the card\r\n\
+            --b\r\n\
+            Content-Disposition: form-data; name=\"attachment1\"; filename=\"a.txt\"\r\n\r\n\
+            ignored\r\n\
+            --b--\r\n";
+        let res = app
+            .oneshot(post(
+                "/inbound/email?token=secret",
+                "multipart/form-data; boundary=b",
+                multipart,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+
+        let todo = repo.find(2).await.unwrap();
+        assert_eq!(todo.text, "Call the bank");
+        assert_eq!(todo.description, Some("about the card".to_string()));
+    }
+}
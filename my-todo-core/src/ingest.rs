@@ -0,0 +1,466 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, post};
+use axum::{Extension, Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::handlers::{error_status, ValidatedJson};
+use crate::inbound::{truncate, MAX_DESCRIPTION_CHARS, MAX_TEXT_CHARS};
+use crate::repositories::ingest_hook::{self, IngestHook, IngestMapping};
+use crate::repositories::label::{CreateLabel, LabelRepository};
+use crate::repositories::todo::{CreateTodo, TodoRepository};
+
+const MAX_LABEL_CHARS: usize = 255;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Key(String),
+    Index(usize),
+    /// Every element of an array or member of an object.
+    Wildcard,
+}
+
+/// The part of JSONPath hook mappings need: `$` followed by `.key` or `['key']`, `[0]`, and
+/// `.*` or `[*]`, e.g. `$.alerts[0].labels.*`. No filters, slices or recursive descent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath(Vec<Step>);
+
+impl FromStr for JsonPath {
+    type Err = String;
+
+    fn from_str(path: &str) -> Result<Self, String> {
+        let rest = path
+            .trim()
+            .strip_prefix('$')
+            .ok_or_else(|| format!("{} doesn't start with $", path))?;
+        let mut chars = rest.chars().peekable();
+        let mut steps = vec![];
+        while let Some(c) = chars.next() {
+            let step = match c {
+                '.' => {
+                    let mut key = String::new();
+                    while let Some(c) = chars.next_if(|c| *c != '.' && *c != '[') {
+                        key.push(c);
+                    }
+                    match key.as_str() {
+                        "" => return Err(format!("{} has an empty key", path)),
+                        "*" => Step::Wildcard,
+                        _ => Step::Key(key),
+                    }
+                }
+                '[' => {
+                    let inner = chars.by_ref().take_while(|c| *c != ']').collect::<String>();
+                    let inner = inner.trim();
+                    let quoted = ['\'', '"'].iter().any(|quote| {
+                        inner.len() >= 2 && inner.starts_with(*quote) && inner.ends_with(*quote)
+                    });
+                    if inner == "*" {
+                        Step::Wildcard
+                    } else if quoted {
+                        Step::Key(inner[1..inner.len() - 1].to_string())
+                    } else {
+                        let index = inner
+                            .parse()
+                            .map_err(|_| format!("{} has an invalid index [{}]", path, inner))?;
+                        Step::Index(index)
+                    }
+                }
+                c => return Err(format!("{} has an unexpected {:?}", path, c)),
+            };
+            steps.push(step);
+        }
+        Ok(JsonPath(steps))
+    }
+}
+
+impl JsonPath {
+    /// Everything the path selects in `value`, in document order.
+    pub fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        self.0.iter().fold(vec![value], |selected, step| {
+            selected
+                .into_iter()
+                .flat_map(|value| match (step, value) {
+                    (Step::Key(key), Value::Object(members)) => {
+                        members.get(key).into_iter().collect()
+                    }
+                    (Step::Index(i), Value::Array(items)) => items.get(*i).into_iter().collect(),
+                    (Step::Wildcard, Value::Array(items)) => items.iter().collect(),
+                    (Step::Wildcard, Value::Object(members)) => members.values().collect(),
+                    _ => vec![],
+                })
+                .collect()
+        })
+    }
+}
+
+/// An [`IngestMapping`] with its paths parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Mapping {
+    text: JsonPath,
+    description: Option<JsonPath>,
+    labels: Option<JsonPath>,
+}
+
+impl TryFrom<&IngestMapping> for Mapping {
+    type Error = String;
+
+    fn try_from(mapping: &IngestMapping) -> Result<Self, String> {
+        let optional = |path: &Option<String>| path.as_deref().map(str::parse).transpose();
+        Ok(Mapping {
+            text: mapping.text.parse()?,
+            description: optional(&mapping.description)?,
+            labels: optional(&mapping.labels)?,
+        })
+    }
+}
+
+/// A string, number or boolean as text, `None` for anything else or blank strings.
+fn scalar(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(text) => text.trim().to_string(),
+        Value::Number(number) => number.to_string(),
+        Value::Bool(flag) => flag.to_string(),
+        _ => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+impl Mapping {
+    /// The todo `payload` maps to, without labels, and the names of its labels. `Err` names
+    /// the path of the text when it selects nothing usable.
+    fn apply(&self, payload: &Value) -> Result<(CreateTodo, Vec<String>), String> {
+        let first = |path: &JsonPath| path.select(payload).into_iter().find_map(scalar);
+        let text = first(&self.text).ok_or_else(|| "the text path selected no text".to_string())?;
+        let description = self.description.as_ref().and_then(first);
+        let mut labels: Vec<String> = vec![];
+        let selected = self
+            .labels
+            .as_ref()
+            .map(|path| path.select(payload))
+            .unwrap_or_default();
+        for value in selected {
+            // a path to an array of names is as good as one to each of them
+            let names = match value {
+                Value::Array(items) => items.iter().filter_map(scalar).collect(),
+                value => scalar(value).into_iter().collect::<Vec<String>>(),
+            };
+            for name in names {
+                let name = truncate(&name, MAX_LABEL_CHARS);
+                if !labels.iter().any(|label| label.eq_ignore_ascii_case(&name)) {
+                    labels.push(name);
+                }
+            }
+        }
+        let todo = CreateTodo::builder(truncate(&text, MAX_TEXT_CHARS));
+        let todo = match description {
+            Some(description) => todo.description(truncate(&description, MAX_DESCRIPTION_CHARS)),
+            None => todo,
+        };
+        Ok((todo.build(), labels))
+    }
+}
+
+/// `POST /hooks` body.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct CreateIngestHook {
+    #[validate(length(min = 1, max = 255, code = "name_length"))]
+    pub name: String,
+    pub mapping: IngestMapping,
+}
+
+/// A new hook. The token is only ever returned here.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreatedIngestHook {
+    #[serde(flatten)]
+    pub hook: IngestHook,
+    pub token: String,
+    /// `/hooks/ingest/{token}`
+    pub path: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Unmapped {
+    message: String,
+}
+
+/// Managing the hooks other tools post to: `POST /hooks` creates one, `GET /hooks` lists them
+/// without their tokens and `DELETE /hooks/:id` deletes one. Posting to the hooks themselves
+/// is [`webhook`].
+pub fn routes(pool: PgPool) -> Router {
+    Router::new()
+        .route("/hooks", post(create_hook).get(all_hooks))
+        .route("/hooks/:id", delete(delete_hook))
+        .layer(Extension(pool))
+}
+
+async fn create_hook(
+    Extension(pool): Extension<PgPool>,
+    ValidatedJson(payload): ValidatedJson<CreateIngestHook>,
+) -> Result<Response, StatusCode> {
+    if let Err(message) = Mapping::try_from(&payload.mapping) {
+        return Ok((StatusCode::BAD_REQUEST, message).into_response());
+    }
+    let (hook, token) = ingest_hook::create(&pool, &payload.name, &payload.mapping)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    let path = format!("/hooks/ingest/{}", token);
+    let created = CreatedIngestHook { hook, token, path };
+    Ok((StatusCode::CREATED, Json(created)).into_response())
+}
+
+async fn all_hooks(Extension(pool): Extension<PgPool>) -> Result<impl IntoResponse, StatusCode> {
+    let hooks = ingest_hook::all(&pool)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok(Json(hooks))
+}
+
+async fn delete_hook(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, StatusCode> {
+    ingest_hook::delete(&pool, id)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?
+        .then_some(StatusCode::NO_CONTENT)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+struct Ingest<TR, LR> {
+    pool: PgPool,
+    todo_repo: Arc<TR>,
+    label_repo: Arc<LR>,
+}
+
+/// `POST /hooks/ingest/:token` creates a todo from whatever JSON is posted, as the hook's
+/// [`IngestMapping`] says, so tools like GitHub or alerting can create todos without code of
+/// their own. The token in the path is the only credential, since most tools can't add
+/// headers. A payload the mapping finds no text in is refused with 422, e.g. GitHub's `ping`.
+pub fn webhook<TR: TodoRepository, LR: LabelRepository>(
+    pool: PgPool,
+    todo_repo: Arc<TR>,
+    label_repo: Arc<LR>,
+) -> Router {
+    let ingest = Arc::new(Ingest {
+        pool,
+        todo_repo,
+        label_repo,
+    });
+    Router::new()
+        .route("/hooks/ingest/:token", post(ingest_todo::<TR, LR>))
+        .layer(Extension(ingest))
+}
+
+async fn ingest_todo<TR: TodoRepository, LR: LabelRepository>(
+    Extension(ingest): Extension<Arc<Ingest<TR, LR>>>,
+    Path(token): Path<String>,
+    Json(payload): Json<Value>,
+) -> Result<Response, StatusCode> {
+    let internal = |e: anyhow::Error| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR);
+    let hook = ingest_hook::find(&ingest.pool, &token)
+        .await
+        .map_err(internal)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    // checked when the hook was created
+    let mapping = Mapping::try_from(&hook.mapping).map_err(|message| {
+        tracing::error!("mapping of ingest hook {} is invalid: {}", hook.id, message);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let (mut todo, names) = match mapping.apply(&payload) {
+        Ok(mapped) => mapped,
+        Err(message) => {
+            let body = Json(Unmapped { message });
+            return Ok((StatusCode::UNPROCESSABLE_ENTITY, body).into_response());
+        }
+    };
+    for name in names {
+        let (label, _) = ingest
+            .label_repo
+            .find_or_create(CreateLabel { name })
+            .await
+            .map_err(internal)?;
+        todo.labels.push(label.id);
+    }
+    let todo = ingest.todo_repo.create(todo).await.map_err(internal)?;
+    metrics::counter!("todos_ingested_total", "hook" => hook.name).increment(1);
+    Ok((StatusCode::CREATED, Json(todo)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn path(path: &str) -> JsonPath {
+        path.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_and_select_paths() {
+        let payload = json!({
+            "issue": {
+                "title": "Crash on save",
+                "labels": [{"name": "bug"}, {"name": "p1"}],
+                "user": {"login": "octocat"},
+            },
+            "alerts": [{"labels": {"severity": "critical", "team": "web"}}],
+        });
+        let strings = |path: &str| {
+            self::path(path)
+                .select(&payload)
+                .into_iter()
+                .filter_map(scalar)
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(strings("$.issue.title"), ["Crash on save"]);
+        assert_eq!(strings("$['issue'][\"user\"].login"), ["octocat"]);
+        assert_eq!(strings("$.issue.labels[*].name"), ["bug", "p1"]);
+        assert_eq!(strings("$.issue.labels[1].name"), ["p1"]);
+        assert_eq!(strings("$.alerts[0].labels.*"), ["critical", "web"]);
+        assert!(strings("$.issue.missing").is_empty());
+        assert!(strings("$.issue.labels[5]").is_empty());
+        assert_eq!(path("$").select(&payload), [&payload]);
+
+        for invalid in ["issue.title", "$.", "$.labels[x]", "$issue"] {
+            assert!(invalid.parse::<JsonPath>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn map_payload_to_todo() {
+        let mapping = Mapping::try_from(&IngestMapping {
+            text: "$.alert.summary".to_string(),
+            description: Some("$.alert.details".to_string()),
+            labels: Some("$.alert.tags".to_string()),
+        })
+        .unwrap();
+        let (todo, labels) = mapping
+            .apply(&json!({
+                "alert": {
+                    "summary": "  Disk 95% full ",
+                    "details": "on db-1",
+                    "tags": ["ops", "Ops", "disk", 3, null],
+                },
+            }))
+            .unwrap();
+        assert_eq!(todo.text, "Disk 95% full");
+        assert_eq!(todo.description, Some("on db-1".to_string()));
+        assert_eq!(labels, ["ops", "disk", "3"]);
+
+        let (todo, labels) = mapping
+            .apply(&json!({"alert": {"summary": "x".repeat(300)}}))
+            .unwrap();
+        assert_eq!(todo.text.len(), MAX_TEXT_CHARS);
+        assert_eq!((todo.description, labels), (None, vec![]));
+
+        assert!(mapping.apply(&json!({"zen": "Keep it simple."})).is_err());
+        assert!(mapping.apply(&json!({"alert": {"summary": " "}})).is_err());
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "db-test")]
+mod test_psql_repo {
+    use axum::body::Body;
+    use axum::http::header::CONTENT_TYPE;
+    use axum::http::{Method, Request};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::repositories::test_db::TestDb;
+
+    async fn send(app: &Router, method: Method, uri: &str, body: Value) -> (StatusCode, Value) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let status = res.status();
+        let body = axum::body::to_bytes(res.into_body(), 100_000)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn create_todos_from_posted_json() {
+        let db = TestDb::new().await;
+        let (todo_repo, label_repo) = (db.todo_repo(), db.label_repo());
+        label_repo
+            .create(CreateLabel {
+                name: "Bug".to_string(),
+            })
+            .await
+            .unwrap();
+        let app = routes(db.pool.clone()).merge(webhook(
+            db.pool.clone(),
+            Arc::new(todo_repo.clone()),
+            Arc::new(label_repo.clone()),
+        ));
+
+        let hook = serde_json::json!({
+            "name": "github issues",
+            "mapping": {
+                "text": "$.issue.title",
+                "description": "$.issue.body",
+                "labels": "$.issue.labels[*].name",
+            },
+        });
+        let (status, created) = send(&app, Method::POST, "/hooks", hook.clone()).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let created: CreatedIngestHook = serde_json::from_value(created).unwrap();
+        assert_eq!(created.hook.name, "github issues");
+        assert_eq!(created.path, format!("/hooks/ingest/{}", created.token));
+        let (status, hooks) = send(&app, Method::GET, "/hooks", Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(hooks, serde_json::json!([created.hook]));
+
+        let issue = serde_json::json!({
+            "action": "opened",
+            "issue": {
+                "title": "Crash on save",
+                "body": "Steps to reproduce",
+                "labels": [{"name": "bug"}, {"name": "needs triage"}],
+            },
+        });
+        let (status, todo) = send(&app, Method::POST, &created.path, issue.clone()).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let todo = todo_repo
+            .find(todo["id"].as_i64().unwrap() as i32)
+            .await
+            .unwrap();
+        assert_eq!(todo.text, "Crash on save");
+        assert_eq!(todo.description, Some("Steps to reproduce".to_string()));
+        let names = todo.labels.iter().map(|label| label.name.as_str());
+        // existing labels are matched regardless of case
+        assert_eq!(names.collect::<Vec<_>>(), ["Bug", "needs triage"]);
+
+        let ping = serde_json::json!({"zen": "Keep it logically awesome."});
+        let (status, _) = send(&app, Method::POST, &created.path, ping).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (status, _) = send(&app, Method::POST, "/hooks/ingest/unknown", issue.clone()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let invalid = serde_json::json!({"name": "broken", "mapping": {"text": "issue.title"}});
+        let (status, _) = send(&app, Method::POST, "/hooks", invalid).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let uri = format!("/hooks/{}", created.hook.id);
+        let (status, _) = send(&app, Method::DELETE, &uri, Value::Null).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&app, Method::POST, &created.path, issue).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&app, Method::DELETE, &uri, Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
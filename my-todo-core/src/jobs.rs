@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use serde::Serialize;
+use sqlx::types::Json;
+use sqlx::{FromRow, PgConnection, PgPool};
+
+use crate::config::JobsConfig;
+use crate::cron::Cron;
+use crate::health::Health;
+use crate::repositories::RepositoryError;
+
+const RETRY_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(3600);
+/// How long a worker owns a claimed job. Jobs running longer may run twice, so handlers should
+/// be idempotent.
+const LEASE: Duration = Duration::from_secs(600);
+
+type Handler =
+    Arc<dyn Fn(serde_json::Value) -> BoxFuture<'static, anyhow::Result<()>> + Send + Sync>;
+
+/// When a recurring job is queued.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(Cron),
+}
+
+#[derive(Debug, FromRow)]
+struct JobRow {
+    id: i64,
+    kind: String,
+    payload: Json<serde_json::Value>,
+    attempts: i32,
+}
+
+/// Queue `kind` to run with `payload`, on `conn` so it commits together with the change that
+/// caused it.
+pub async fn enqueue(
+    conn: &mut PgConnection,
+    kind: &str,
+    payload: &impl Serialize,
+) -> Result<i64, RepositoryError> {
+    let payload =
+        serde_json::to_value(payload).map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+    let id = sqlx::query_scalar(r#"insert into jobs (kind, payload) values ($1, $2) returning id"#)
+        .bind(kind)
+        .bind(Json(payload))
+        .fetch_one(conn)
+        .await?;
+    Ok(id)
+}
+
+/// Persistent background jobs: handlers by kind, recurring schedules and a pool of workers.
+///
+/// Every job runs at least once. A failing or panicking job is retried with exponential
+/// backoff; after `config.max_attempts` it is dead-lettered and stays in the table with its
+/// last error.
+pub struct Jobs {
+    pool: PgPool,
+    config: JobsConfig,
+    handlers: HashMap<&'static str, Handler>,
+    recurring: Vec<(&'static str, Schedule)>,
+}
+
+impl Jobs {
+    pub fn new(pool: PgPool, config: JobsConfig) -> Self {
+        Jobs {
+            pool,
+            config,
+            handlers: HashMap::new(),
+            recurring: vec![],
+        }
+    }
+
+    pub fn register<F, Fut>(mut self, kind: &'static str, handler: F) -> Self
+    where
+        F: Fn(serde_json::Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.handlers
+            .insert(kind, Arc::new(move |payload| handler(payload).boxed()));
+        self
+    }
+
+    /// Queue `kind` on `schedule`, unless a run of it is still pending.
+    pub fn recurring(mut self, kind: &'static str, schedule: Schedule) -> Self {
+        self.recurring.push((kind, schedule));
+        self
+    }
+
+    /// Start the workers and schedules. Returns right away without any registered handler.
+    pub fn start(self, health: Arc<Health>) {
+        const WORKER: &str = "jobs";
+        if self.handlers.is_empty() {
+            return;
+        }
+        let jobs = Arc::new(self);
+        health.register_worker(WORKER, jobs.config.poll_interval);
+        for (kind, schedule) in &jobs.recurring {
+            tokio::spawn(run_schedule(jobs.pool.clone(), kind, schedule.clone()));
+        }
+        for _ in 0..jobs.config.workers {
+            let jobs = jobs.clone();
+            let health = health.clone();
+            tokio::spawn(async move {
+                loop {
+                    health.tick(WORKER);
+                    match jobs.run_next().await {
+                        Ok(true) => {}
+                        Ok(false) => tokio::time::sleep(jobs.config.poll_interval).await,
+                        Err(e) => {
+                            tracing::error!("running jobs failed: {}", e);
+                            tokio::time::sleep(jobs.config.poll_interval).await;
+                        }
+                    }
+                }
+            });
+        }
+    }
+
+    /// `false` when no job was due.
+    async fn run_next(&self) -> anyhow::Result<bool> {
+        let job = sqlx::query_as::<_, JobRow>(
+            r#"
+            update jobs
+            set attempts = attempts + 1, locked_until = now() + make_interval(secs => $1)
+            where id = (select id
+                        from jobs
+                        where dead_lettered_at is null
+                          and run_at <= now()
+                          and (locked_until is null or locked_until < now())
+                        order by run_at, id
+                        limit 1 for update skip locked)
+            returning id, kind, payload, attempts
+            "#,
+        )
+        .bind(LEASE.as_secs_f64())
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(job) = job else {
+            return Ok(false);
+        };
+
+        let result = match self.handlers.get(job.kind.as_str()) {
+            // a task of its own, so a panic fails the job instead of the worker
+            Some(handler) => match tokio::spawn(handler(job.payload.0.clone())).await {
+                Ok(result) => result,
+                Err(e) => Err(anyhow::anyhow!("job panicked: {}", e)),
+            },
+            None => Err(anyhow::anyhow!("no handler for job kind [{}]", job.kind)),
+        };
+        match result {
+            Ok(()) => {
+                sqlx::query(r#"delete from jobs where id = $1"#)
+                    .bind(job.id)
+                    .execute(&self.pool)
+                    .await?;
+                metrics::counter!("jobs_succeeded_total", "kind" => job.kind).increment(1);
+            }
+            Err(e) => self.fail(job, e.to_string()).await?,
+        }
+        Ok(true)
+    }
+
+    async fn fail(&self, job: JobRow, error: String) -> anyhow::Result<()> {
+        metrics::counter!("jobs_failed_total", "kind" => job.kind.clone()).increment(1);
+        if job.attempts < self.config.max_attempts {
+            let backoff = RETRY_BACKOFF
+                .saturating_mul(2u32.saturating_pow(job.attempts as u32 - 1))
+                .min(MAX_RETRY_BACKOFF);
+            tracing::warn!(
+                "job {} ({}) failed (attempt {}), retrying in {:?}: {}",
+                job.id,
+                job.kind,
+                job.attempts,
+                backoff,
+                error
+            );
+            sqlx::query(
+                r#"
+                update jobs
+                set last_error = $2, locked_until = null, run_at = now() + make_interval(secs => $3)
+                where id = $1
+                "#,
+            )
+            .bind(job.id)
+            .bind(&error)
+            .bind(backoff.as_secs_f64())
+            .execute(&self.pool)
+            .await?;
+            return Ok(());
+        }
+
+        tracing::error!(
+            "dead-lettering job {} ({}) after {} attempts: {}",
+            job.id,
+            job.kind,
+            job.attempts,
+            error
+        );
+        sqlx::query(
+            r#"
+            update jobs set last_error = $2, locked_until = null, dead_lettered_at = now()
+            where id = $1
+            "#,
+        )
+        .bind(job.id)
+        .bind(&error)
+        .execute(&self.pool)
+        .await?;
+        metrics::counter!("jobs_dead_letters_total", "kind" => job.kind).increment(1);
+        Ok(())
+    }
+}
+
+async fn run_schedule(pool: PgPool, kind: &'static str, schedule: Schedule) {
+    match schedule {
+        Schedule::Every(interval) => {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                enqueue_scheduled(&pool, kind).await;
+            }
+        }
+        Schedule::Cron(cron) => loop {
+            let Some(next) = cron.next_after(Utc::now()) else {
+                tracing::warn!("job {} never runs, [{}] never fires", kind, cron);
+                return;
+            };
+            tokio::time::sleep((next - Utc::now()).to_std().unwrap_or_default()).await;
+            enqueue_scheduled(&pool, kind).await;
+        },
+    }
+}
+
+async fn enqueue_scheduled(pool: &PgPool, kind: &str) {
+    if let Err(e) = enqueue_recurring(pool, kind).await {
+        tracing::error!("scheduling job {} failed: {}", kind, e);
+    }
+}
+
+/// The kind doubles as dedupe key, so instances scheduling the same job queue it once.
+async fn enqueue_recurring(pool: &PgPool, kind: &str) -> Result<(), RepositoryError> {
+    sqlx::query(
+        r#"
+        insert into jobs (kind, payload, dedupe_key) values ($1, '{}', $1)
+        on conflict (dedupe_key) where dead_lettered_at is null do nothing
+        "#,
+    )
+    .bind(kind)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+#[cfg(feature = "db-test")]
+mod test_psql_repo {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::repositories::test_db::TestDb;
+
+    #[tokio::test]
+    async fn retries_then_dead_letters() {
+        let db = TestDb::new().await;
+        let calls = Arc::new(AtomicUsize::new(0));
+        let jobs = Jobs::new(
+            db.pool.clone(),
+            JobsConfig {
+                max_attempts: 2,
+                ..JobsConfig::default()
+            },
+        )
+        .register("flaky", {
+            let calls = calls.clone();
+            move |payload| {
+                let calls = calls.clone();
+                async move {
+                    assert_eq!(payload["n"], 1);
+                    if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                        anyhow::bail!("first attempt fails");
+                    }
+                    Ok(())
+                }
+            }
+        })
+        .register("panics", |_| async { panic!("boom") });
+
+        let mut conn = db.pool.acquire().await.unwrap();
+        enqueue(&mut conn, "flaky", &serde_json::json!({"n": 1}))
+            .await
+            .unwrap();
+        enqueue(&mut conn, "panics", &()).await.unwrap();
+        // recurring jobs are queued once
+        enqueue_recurring(&db.pool, "panics").await.unwrap();
+        enqueue_recurring(&db.pool, "panics").await.unwrap();
+        let pending = || async {
+            sqlx::query_as::<_, (String, i32, bool)>(
+                "select kind, attempts, dead_lettered_at is not null from jobs order by id",
+            )
+            .fetch_all(&db.pool)
+            .await
+            .unwrap()
+        };
+        assert_eq!(pending().await.len(), 3);
+
+        while jobs.run_next().await.unwrap() {}
+        // the retry is backed off
+        sqlx::query("update jobs set run_at = now()")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        while jobs.run_next().await.unwrap() {}
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            pending().await,
+            vec![
+                ("panics".to_string(), 2, true),
+                ("panics".to_string(), 2, true)
+            ]
+        );
+    }
+}
@@ -0,0 +1,1084 @@
+use std::sync::Arc;
+
+use axum::extract::Extension;
+use axum::routing::{delete, get, post};
+use axum::Router;
+
+use crate::handlers::label::{
+    all_label, archive_label, assign_label, create_label, delete_label, export_labels,
+    find_or_create_label, import_labels, label_defaults, set_label_defaults, unarchive_label,
+    unassign_label,
+};
+use crate::handlers::todo::{
+    all_todo, create_todo, delete_todo, find_todo, next_todo, quick_add_todo, starred_todos,
+    stream_todos, todo_workload, update_todo,
+};
+use crate::middleware::json_api;
+use crate::repositories::label::LabelRepository;
+use crate::repositories::todo::TodoRepository;
+
+pub mod admin;
+pub mod config;
+pub mod cron;
+pub mod diagnostics;
+pub mod events;
+pub mod fixtures;
+pub mod handlers;
+pub mod health;
+pub mod i18n;
+pub mod inbound;
+pub mod ingest;
+pub mod jobs;
+pub mod middleware;
+pub mod nudge;
+pub mod preflight;
+pub mod public_id;
+pub mod purge;
+pub mod quick_add;
+pub mod reload;
+pub mod reports;
+pub mod repositories;
+pub mod revisions;
+pub mod share;
+pub mod slo;
+pub mod static_files;
+pub mod telemetry;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+pub mod ui;
+pub mod weekly;
+
+async fn root() -> &'static str {
+    "Hello, world!"
+}
+
+/// The todo and label API. Embedders can merge it into their own router; the server adds
+/// health, CSRF, admin and telemetry layers on top.
+pub fn create_app<TR, LR>(todo_repo: TR, label_repo: LR) -> Router
+where
+    TR: TodoRepository,
+    LR: LabelRepository,
+{
+    Router::new()
+        .route("/", get(root))
+        .route("/todos", post(create_todo::<TR>).get(all_todo::<TR>))
+        .route("/todos/next", get(next_todo::<TR>))
+        .route("/todos/starred", get(starred_todos::<TR>))
+        .route("/todos/stream", get(stream_todos::<TR>))
+        .route("/todos/workload", get(todo_workload::<TR>))
+        .route("/todos/quick", post(quick_add_todo::<TR, LR>))
+        .route(
+            "/todos/:id",
+            get(find_todo::<TR>)
+                .delete(delete_todo::<TR>)
+                .patch(update_todo::<TR>),
+        )
+        .route(
+            "/label",
+            post(create_label::<LR>)
+                .get(all_label::<LR>)
+                .put(find_or_create_label::<LR>),
+        )
+        .route("/label/export", get(export_labels::<LR>))
+        .route("/label/import", post(import_labels::<LR>))
+        .route("/label/:id", delete(delete_label::<LR>))
+        .route("/label/:id/assign", post(assign_label::<LR>))
+        .route("/label/:id/unassign", post(unassign_label::<LR>))
+        .route("/label/:id/archive", post(archive_label::<LR>))
+        .route("/label/:id/unarchive", post(unarchive_label::<LR>))
+        .route(
+            "/label/:id/defaults",
+            get(label_defaults::<LR>).put(set_label_defaults::<LR>),
+        )
+        .layer(axum::middleware::from_fn(json_api::negotiate))
+        .layer(Extension(Arc::new(todo_repo)))
+        .layer(Extension(Arc::new(label_repo)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::response::Response;
+    use axum::Extension;
+    use axum::{
+        body::Body,
+        http::{Method, Request},
+    };
+    use hyper::header::{
+        ACCEPT, ACCEPT_LANGUAGE, CACHE_CONTROL, CONTENT_LANGUAGE, CONTENT_TYPE, IF_MODIFIED_SINCE,
+        LAST_MODIFIED, VARY,
+    };
+    use hyper::StatusCode;
+    use mime::APPLICATION_JSON;
+    use tower::ServiceExt;
+
+    use crate::create_app;
+    use crate::handlers::label::LabelImport;
+    use crate::handlers::pagination::PageSizes;
+    use crate::handlers::todo::{DayWorkload, X_TOTAL_COMPLETED, X_TOTAL_COUNT, X_TOTAL_OVERDUE};
+    use crate::middleware::json_api;
+    use crate::repositories::label::test_inmemory_repo::LabelRepositoryForMemory;
+    use crate::repositories::label::{
+        CreateLabel, ExportedLabel, Label, LabelDefaults, LabelExport, LabelRepository,
+    };
+    use crate::repositories::mock::{MockLabelRepository, MockTodoRepository};
+    use crate::repositories::todo::{
+        test_inmemory_repo::TodoRepositoryMemory, CreateTodo, Priority, TodoEntity, TodoRepository,
+        UpdateTodo,
+    };
+    use crate::repositories::RepositoryError;
+
+    // Test utilities
+
+    struct RequestBuilder {
+        uri: String,
+        method: Method,
+    }
+
+    impl RequestBuilder {
+        fn new(uri: &str, method: Method) -> RequestBuilder {
+            RequestBuilder {
+                uri: uri.to_string(),
+                method,
+            }
+        }
+
+        fn with_json_string(self, json_string: String) -> Request<Body> {
+            Request::builder()
+                .uri(self.uri)
+                .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+                .method(self.method)
+                .body(Body::from(json_string))
+                .unwrap()
+        }
+
+        fn with_empty(&self) -> Request<Body> {
+            Request::builder()
+                .uri(self.uri.as_str())
+                .method(self.method.as_ref())
+                .body(Body::empty())
+                .unwrap()
+        }
+    }
+
+    async fn res_to_todo(res: Response) -> TodoEntity {
+        let bytes = axum::body::to_bytes(res.into_body(), 10_000).await.unwrap();
+        let body: String = String::from_utf8(bytes.to_vec()).unwrap();
+        let todo: TodoEntity = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("cannot convert Todo instance. body: {}", body));
+        todo
+    }
+
+    async fn res_to_todos(res: Response) -> Vec<TodoEntity> {
+        let bytes = axum::body::to_bytes(res.into_body(), 10_000).await.unwrap();
+        let body = String::from_utf8(bytes.to_vec()).unwrap();
+
+        let todos: Vec<TodoEntity> = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("failed to parse json: {}", body));
+        todos
+    }
+
+    // Tests
+
+    #[tokio::test]
+    async fn test_root() {
+        let req = RequestBuilder::new("/", Method::GET).with_empty();
+        let app = create_app(TodoRepositoryMemory::new(), LabelRepositoryForMemory::new());
+        let res = app.oneshot(req).await.unwrap();
+        let body = axum::body::to_bytes(res.into_body(), 10_000).await.unwrap();
+        assert_eq!(body, "Hello, world!");
+    }
+
+    #[tokio::test]
+    async fn test_create_todo_route() {
+        let req = RequestBuilder::new("/todos", Method::POST)
+            .with_json_string(r#"{"text": "test todo","labels": []}"#.to_string());
+        let todo_repo = TodoRepositoryMemory::new();
+        let label_repo = LabelRepositoryForMemory::new();
+        let app = create_app(todo_repo, label_repo);
+        let res = app.oneshot(req).await.unwrap();
+
+        let sut = res_to_todo(res).await;
+
+        let expected = TodoEntity::new(1, "test todo".to_string());
+        assert_eq!(sut.id, expected.id);
+        assert_eq!(sut.text, expected.text);
+        assert_eq!(sut.completed, expected.completed);
+        assert_eq!(sut.labels, expected.labels);
+    }
+
+    #[tokio::test]
+    async fn test_create_todo_validation_message_is_localized() {
+        let app = create_app(TodoRepositoryMemory::new(), LabelRepositoryForMemory::new());
+        let request = |accept_language: &str| {
+            Request::builder()
+                .uri("/todos")
+                .method(Method::POST)
+                .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+                .header(ACCEPT_LANGUAGE, accept_language)
+                .body(Body::from(r#"{"text": "", "labels": []}"#))
+                .unwrap()
+        };
+
+        let res = app.clone().oneshot(request("en-US")).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let body = axum::body::to_bytes(res.into_body(), 10_000).await.unwrap();
+        assert_eq!(
+            body,
+            "Validation error: [text: The text length is from 1 to 288 characters]"
+        );
+
+        let res = app.oneshot(request("ja-JP,en;q=0.5")).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        assert_eq!(res.headers()[CONTENT_LANGUAGE], "ja");
+        let body = axum::body::to_bytes(res.into_body(), 10_000).await.unwrap();
+        assert_eq!(
+            String::from_utf8(body.to_vec()).unwrap(),
+            "入力内容が正しくありません: [text: テキストは1文字以上288文字以下で入力してください]"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_todo_with_color_and_icon() {
+        let app = create_app(TodoRepositoryMemory::new(), LabelRepositoryForMemory::new());
+        let req = RequestBuilder::new("/todos", Method::POST).with_json_string(
+            r##"{"text": "groceries", "labels": [], "color": "#00aa00", "icon": "🛒"}"##
+                .to_string(),
+        );
+        let todo = res_to_todo(app.clone().oneshot(req).await.unwrap()).await;
+        assert_eq!(todo.color.as_deref(), Some("#00aa00"));
+        assert_eq!(todo.icon.as_deref(), Some("🛒"));
+
+        let req = RequestBuilder::new("/todos/1", Method::PATCH)
+            .with_json_string(r#"{"color": "green"}"#.to_string());
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let body = axum::body::to_bytes(res.into_body(), 10_000).await.unwrap();
+        assert_eq!(
+            body,
+            "Validation error: [color: The color is a hex code like #ff8800]"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_todo_by_id_route() {
+        // Given a todo in the repository as memory
+        let todo_repo = TodoRepositoryMemory::new();
+        let c_todo = CreateTodo::new("test todo".to_string(), vec![]);
+        let todo_registered = todo_repo
+            .create(c_todo)
+            .await
+            .expect("failed to create todo");
+        let label_repo = LabelRepositoryForMemory::new();
+
+        // When a request is made to find the todo by id
+        let req = RequestBuilder::new("/todos/1", Method::GET).with_empty();
+        let app = create_app(todo_repo, label_repo);
+        let res = app.oneshot(req).await.unwrap();
+        let result_response = res_to_todo(res).await;
+
+        // then
+        assert_eq!(result_response, todo_registered)
+    }
+
+    #[tokio::test]
+    async fn test_find_todo_by_public_id_route() {
+        let todo_repo = TodoRepositoryMemory::new();
+        let c_todo = CreateTodo::new("test todo".to_string(), vec![]);
+        let todo_registered = todo_repo.create(c_todo).await.unwrap();
+        let app = create_app(todo_repo, LabelRepositoryForMemory::new());
+
+        let uri = format!("/todos/{}", todo_registered.public_id);
+        let req = RequestBuilder::new(&uri, Method::GET).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res_to_todo(res).await, todo_registered);
+
+        let uri = format!("/todos/{}", crate::public_id::from_serial(2));
+        let req = RequestBuilder::new(&uri, Method::DELETE).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let req = RequestBuilder::new("/todos/one", Method::GET).with_empty();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_find_todo_if_modified_since() {
+        // Given a todo in the repository as memory
+        let todo_repo = TodoRepositoryMemory::new();
+        let c_todo = CreateTodo::new("test todo".to_string(), vec![]);
+        todo_repo
+            .create(c_todo)
+            .await
+            .expect("failed to create todo");
+        let app = create_app(todo_repo, LabelRepositoryForMemory::new());
+
+        // When a request is made without validators
+        let req = RequestBuilder::new("/todos/1", Method::GET).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+
+        // then cache headers are emitted
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!(res.headers()[CACHE_CONTROL], "no-cache");
+        let last_modified = res.headers()[LAST_MODIFIED].clone();
+
+        // and revalidating with that date yields 304 without body
+        let req = Request::builder()
+            .uri("/todos/1")
+            .header(IF_MODIFIED_SINCE, last_modified)
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_MODIFIED, res.status());
+        let body = axum::body::to_bytes(res.into_body(), 10_000).await.unwrap();
+        assert!(body.is_empty());
+
+        // and an outdated date yields the full todo
+        let req = Request::builder()
+            .uri("/todos/1")
+            .header(IF_MODIFIED_SINCE, "Sun, 06 Nov 1994 08:49:37 GMT")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+    }
+
+    #[tokio::test]
+    async fn test_all_todos_route() {
+        // Given a todo in the repository as memory
+        let todo_repo = TodoRepositoryMemory::new();
+        let c_todo = CreateTodo::new("test todo".to_string(), vec![]);
+        let todo_registered = todo_repo
+            .create(c_todo)
+            .await
+            .expect("Failed to create todo");
+        let c_todo2 = CreateTodo::new("test todo2".to_string(), vec![]);
+        let todo_registered2 = todo_repo
+            .create(c_todo2)
+            .await
+            .expect("Failed to create todo");
+
+        let label_repo = LabelRepositoryForMemory::new();
+
+        // When a request is made to find the todo by id
+        let req = RequestBuilder::new("/todos", Method::GET).with_empty();
+        let app = create_app(todo_repo, label_repo);
+        let res = app.oneshot(req).await.unwrap();
+        let result_response = res_to_todos(res).await;
+
+        // then
+        assert_eq!(result_response, vec![todo_registered, todo_registered2]);
+    }
+
+    #[tokio::test]
+    async fn test_json_api_mode() {
+        let todo_repo = TodoRepositoryMemory::new();
+        todo_repo
+            .create(CreateTodo::new("test todo".to_string(), vec![]))
+            .await
+            .expect("Failed to create todo");
+        let app = create_app(todo_repo, LabelRepositoryForMemory::new());
+        let get = |uri: &str| {
+            Request::builder()
+                .uri(uri)
+                .header(ACCEPT, json_api::JSON_API)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let res = app.clone().oneshot(get("/todos")).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!(res.headers()[CONTENT_TYPE], json_api::JSON_API);
+        assert_eq!(res.headers()[VARY], "accept");
+        let body = axum::body::to_bytes(res.into_body(), 100_000)
+            .await
+            .unwrap();
+        let document: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(document["data"][0]["type"], "todos");
+        assert_eq!(document["data"][0]["id"], "1");
+        assert_eq!(document["data"][0]["attributes"]["text"], "test todo");
+        assert_eq!(document["included"], serde_json::json!([]));
+
+        let res = app.clone().oneshot(get("/todos/9")).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let body = axum::body::to_bytes(res.into_body(), 100_000)
+            .await
+            .unwrap();
+        let document: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(document["errors"][0]["status"], "404");
+
+        // plain JSON without the media type
+        let req = RequestBuilder::new("/todos/1", Method::GET).with_empty();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.headers()[CONTENT_TYPE], mime::APPLICATION_JSON.as_ref());
+    }
+
+    #[tokio::test]
+    async fn test_app_with_dyn_repositories() {
+        // e.g. picked from configuration at runtime
+        let todo_repo: Arc<dyn TodoRepository> = Arc::new(TodoRepositoryMemory::new());
+        let label_repo: Arc<dyn LabelRepository> = Arc::new(LabelRepositoryForMemory::new());
+        let todo = todo_repo
+            .create(CreateTodo::new("test todo".to_string(), vec![]))
+            .await
+            .expect("Failed to create todo");
+
+        let req = RequestBuilder::new("/todos", Method::GET).with_empty();
+        let app = create_app(todo_repo, label_repo);
+        let res = app.oneshot(req).await.unwrap();
+
+        assert_eq!(res_to_todos(res).await, vec![todo]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_todos_route() {
+        let todo_repo = TodoRepositoryMemory::new();
+        let mut todos = vec![];
+        for text in ["first", "second"] {
+            let todo = todo_repo
+                .create(CreateTodo::new(text.to_string(), vec![]))
+                .await
+                .expect("Failed to create todo");
+            todos.push(todo);
+        }
+
+        let req = RequestBuilder::new("/todos/stream", Method::GET).with_empty();
+        let app = create_app(todo_repo, LabelRepositoryForMemory::new());
+        let res = app.oneshot(req).await.unwrap();
+
+        assert_eq!(res.headers()[CONTENT_TYPE], "application/x-ndjson");
+        let bytes = axum::body::to_bytes(res.into_body(), 10_000).await.unwrap();
+        let streamed = String::from_utf8(bytes.to_vec())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<TodoEntity>(line).unwrap())
+            .collect::<Vec<TodoEntity>>();
+        assert_eq!(streamed, todos);
+    }
+
+    #[tokio::test]
+    async fn test_all_todos_route_with_page() {
+        let todo_repo = TodoRepositoryMemory::new();
+        for text in ["todo1", "todo2", "todo3"] {
+            todo_repo
+                .create(CreateTodo::new(text.to_string(), vec![]))
+                .await
+                .expect("Failed to create todo");
+        }
+        let app = create_app(todo_repo, LabelRepositoryForMemory::new());
+
+        let req =
+            RequestBuilder::new("/todos?limit=2&offset=1&order=desc", Method::GET).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let ids = res_to_todos(res)
+            .await
+            .into_iter()
+            .map(|todo| todo.id)
+            .collect::<Vec<i32>>();
+        assert_eq!(ids, vec![2, 1]);
+
+        // and a batch of ids is fetched at once
+        let req = RequestBuilder::new("/todos?ids=3,1", Method::GET).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let ids = res_to_todos(res)
+            .await
+            .into_iter()
+            .map(|todo| todo.id)
+            .collect::<Vec<i32>>();
+        assert_eq!(ids, vec![1, 3]);
+
+        // and an empty page is still a JSON array
+        let req = RequestBuilder::new("/todos?offset=10", Method::GET).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res_to_todos(res).await, vec![]);
+
+        // and unknown sort keys are rejected
+        let req = RequestBuilder::new("/todos?sort=nope", Method::GET).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+        // and the configured page sizes apply
+        let app = app.layer(Extension(PageSizes { default: 2, max: 2 }));
+        let req = RequestBuilder::new("/todos", Method::GET).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res_to_todos(res).await.len(), 2);
+        for limit in ["0", "3"] {
+            let req =
+                RequestBuilder::new(&format!("/todos?limit={}", limit), Method::GET).with_empty();
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+        }
+        // as is a batch of more ids than fit in a page
+        for (query, message) in [
+            ("limit=3", "limit [3] is not between 1 and 2"),
+            ("ids=1,2,3", "ids [3] is not between 1 and 2"),
+        ] {
+            let req = RequestBuilder::new(&format!("/todos?{}", query), Method::GET).with_empty();
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+            let body = axum::body::to_bytes(res.into_body(), 10_000).await.unwrap();
+            assert_eq!(body, message);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_next_todo_route() {
+        let app = create_app(TodoRepositoryMemory::new(), LabelRepositoryForMemory::new());
+        let req = RequestBuilder::new("/todos/next", Method::GET).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        for body in [
+            r#"{"text": "someday", "labels": []}"#,
+            r#"{"text": "important", "labels": [], "priority": "high"}"#,
+        ] {
+            let req =
+                RequestBuilder::new("/todos", Method::POST).with_json_string(body.to_string());
+            app.clone().oneshot(req).await.unwrap();
+        }
+
+        let req = RequestBuilder::new("/todos/next", Method::GET).with_empty();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let todo = res_to_todo(res).await;
+        assert_eq!(todo.text, "important");
+    }
+
+    #[tokio::test]
+    async fn test_starred_todos_route() {
+        let app = create_app(TodoRepositoryMemory::new(), LabelRepositoryForMemory::new());
+        for body in [
+            r#"{"text": "someday", "labels": []}"#,
+            r#"{"text": "favorite", "labels": [], "starred": true}"#,
+        ] {
+            let req =
+                RequestBuilder::new("/todos", Method::POST).with_json_string(body.to_string());
+            app.clone().oneshot(req).await.unwrap();
+        }
+        let starred = || async {
+            let req = RequestBuilder::new("/todos/starred", Method::GET).with_empty();
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::OK, res.status());
+            res_to_todos(res)
+                .await
+                .into_iter()
+                .map(|todo| todo.text)
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(starred().await, vec!["favorite"]);
+
+        let req = RequestBuilder::new("/todos/1", Method::PATCH)
+            .with_json_string(r#"{"starred": true}"#.to_string());
+        app.clone().oneshot(req).await.unwrap();
+        assert_eq!(starred().await, vec!["someday", "favorite"]);
+        let req = RequestBuilder::new("/todos/2", Method::PATCH)
+            .with_json_string(r#"{"starred": false}"#.to_string());
+        app.clone().oneshot(req).await.unwrap();
+        assert_eq!(starred().await, vec!["someday"]);
+    }
+
+    #[tokio::test]
+    async fn test_quick_add_todo_route() {
+        let label_repo = LabelRepositoryForMemory::new();
+        let app = create_app(TodoRepositoryMemory::new(), label_repo.clone());
+        let quick_add = |text: &str| {
+            RequestBuilder::new("/todos/quick", Method::POST)
+                .with_json_string(format!(r#"{{"text": "{}"}}"#, text))
+        };
+
+        let res = app
+            .clone()
+            .oneshot(quick_add("pay rent tomorrow 5pm #finance !high"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let todo = res_to_todo(res).await;
+        assert_eq!(todo.text, "pay rent");
+        assert_eq!(todo.priority, Some(Priority::High));
+        assert!(todo.due_at.is_some());
+
+        // existing labels are reused
+        let res = app
+            .clone()
+            .oneshot(quick_add("file taxes #finance"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let labels = label_repo.all().await.unwrap();
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels[0].name, "finance");
+
+        // nothing left for the todo text
+        let res = app.oneshot(quick_add("tomorrow #finance")).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+    }
+
+    #[tokio::test]
+    async fn test_todo_workload_route() {
+        let app = create_app(TodoRepositoryMemory::new(), LabelRepositoryForMemory::new());
+        for (text, due_at, estimate_minutes) in [
+            ("report", Some("2030-06-03T09:00:00Z"), Some(300)),
+            ("slides", Some("2030-06-03T23:30:00Z"), Some(240)),
+            ("call", Some("2030-06-04T10:00:00Z"), None),
+            ("later", Some("2030-06-10T10:00:00Z"), Some(60)),
+            ("someday", None, Some(60)),
+        ] {
+            let body = serde_json::json!({
+                "text": text,
+                "labels": [],
+                "due_at": due_at,
+                "estimate_minutes": estimate_minutes,
+            });
+            let req =
+                RequestBuilder::new("/todos", Method::POST).with_json_string(body.to_string());
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::CREATED, res.status());
+        }
+        let workload = |query: &str| {
+            let req = RequestBuilder::new(&format!("/todos/workload?{}", query), Method::GET)
+                .with_empty();
+            app.clone().oneshot(req)
+        };
+
+        let res = workload("date=2030-06-03&days=3").await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let days: Vec<DayWorkload> = serde_json::from_slice(&bytes).unwrap();
+        let summary = days
+            .iter()
+            .map(|day| {
+                (
+                    day.date.to_string(),
+                    day.todos,
+                    day.estimate_minutes,
+                    day.unestimated,
+                    day.overloaded,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("2030-06-03".to_string(), 2, 540, 0, true),
+                ("2030-06-04".to_string(), 1, 0, 1, false),
+                ("2030-06-05".to_string(), 0, 0, 0, false),
+            ]
+        );
+
+        // the late one falls on the next day in JST
+        let res = workload("date=2030-06-04&days=1&utc_offset_minutes=540")
+            .await
+            .unwrap();
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let days: Vec<DayWorkload> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!((days[0].todos, days[0].estimate_minutes), (2, 240));
+
+        // without its estimate the report no longer overloads the day
+        let req = RequestBuilder::new("/todos/1", Method::PATCH)
+            .with_json_string(r#"{"estimate_minutes": null}"#.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let res = workload("date=2030-06-03&days=1").await.unwrap();
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let days: Vec<DayWorkload> = serde_json::from_slice(&bytes).unwrap();
+        let day = &days[0];
+        assert_eq!(
+            (
+                day.todos,
+                day.estimate_minutes,
+                day.unestimated,
+                day.overloaded
+            ),
+            (2, 240, 1, false)
+        );
+
+        let res = workload("days=32").await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+    }
+
+    #[tokio::test]
+    async fn test_delete_todo_route() {
+        // Given a todo in the repository as memory
+        let todo_repo = TodoRepositoryMemory::new();
+        let c_todo = CreateTodo::new("test todo".to_string(), vec![]);
+        let _todo_registered = todo_repo
+            .create(c_todo)
+            .await
+            .expect("Failed to create todo");
+
+        let label_repo = LabelRepositoryForMemory::new();
+
+        // When a delete request made with path param id=1
+        let req = RequestBuilder::new("/todos/1", Method::DELETE).with_empty();
+        let app = create_app(todo_repo, label_repo);
+        let res = app.clone().oneshot(req).await.unwrap();
+
+        // then
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+
+        // and with not found request
+        let req = RequestBuilder::new("/todos/2", Method::DELETE).with_empty();
+        let res = app.oneshot(req).await.unwrap();
+        // then
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn test_label_routes() {
+        let app = create_app(TodoRepositoryMemory::new(), LabelRepositoryForMemory::new());
+
+        let req = RequestBuilder::new("/label", Method::POST)
+            .with_json_string(r#"{"name": "label"}"#.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+
+        let req = RequestBuilder::new("/label", Method::GET).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        // find or create
+        let req = RequestBuilder::new("/label?name=label", Method::PUT).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let body = axum::body::to_bytes(res.into_body(), 1_000).await.unwrap();
+        let label: Label = serde_json::from_slice(&body).unwrap();
+        assert_eq!(label, Label::new(1, "label".to_string()));
+        let req = RequestBuilder::new("/label?name=home", Method::PUT).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let req = RequestBuilder::new("/label?name=", Method::PUT).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+        let req = RequestBuilder::new("/label/1?force=true", Method::DELETE).with_empty();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+    }
+
+    #[tokio::test]
+    async fn test_label_defaults_route() {
+        let label_repo = LabelRepositoryForMemory::new();
+        let name = "urgent".to_string();
+        label_repo.create(CreateLabel { name }).await.unwrap();
+        let app = create_app(TodoRepositoryMemory::new(), label_repo);
+        let put = |uri: &str, body: &str| {
+            RequestBuilder::new(uri, Method::PUT).with_json_string(body.to_string())
+        };
+
+        let req = put(
+            "/label/1/defaults",
+            r#"{"priority": "high", "due_in_minutes": 60}"#,
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let req = RequestBuilder::new("/label/1/defaults", Method::GET).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let body = axum::body::to_bytes(res.into_body(), 1_000).await.unwrap();
+        let defaults: LabelDefaults = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            defaults,
+            LabelDefaults {
+                priority: Some(Priority::High),
+                due_in_minutes: Some(60),
+            }
+        );
+
+        let req = put("/label/1/defaults", r#"{"due_in_minutes": 0}"#);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let req = put("/label/9/defaults", "{}");
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn test_label_export_and_import() {
+        let source = create_app(TodoRepositoryMemory::new(), LabelRepositoryForMemory::new());
+        for name in ["work", "home"] {
+            let uri = format!("/label?name={}", name);
+            let req = RequestBuilder::new(&uri, Method::PUT).with_empty();
+            source.clone().oneshot(req).await.unwrap();
+        }
+        let req = RequestBuilder::new("/label/1/archive", Method::POST).with_empty();
+        source.clone().oneshot(req).await.unwrap();
+        let req = RequestBuilder::new("/label/export", Method::GET).with_empty();
+        let res = source.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(res.into_body(), 1_000).await.unwrap();
+        let export: LabelExport = serde_json::from_slice(&body).unwrap();
+        let exported = |name: &str, archived| ExportedLabel {
+            name: name.to_string(),
+            archived,
+        };
+        assert_eq!(
+            export.labels,
+            vec![exported("home", false), exported("work", true)]
+        );
+
+        let label_repo = LabelRepositoryForMemory::new();
+        label_repo
+            .create(CreateLabel {
+                name: "home".to_string(),
+            })
+            .await
+            .unwrap();
+        let target = create_app(TodoRepositoryMemory::new(), label_repo.clone());
+        let import = |body: String| {
+            RequestBuilder::new("/label/import", Method::POST).with_json_string(body)
+        };
+        let res = target
+            .clone()
+            .oneshot(import(String::from_utf8(body.to_vec()).unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(res.into_body(), 1_000).await.unwrap();
+        let outcome: LabelImport = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            outcome,
+            LabelImport {
+                created: 1,
+                existing: 1
+            }
+        );
+        let labels = label_repo.all().await.unwrap();
+        assert!(labels
+            .iter()
+            .any(|label| label.name == "work" && label.archived));
+
+        // nothing is written when a name is invalid
+        let res = target
+            .oneshot(import(
+                r#"{"labels": [{"name": "new"}, {"name": ""}]}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(label_repo.all().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn update_todo_route() {
+        // Given a todo in the repository as memory
+        let todo_repo = TodoRepositoryMemory::new();
+        let c_todo = CreateTodo::new("test todo".to_string(), vec![]);
+        let _todo_registered = todo_repo
+            .create(c_todo)
+            .await
+            .expect("Failed to create todo");
+
+        let label_repo = LabelRepositoryForMemory::new();
+
+        // When a delete request made with path param id=1
+        let req = RequestBuilder::new("/todos/1", Method::PATCH)
+            .with_json_string(r#"{"text": "test todo updated"}"#.to_string());
+        let app = create_app(todo_repo, label_repo);
+        let res = app.clone().oneshot(req).await.unwrap();
+
+        // then
+        assert_eq!(StatusCode::CREATED, res.status());
+
+        // and with not found request
+        let req = RequestBuilder::new("/todos/2", Method::PATCH)
+            .with_json_string(r#"{"text": "test todo updated"}"#.to_string());
+        let res = app.oneshot(req).await.unwrap();
+        // then
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn update_todo_route_clears_with_null() {
+        let todo_repo = TodoRepositoryMemory::new();
+        todo_repo
+            .create(
+                CreateTodo::builder("file taxes")
+                    .description("receipts are in the blue folder")
+                    .due_at("2030-06-03T09:00:00Z".parse().unwrap())
+                    .priority(Priority::High)
+                    .color("#00aa00")
+                    .icon("🧾")
+                    .build(),
+            )
+            .await
+            .unwrap();
+        let app = create_app(todo_repo, LabelRepositoryForMemory::new());
+
+        let req = RequestBuilder::new("/todos/1", Method::PATCH)
+            .with_json_string(r#"{"text": "file the taxes"}"#.to_string());
+        let todo = res_to_todo(app.clone().oneshot(req).await.unwrap()).await;
+        assert!(todo.due_at.is_some());
+        assert_eq!(todo.priority, Some(Priority::High));
+        assert!(todo.description.is_some());
+        assert!(todo.color.is_some() && todo.icon.is_some());
+
+        let update = serde_json::json!({
+            "description": null,
+            "due_at": null,
+            "priority": null,
+            "color": null,
+            "icon": null,
+        });
+        let req =
+            RequestBuilder::new("/todos/1", Method::PATCH).with_json_string(update.to_string());
+        let todo = res_to_todo(app.oneshot(req).await.unwrap()).await;
+        assert_eq!((todo.due_at, todo.priority), (None, None));
+        assert_eq!(todo.description, None);
+        assert_eq!((todo.color, todo.icon), (None, None));
+    }
+
+    #[tokio::test]
+    async fn test_todo_routes_repository_errors() {
+        let todo_repo = MockTodoRepository::default()
+            .expect_find(|id| Err(RepositoryError::NotFound(id).into()))
+            .expect_stream(|_| {
+                Err(RepositoryError::Unexpected("connection refused".to_string()).into())
+            })
+            .expect_delete(|_| {
+                Err(RepositoryError::Unexpected("connection refused".to_string()).into())
+            });
+        let app = create_app(todo_repo, MockLabelRepository::default());
+
+        let req = RequestBuilder::new("/todos/1", Method::GET).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let req = RequestBuilder::new("/todos", Method::GET).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let req = RequestBuilder::new("/todos/1", Method::DELETE).with_empty();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_find_todo_route_database_down() {
+        let todo_repo = MockTodoRepository::default().expect_find(|_| {
+            Err(RepositoryError::Unexpected("connection refused".to_string()).into())
+        });
+        let app = create_app(todo_repo, MockLabelRepository::default());
+
+        let req = RequestBuilder::new("/todos/1", Method::GET).with_empty();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_todo_list_counts() {
+        let todo_repo = TodoRepositoryMemory::new();
+        for text in ["one", "two", "three"] {
+            todo_repo
+                .create(CreateTodo::new(text.to_string(), vec![]))
+                .await
+                .unwrap();
+        }
+        todo_repo
+            .update(1, UpdateTodo::builder().completed(true).build())
+            .await
+            .unwrap();
+        let app = create_app(todo_repo, LabelRepositoryForMemory::new());
+        let counts = |res: &Response| {
+            [&X_TOTAL_COUNT, &X_TOTAL_COMPLETED, &X_TOTAL_OVERDUE].map(|name| {
+                res.headers()
+                    .get(name)
+                    .map(|v| v.to_str().unwrap().to_string())
+            })
+        };
+        let count = |n: &str| Some(n.to_string());
+
+        let req = RequestBuilder::new("/todos?limit=1", Method::GET).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(counts(&res), [count("3"), count("1"), count("0")]);
+        assert_eq!(res_to_todos(res).await.len(), 1);
+
+        let req = RequestBuilder::new("/todos?completed=false", Method::GET).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(counts(&res), [count("2"), count("0"), count("0")]);
+
+        // past the end nothing is known about the listing
+        let req = RequestBuilder::new("/todos?offset=10", Method::GET).with_empty();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(counts(&res), [None, None, None]);
+
+        let req = RequestBuilder::new("/todos?q=nothing", Method::GET).with_empty();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(counts(&res), [count("0"), count("0"), count("0")]);
+    }
+
+    #[tokio::test]
+    async fn test_empty_todo_list() {
+        let todo_repo = MockTodoRepository::default().expect_stream(|_| Ok(vec![]));
+        let app = create_app(todo_repo, MockLabelRepository::default());
+
+        let req = RequestBuilder::new("/todos", Method::GET).with_empty();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res_to_todos(res).await, vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_label_routes_conflicts() {
+        let label_repo = MockLabelRepository::default()
+            .expect_create(|_| Err(RepositoryError::DuplicatedLabel(1).into()))
+            .expect_delete(|(id, _)| Err(RepositoryError::LabelInUse { id, todo_count: 2 }.into()));
+        let app = create_app(MockTodoRepository::default(), label_repo);
+
+        let req = RequestBuilder::new("/label", Method::POST)
+            .with_json_string(r#"{"name": "finance"}"#.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+
+        let req = RequestBuilder::new("/label/1", Method::DELETE).with_empty();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_label_assignment() {
+        let label_repo = MockLabelRepository::default()
+            .expect_assign(|(id, todo_ids)| match id {
+                1 => Ok(todo_ids.into_iter().filter(|id| *id != 2).collect()),
+                _ => Err(RepositoryError::NotFound(id).into()),
+            })
+            .expect_unassign(|(_, todo_ids)| Ok(todo_ids));
+        let app = create_app(MockTodoRepository::default(), label_repo);
+        let post = |uri: &str, body: &str| {
+            RequestBuilder::new(uri, Method::POST).with_json_string(body.to_string())
+        };
+
+        let res = app
+            .clone()
+            .oneshot(post("/label/1/assign", r#"{"todo_ids": [1, 2, 3]}"#))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(res.into_body(), 1_000).await.unwrap();
+        assert_eq!(&body[..], br#"{"todo_ids":[1,3]}"#);
+
+        let res = app
+            .clone()
+            .oneshot(post("/label/9/assign", r#"{"todo_ids": [1]}"#))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = app
+            .clone()
+            .oneshot(post("/label/1/unassign", r#"{"todo_ids": []}"#))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let res = app
+            .oneshot(post("/label/1/unassign", r#"{"todo_ids": [4]}"#))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
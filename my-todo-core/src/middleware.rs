@@ -0,0 +1,9 @@
+pub mod access_log;
+pub mod concurrency;
+pub mod csrf;
+pub mod dedupe;
+pub mod json_api;
+pub mod load_shed;
+pub mod options;
+pub mod rate_limit;
+pub mod read_only;
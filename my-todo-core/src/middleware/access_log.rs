@@ -0,0 +1,311 @@
+use axum::body::{Body, HttpBody};
+use axum::extract::Request;
+use axum::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use axum::http::{HeaderMap, HeaderName};
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use axum::Router;
+use futures_util::StreamExt;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::{DefaultOnFailure, DefaultOnResponse, TraceLayer};
+use tower_http::LatencyUnit;
+use tracing::Level;
+
+pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// Bodies are only logged at debug level, and cut to this many bytes.
+const LOGGED_BODY_LIMIT: usize = 1024;
+
+/// Log method, path, status, latency and request id of every request at info level.
+/// A request id is generated unless the client already sent `X-Request-Id`, and echoed back.
+pub fn trace(router: Router) -> Router {
+    router
+        .layer(middleware::from_fn(log_bodies))
+        .layer(PropagateRequestIdLayer::new(REQUEST_ID_HEADER.clone()))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(|req: &Request| {
+                    let request_id = req
+                        .headers()
+                        .get(&REQUEST_ID_HEADER)
+                        .and_then(|value| value.to_str().ok())
+                        .unwrap_or("-");
+                    tracing::info_span!(
+                        "request",
+                        method = %req.method(),
+                        path = %req.uri().path(),
+                        request_id = %request_id,
+                    )
+                })
+                .on_request(())
+                .on_response(
+                    DefaultOnResponse::new()
+                        .level(Level::INFO)
+                        .latency_unit(LatencyUnit::Millis),
+                )
+                .on_failure(DefaultOnFailure::new().latency_unit(LatencyUnit::Millis)),
+        )
+        .layer(SetRequestIdLayer::new(
+            REQUEST_ID_HEADER.clone(),
+            MakeRequestUuid,
+        ))
+}
+
+fn content_length(headers: &HeaderMap) -> Option<usize> {
+    headers
+        .get(CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse().ok())
+}
+
+/// The length a body announces, in its headers or because it is already in memory.
+fn known_length(headers: &HeaderMap, body: &Body) -> Option<usize> {
+    content_length(headers).or_else(|| body.size_hint().exact().map(|length| length as usize))
+}
+
+async fn log_bodies(req: Request, next: Next) -> Response {
+    if !tracing::enabled!(Level::DEBUG) {
+        return next.run(req).await;
+    }
+
+    // Request bodies are passed on as they come, whatever their size, keeping a copy of the
+    // start to log. Whether one is too large is up to the route reading it.
+    let length = known_length(req.headers(), req.body());
+    let (parts, body) = req.into_parts();
+    let req = Request::from_parts(parts, tee(body, "request", length));
+    let res = next.run(req).await;
+
+    // Streamed responses, like `GET /todos` and the NDJSON export, are passed on untouched,
+    // the others are teed like requests.
+    let streamed = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|content_type| content_type == "application/x-ndjson");
+    let length = known_length(res.headers(), res.body());
+    let Some(length) = length.filter(|_| !streamed) else {
+        return res;
+    };
+    let (parts, body) = res.into_parts();
+    Response::from_parts(parts, tee(body, "response", Some(length)))
+}
+
+/// Pass `body` on, logging its start once `length` bytes have gone through, or once it is
+/// dropped when the length isn't known or the body isn't read to the end.
+fn tee(body: Body, kind: &'static str, length: Option<usize>) -> Body {
+    if length == Some(0) {
+        tracing::debug!("{} body: ", kind);
+        return body;
+    }
+    let mut log = BodyLog {
+        span: tracing::Span::current(),
+        kind,
+        start: Vec::with_capacity(length.unwrap_or(LOGGED_BODY_LIMIT).min(LOGGED_BODY_LIMIT)),
+        seen: 0,
+        length,
+        logged: false,
+    };
+    let body = body.into_data_stream().map(move |chunk| {
+        if let Ok(chunk) = &chunk {
+            log.record(chunk);
+        }
+        chunk
+    });
+    Body::from_stream(body)
+}
+
+/// The start of a body going through [`tee`].
+struct BodyLog {
+    span: tracing::Span,
+    kind: &'static str,
+    start: Vec<u8>,
+    seen: usize,
+    length: Option<usize>,
+    logged: bool,
+}
+
+impl BodyLog {
+    fn record(&mut self, chunk: &[u8]) {
+        let take = chunk
+            .len()
+            .min(LOGGED_BODY_LIMIT.saturating_sub(self.start.len()));
+        self.start.extend_from_slice(&chunk[..take]);
+        self.seen += chunk.len();
+        // without a length only the end tells how large the body is
+        if self
+            .length
+            .is_some_and(|length| self.seen >= length || self.start.len() >= LOGGED_BODY_LIMIT)
+        {
+            self.log();
+        }
+    }
+
+    fn log(&mut self) {
+        if self.logged {
+            return;
+        }
+        self.logged = true;
+        let len = self.length.unwrap_or(self.seen);
+        self.span
+            .in_scope(|| tracing::debug!("{} body: {}", self.kind, truncated(&self.start, len)));
+    }
+}
+
+impl Drop for BodyLog {
+    fn drop(&mut self) {
+        self.log();
+    }
+}
+
+/// The start of a body of `len` bytes as text.
+fn truncated(start: &[u8], len: usize) -> String {
+    let body = String::from_utf8_lossy(&start[..start.len().min(LOGGED_BODY_LIMIT)]);
+    if len > LOGGED_BODY_LIMIT {
+        format!("{}... ({} bytes)", body, len)
+    } else {
+        body.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use axum::routing::{get, post};
+    use futures_util::stream;
+    use tower::ServiceExt;
+    use tracing_subscriber::fmt::MakeWriter;
+
+    use super::*;
+
+    /// Collects what is logged, to look at after the requests.
+    #[derive(Debug, Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Logs {
+        type Writer = Logs;
+
+        fn make_writer(&'a self) -> Logs {
+            self.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn request_id_is_generated_and_propagated() {
+        let app = trace(Router::new().route("/", get(|| async { "ok" })));
+
+        let req = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert!(res.headers().contains_key(&REQUEST_ID_HEADER));
+
+        let req = Request::builder()
+            .uri("/")
+            .header(&REQUEST_ID_HEADER, "client-id")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.headers()[&REQUEST_ID_HEADER], "client-id");
+    }
+
+    #[tokio::test]
+    async fn debug_logs_only_the_start_of_bodies() {
+        let logs = Logs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(Level::DEBUG)
+            .with_ansi(false)
+            .with_writer(logs.clone())
+            .finish();
+        let _default = tracing::subscriber::set_default(subscriber);
+        let large = "x".repeat(3 * LOGGED_BODY_LIMIT);
+        let app = trace(
+            Router::new()
+                .route("/large", get(move || async move { large }))
+                .route("/echo", post(|body: String| async move { body }))
+                .route(
+                    "/count",
+                    post(|body: Body| async move {
+                        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+                        bytes.len().to_string()
+                    }),
+                )
+                .route(
+                    "/stream",
+                    get(|| async {
+                        let lines = stream::iter(["{}\n", "{}\n"].map(anyhow::Ok));
+                        Body::from_stream(lines)
+                    }),
+                ),
+        );
+        let body = |res: Response| axum::body::to_bytes(res.into_body(), usize::MAX);
+
+        let req = Request::builder()
+            .uri("/large")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(body(res).await.unwrap().len(), 3 * LOGGED_BODY_LIMIT);
+        let req = Request::builder()
+            .uri("/stream")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(body(res).await.unwrap(), "{}\n{}\n");
+        let req = Request::builder()
+            .method("POST")
+            .uri("/echo")
+            .body(Body::from("hello"))
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(body(res).await.unwrap(), "hello");
+        // larger than axum's default limit and without saying so up front, which is for
+        // the route to judge
+        let large_request = 2 * 1024 * 1024 + 1;
+        let chunks = stream::iter([
+            anyhow::Ok(vec![b'x'; large_request - 1]),
+            anyhow::Ok(vec![b'x']),
+        ]);
+        let req = Request::builder()
+            .method("POST")
+            .uri("/count")
+            .body(Body::from_stream(chunks))
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(body(res).await.unwrap(), large_request.to_string());
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let logged = |prefix: &str| {
+            logs.lines()
+                .filter_map(|line| line.split_once(prefix).map(|(_, body)| body.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let truncated = format!(
+            "{}... ({} bytes)",
+            "x".repeat(LOGGED_BODY_LIMIT),
+            3 * LOGGED_BODY_LIMIT
+        );
+        assert_eq!(
+            logged("response body: "),
+            [truncated, "hello".to_string(), large_request.to_string()]
+        );
+        let truncated = format!(
+            "{}... ({} bytes)",
+            "x".repeat(LOGGED_BODY_LIMIT),
+            large_request
+        );
+        assert_eq!(
+            logged("request body: "),
+            [String::new(), String::new(), "hello".to_string(), truncated]
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::header::{CONTENT_LANGUAGE, RETRY_AFTER};
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use futures_util::StreamExt;
+use tokio::sync::Semaphore;
+
+use crate::i18n::{Locale, Message};
+
+pub const DEFAULT_CONCURRENCY_LIMIT: usize = 4;
+
+/// Requests to `path` allowed to run at once, configured with `CONCURRENCY_LIMITS`. With a
+/// `param`, only those carrying that query parameter count, e.g. searches of `GET /todos`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitedRoute {
+    pub path: String,
+    pub param: Option<String>,
+    pub max_in_flight: usize,
+}
+
+impl LimitedRoute {
+    fn matches(&self, req: &Request) -> bool {
+        req.uri().path() == self.path
+            && self.param.as_ref().is_none_or(|param| {
+                req.uri()
+                    .query()
+                    .unwrap_or_default()
+                    .split('&')
+                    .any(|pair| pair.split('=').next() == Some(param))
+            })
+    }
+}
+
+#[derive(Debug)]
+struct Limited {
+    route: LimitedRoute,
+    permits: Arc<Semaphore>,
+}
+
+/// Searching and exporting, which read far more than the other routes.
+pub fn default_routes() -> Vec<LimitedRoute> {
+    [
+        ("/todos", Some("q")),
+        ("/todos/stream", None),
+        ("/label/export", None),
+    ]
+    .into_iter()
+    .map(|(path, param)| LimitedRoute {
+        path: path.to_string(),
+        param: param.map(str::to_string),
+        max_in_flight: DEFAULT_CONCURRENCY_LIMIT,
+    })
+    .collect()
+}
+
+/// Run at most `max_in_flight` requests to each of `routes` at once, so a burst of expensive
+/// ones can't take every database connection. Those over the limit are refused right away
+/// with 503 and `Retry-After` rather than queued. A request counts until its whole body has
+/// been sent, as the exports stream theirs.
+pub fn limit(router: Router, routes: Vec<LimitedRoute>) -> Router {
+    let routes = Arc::new(
+        routes
+            .into_iter()
+            .map(|route| Limited {
+                permits: Arc::new(Semaphore::new(route.max_in_flight)),
+                route,
+            })
+            .collect::<Vec<Limited>>(),
+    );
+    router.layer(middleware::from_fn_with_state(routes, shed))
+}
+
+async fn shed(State(routes): State<Arc<Vec<Limited>>>, req: Request, next: Next) -> Response {
+    let Some(limited) = routes.iter().find(|limited| limited.route.matches(&req)) else {
+        return next.run(req).await;
+    };
+    let Ok(permit) = limited.permits.clone().try_acquire_owned() else {
+        let path = limited.route.path.clone();
+        metrics::counter!("requests_shed_total", "path" => path).increment(1);
+        let locale = Locale::from_headers(req.headers());
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(RETRY_AFTER, "1"), (CONTENT_LANGUAGE, locale.tag())],
+            Message::Overloaded.text(locale),
+        )
+            .into_response();
+    };
+    let (parts, body) = next.run(req).await.into_parts();
+    let body = body.into_data_stream().map(move |chunk| {
+        let _held = &permit;
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    async fn get_status(app: &Router, uri: &str) -> StatusCode {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let status = res.status();
+        axum::body::to_bytes(res.into_body(), 1_000).await.unwrap();
+        status
+    }
+
+    #[tokio::test]
+    async fn shed_requests_over_the_limit() {
+        let slow = || async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            "todos"
+        };
+        let router = Router::new()
+            .route("/todos", get(slow))
+            .route("/other", get(slow));
+        let app = limit(
+            router,
+            vec![LimitedRoute {
+                path: "/todos".to_string(),
+                param: Some("q".to_string()),
+                max_in_flight: 1,
+            }],
+        );
+
+        let (first, second) = tokio::join!(
+            get_status(&app, "/todos?q=milk"),
+            get_status(&app, "/todos?completed=false&q=eggs"),
+        );
+        assert_eq!(
+            (first, second),
+            (StatusCode::OK, StatusCode::SERVICE_UNAVAILABLE)
+        );
+        assert_eq!(get_status(&app, "/todos?q=milk").await, StatusCode::OK);
+
+        // listings without a search and other routes are not limited
+        let statuses = tokio::join!(
+            get_status(&app, "/todos"),
+            get_status(&app, "/todos?quiet=1"),
+            get_status(&app, "/other?q=milk"),
+            get_status(&app, "/todos?q=milk"),
+        );
+        assert_eq!(
+            statuses,
+            (
+                StatusCode::OK,
+                StatusCode::OK,
+                StatusCode::OK,
+                StatusCode::OK
+            )
+        );
+    }
+}
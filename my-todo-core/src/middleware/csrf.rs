@@ -0,0 +1,169 @@
+use axum::extract::Request;
+use axum::http::header::{CONTENT_LANGUAGE, COOKIE, SET_COOKIE};
+use axum::http::{HeaderMap, HeaderName, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Serialize;
+
+use crate::config::CsrfConfig;
+use crate::i18n::{Locale, Message};
+
+pub const CSRF_COOKIE: &str = "csrf_token";
+pub static CSRF_HEADER: HeaderName = HeaderName::from_static("x-csrf-token");
+
+#[derive(Debug, Serialize)]
+struct CsrfToken {
+    token: String,
+}
+
+/// Double-submit cookie protection: every mutating request has to echo the
+/// `csrf_token` cookie in the `X-CSRF-Token` header. The SPA fetches a token from `GET /auth/csrf`.
+pub fn protect(router: Router, config: &CsrfConfig) -> Router {
+    let cookie_secure = config.cookie_secure;
+    router
+        .route("/auth/csrf", get(move || issue_token(cookie_secure)))
+        .layer(middleware::from_fn(verify_token))
+}
+
+async fn issue_token(cookie_secure: bool) -> impl IntoResponse {
+    let token = new_token();
+    let cookie = token_cookie(&token, cookie_secure);
+    ([(SET_COOKIE, cookie)], Json(CsrfToken { token }))
+}
+
+pub(crate) fn new_token() -> String {
+    rand::random::<[u8; 32]>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The `Set-Cookie` value carrying `token`.
+pub(crate) fn token_cookie(token: &str, cookie_secure: bool) -> String {
+    let mut cookie = format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Strict",
+        CSRF_COOKIE, token
+    );
+    if cookie_secure {
+        cookie.push_str("; Secure");
+    }
+    cookie
+}
+
+async fn verify_token(req: Request, next: Next) -> Response {
+    let safe_method = matches!(
+        *req.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+    );
+    if safe_method {
+        return next.run(req).await;
+    }
+
+    let headers = req.headers();
+    let cookie_token = find_cookie(headers, CSRF_COOKIE);
+    let header_token = headers
+        .get(&CSRF_HEADER)
+        .and_then(|value| value.to_str().ok());
+    match (cookie_token, header_token) {
+        (Some(cookie), Some(header)) if constant_time_eq(cookie.as_bytes(), header.as_bytes()) => {
+            next.run(req).await
+        }
+        _ => {
+            let locale = Locale::from_headers(headers);
+            (
+                StatusCode::FORBIDDEN,
+                [(CONTENT_LANGUAGE, locale.tag())],
+                Message::CsrfInvalid.text(locale),
+            )
+                .into_response()
+        }
+    }
+}
+
+pub(crate) fn find_cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::routing::post;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn app() -> Router {
+        let router = Router::new().route("/todos", post(|| async { StatusCode::CREATED }));
+        protect(
+            router,
+            &CsrfConfig {
+                enabled: true,
+                cookie_secure: false,
+            },
+        )
+    }
+
+    fn post_todos(cookie: Option<&str>, header: Option<&str>) -> Request {
+        let mut builder = Request::builder().uri("/todos").method(Method::POST);
+        if let Some(cookie) = cookie {
+            builder = builder.header(COOKIE, format!("{}={}", CSRF_COOKIE, cookie));
+        }
+        if let Some(header) = header {
+            builder = builder.header(&CSRF_HEADER, header);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn csrf_double_submit() {
+        let app = app();
+
+        // issue token
+        let req = Request::builder()
+            .uri("/auth/csrf")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let cookie = res.headers()[SET_COOKIE].to_str().unwrap().to_string();
+        let token = cookie
+            .split(';')
+            .next()
+            .and_then(|pair| pair.split_once('='))
+            .map(|(_, value)| value.to_string())
+            .unwrap();
+        assert!(cookie.contains("SameSite=Strict"));
+
+        // mutating request without token is rejected
+        let res = app.clone().oneshot(post_todos(None, None)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        // mismatched token is rejected
+        let res = app
+            .clone()
+            .oneshot(post_todos(Some(&token), Some("forged")))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        // matching cookie and header pass through
+        let res = app
+            .oneshot(post_todos(Some(&token), Some(&token)))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+    }
+}
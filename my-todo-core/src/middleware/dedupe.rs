@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::body::{Body, Bytes};
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{HeaderMap, HeaderName, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use ring::digest::{digest, SHA256};
+use tokio::sync::OnceCell;
+
+/// Tells clients sharing an address, e.g. behind one proxy, apart. Optional.
+pub static CLIENT_ID_HEADER: HeaderName = HeaderName::from_static("x-client-id");
+
+pub const DEFAULT_DEDUPE_WINDOW: Duration = Duration::from_secs(2);
+
+/// axum's default body limit, which the routes behind this layer apply anyway.
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// A `POST` route whose repeated submissions within `window` are collapsed, configured with
+/// `DEDUPE_ROUTES`, e.g. the create routes `/todos` and `/todos/quick`, where a double-clicked
+/// "Add" would otherwise add the todo twice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DedupedRoute {
+    pub path: String,
+    pub window: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    path: String,
+    client: String,
+    body: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct Submission {
+    started: Instant,
+    window: Duration,
+    response: Arc<OnceCell<StoredResponse>>,
+}
+
+#[derive(Debug, Clone)]
+struct StoredResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+#[derive(Debug)]
+struct Dedupe {
+    routes: Vec<DedupedRoute>,
+    submissions: Mutex<HashMap<Key, Submission>>,
+}
+
+/// Answer a `POST` to one of `routes` that repeats an earlier one, from the same client with
+/// the same body and within the route's window, with the response of the earlier one instead
+/// of running it again. A repeat arriving while the first is still running waits for it.
+///
+/// Only successful responses are shared. A repeat of one refused or failed, like the retry of
+/// a SPA after fetching a CSRF token, runs again.
+///
+/// Clients are told apart by address and [`CLIENT_ID_HEADER`].
+pub fn collapse(router: Router, routes: Vec<DedupedRoute>) -> Router {
+    let dedupe = Arc::new(Dedupe {
+        routes,
+        submissions: Mutex::default(),
+    });
+    router.layer(middleware::from_fn_with_state(dedupe, collapse_repeats))
+}
+
+async fn collapse_repeats(
+    State(dedupe): State<Arc<Dedupe>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let route = dedupe
+        .routes
+        .iter()
+        .find(|route| route.path == req.uri().path());
+    let window = match route {
+        Some(route) if req.method() == Method::POST => route.window,
+        _ => return next.run(req).await,
+    };
+
+    let (parts, body) = req.into_parts();
+    let bytes = match axum::body::to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    let client_id = parts
+        .headers
+        .get(&CLIENT_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("-");
+    let address = connect_info.map(|ConnectInfo(addr)| addr.ip().to_string());
+    let key = Key {
+        path: parts.uri.path().to_string(),
+        client: format!("{} {}", address.as_deref().unwrap_or("-"), client_id),
+        body: digest(&SHA256, &bytes).as_ref().to_vec(),
+    };
+
+    let (response, repeated) = {
+        let mut submissions = dedupe.submissions.lock().unwrap();
+        // a submission still running stays, however long it takes
+        submissions.retain(|_, submission| {
+            submission.started.elapsed() < submission.window || !submission.response.initialized()
+        });
+        match submissions.get(&key) {
+            Some(submission) => (submission.response.clone(), true),
+            None => {
+                let response = Arc::new(OnceCell::new());
+                let submission = Submission {
+                    started: Instant::now(),
+                    window,
+                    response: response.clone(),
+                };
+                submissions.insert(key.clone(), submission);
+                (response, false)
+            }
+        }
+    };
+    if repeated {
+        metrics::counter!("requests_deduplicated_total").increment(1);
+        tracing::debug!("collapsing a repeated POST {}", parts.uri.path());
+    }
+    let mut request = Some((parts, next));
+    let stored = response
+        .get_or_init(|| {
+            let (parts, next) = request.take().expect("only taken here");
+            let req = Request::from_parts(parts, Body::from(bytes.clone()));
+            async move { store(next.run(req).await).await }
+        })
+        .await;
+    if !stored.status.is_success() {
+        {
+            let mut submissions = dedupe.submissions.lock().unwrap();
+            let current = submissions
+                .get(&key)
+                .is_some_and(|submission| Arc::ptr_eq(&submission.response, &response));
+            if current {
+                submissions.remove(&key);
+            }
+        }
+        // a repeat that waited for it runs on its own
+        if let Some((parts, next)) = request {
+            return next
+                .run(Request::from_parts(parts, Body::from(bytes)))
+                .await;
+        }
+    }
+
+    let mut res = Response::new(Body::from(stored.body.clone()));
+    *res.status_mut() = stored.status;
+    *res.headers_mut() = stored.headers.clone();
+    res
+}
+
+async fn store(res: Response) -> StoredResponse {
+    let (parts, body) = res.into_parts();
+    match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => StoredResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+        },
+        Err(e) => StoredResponse {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            headers: HeaderMap::new(),
+            body: Bytes::from(e.to_string()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::routing::post;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn app(window: Duration, calls: Arc<AtomicUsize>) -> Router {
+        let create = move || async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            (StatusCode::CREATED, format!("todo {}", call))
+        };
+        let router = Router::new()
+            .route("/todos", post(create.clone()))
+            .route("/other", post(create));
+        let routes = vec![DedupedRoute {
+            path: "/todos".to_string(),
+            window,
+        }];
+        collapse(router, routes)
+    }
+
+    async fn submit(app: &Router, uri: &str, client_id: &str, body: &str) -> (StatusCode, String) {
+        let req = Request::builder()
+            .uri(uri)
+            .method(Method::POST)
+            .header(&CLIENT_ID_HEADER, client_id)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let status = res.status();
+        let body = axum::body::to_bytes(res.into_body(), 1_000).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn collapse_double_submits() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = app(Duration::from_secs(60), calls.clone());
+
+        let (first, second) = tokio::join!(
+            submit(&app, "/todos", "a", "milk"),
+            submit(&app, "/todos", "a", "milk"),
+        );
+        assert_eq!(first, (StatusCode::CREATED, "todo 1".to_string()));
+        assert_eq!(second, first);
+        assert_eq!(submit(&app, "/todos", "a", "milk").await, first);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        assert_eq!(submit(&app, "/todos", "a", "eggs").await.1, "todo 2");
+        assert_eq!(submit(&app, "/todos", "b", "milk").await.1, "todo 3");
+        assert_eq!(submit(&app, "/other", "a", "milk").await.1, "todo 4");
+        assert_eq!(submit(&app, "/other", "a", "milk").await.1, "todo 5");
+    }
+
+    #[tokio::test]
+    async fn repeats_after_the_window_run_again() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = app(Duration::ZERO, calls.clone());
+        submit(&app, "/todos", "a", "milk").await;
+        submit(&app, "/todos", "a", "milk").await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn only_successes_are_shared() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let create = {
+            let calls = calls.clone();
+            move |headers: HeaderMap| async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                match headers.get("x-csrf-token") {
+                    Some(_) => (StatusCode::CREATED, "todo"),
+                    None => (StatusCode::FORBIDDEN, "no token"),
+                }
+            }
+        };
+        let routes = vec![DedupedRoute {
+            path: "/todos".to_string(),
+            window: Duration::from_secs(60),
+        }];
+        let app = collapse(Router::new().route("/todos", post(create)), routes);
+        let submit = |token: Option<&str>| {
+            let mut req = Request::builder()
+                .uri("/todos")
+                .method(Method::POST)
+                .header(&CLIENT_ID_HEADER, "a");
+            if let Some(token) = token {
+                req = req.header("x-csrf-token", token);
+            }
+            app.clone().oneshot(req.body(Body::from("milk")).unwrap())
+        };
+
+        let res = submit(None).await.unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        // the retry with a token runs, and is shared from then on
+        let res = submit(Some("token")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let res = submit(Some("token")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
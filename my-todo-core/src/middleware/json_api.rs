@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+
+use axum::body::{to_bytes, Body};
+use axum::extract::{MatchedPath, Request};
+use axum::http::header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE, VARY};
+use axum::http::{HeaderMap, HeaderValue};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde_json::{json, Map, Value};
+
+pub const JSON_API: &str = "application/vnd.api+json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResourceType {
+    Todos,
+    Labels,
+}
+
+impl ResourceType {
+    fn of(route: &str) -> Option<Self> {
+        match route {
+            "/todos" | "/todos/:id" | "/todos/next" | "/todos/starred" | "/todos/quick" => {
+                Some(ResourceType::Todos)
+            }
+            "/label" | "/label/:id" => Some(ResourceType::Labels),
+            _ => None,
+        }
+    }
+}
+
+/// Opt-in JSON:API mode: with `Accept: application/vnd.api+json`, todo and label responses are
+/// wrapped in JSON:API documents, todos with their labels as `included` resources, and errors
+/// become an `errors` array. Request bodies stay plain JSON.
+///
+/// Other clients get the plain responses as before; `Vary: Accept` keeps caches apart.
+pub async fn negotiate(req: Request, next: Next) -> Response {
+    let resource_type = req
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|route| ResourceType::of(route.as_str()));
+    let wanted = accepts_json_api(req.headers());
+    let mut res = next.run(req).await;
+    let Some(resource_type) = resource_type else {
+        return res;
+    };
+    res.headers_mut()
+        .append(VARY, HeaderValue::from_static("accept"));
+    if !wanted || res.status().is_informational() || res.status().is_redirection() {
+        return res;
+    }
+
+    let (mut parts, body) = res.into_parts();
+    let is_json = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with(mime::APPLICATION_JSON.as_ref()));
+    let Ok(bytes) = to_bytes(body, usize::MAX).await else {
+        return (parts, Body::empty()).into_response();
+    };
+    let document = if parts.status.is_success() {
+        match serde_json::from_slice::<Value>(&bytes) {
+            Ok(value) if is_json => document(resource_type, value),
+            _ => return (parts, bytes).into_response(),
+        }
+    } else {
+        json!({
+            "errors": [{
+                "status": parts.status.as_str(),
+                "detail": String::from_utf8_lossy(&bytes),
+            }]
+        })
+    };
+    parts
+        .headers
+        .insert(CONTENT_TYPE, HeaderValue::from_static(JSON_API));
+    parts.headers.remove(CONTENT_LENGTH);
+    (parts, document.to_string()).into_response()
+}
+
+fn accepts_json_api(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|range| range.split(';').next().unwrap_or_default().trim() == JSON_API)
+}
+
+/// `{"data": ..., "included": [...]}` for a resource or a list of them.
+fn document(resource_type: ResourceType, value: Value) -> Value {
+    let mut included = BTreeMap::new();
+    let data = match value {
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| resource(resource_type, item, &mut included))
+                .collect(),
+        ),
+        item => resource(resource_type, item, &mut included),
+    };
+    match resource_type {
+        ResourceType::Todos => json!({
+            "data": data,
+            "included": included.into_values().collect::<Vec<Value>>(),
+        }),
+        ResourceType::Labels => json!({ "data": data }),
+    }
+}
+
+/// `type`, `id` and the other fields as `attributes`. The labels of a todo become a
+/// relationship and are collected into `included` by id.
+fn resource(
+    resource_type: ResourceType,
+    item: Value,
+    included: &mut BTreeMap<i64, Value>,
+) -> Value {
+    let Value::Object(mut attributes) = item else {
+        return item;
+    };
+    let id = attributes.remove("id").unwrap_or_default();
+    let mut object = Map::new();
+    match resource_type {
+        ResourceType::Todos => {
+            object.insert("type".to_string(), "todos".into());
+            object.insert("id".to_string(), id.to_string().into());
+            let labels = match attributes.remove("labels") {
+                Some(Value::Array(labels)) => labels,
+                _ => vec![],
+            };
+            let linkage = labels
+                .into_iter()
+                .map(|label| {
+                    let label = resource(ResourceType::Labels, label, included);
+                    if let Some(id) = label["id"].as_str().and_then(|id| id.parse().ok()) {
+                        included.insert(id, label.clone());
+                    }
+                    json!({"type": "labels", "id": label["id"]})
+                })
+                .collect::<Vec<Value>>();
+            object.insert("attributes".to_string(), Value::Object(attributes));
+            object.insert(
+                "relationships".to_string(),
+                json!({"labels": {"data": linkage}}),
+            );
+        }
+        ResourceType::Labels => {
+            object.insert("type".to_string(), "labels".into());
+            object.insert("id".to_string(), id.to_string().into());
+            object.insert("attributes".to_string(), Value::Object(attributes));
+        }
+    }
+    Value::Object(object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn todo_documents() {
+        let todos = json!([
+            {"id": 1, "text": "a", "labels": [{"id": 2, "name": "home"}]},
+            {"id": 3, "text": "b", "labels": [{"id": 2, "name": "home"}, {"id": 1, "name": "x"}]},
+        ]);
+        let document = document(ResourceType::Todos, todos);
+        assert_eq!(
+            document["data"][1],
+            json!({
+                "type": "todos",
+                "id": "3",
+                "attributes": {"text": "b"},
+                "relationships": {"labels": {"data": [
+                    {"type": "labels", "id": "2"},
+                    {"type": "labels", "id": "1"},
+                ]}},
+            })
+        );
+        assert_eq!(
+            document["included"],
+            json!([
+                {"type": "labels", "id": "1", "attributes": {"name": "x"}},
+                {"type": "labels", "id": "2", "attributes": {"name": "home"}},
+            ])
+        );
+
+        let label = super::document(ResourceType::Labels, json!({"id": 5, "name": "work"}));
+        assert_eq!(
+            label,
+            json!({"data": {"type": "labels", "id": "5", "attributes": {"name": "work"}}})
+        );
+    }
+
+    #[test]
+    fn negotiate_media_type() {
+        let accept = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(ACCEPT, HeaderValue::from_static(value));
+            accepts_json_api(&headers)
+        };
+        assert!(accept(JSON_API));
+        assert!(accept("text/html, application/vnd.api+json;q=0.9"));
+        assert!(!accept("application/json"));
+        assert!(!accepts_json_api(&HeaderMap::new()));
+    }
+}
@@ -0,0 +1,195 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::{Request, State};
+use axum::http::header::{CONTENT_LANGUAGE, RETRY_AFTER};
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use sqlx::PgPool;
+
+use crate::i18n::{Locale, Message};
+
+/// How often the pool is probed.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Shedding low-priority routes while the database is saturated, configured with
+/// `LOAD_SHED_WAIT_MS` and `LOAD_SHED_ROUTES`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadShedding {
+    /// The pool counts as saturated once getting a connection takes this long.
+    pub max_wait: Duration,
+    /// Path prefixes of the routes that can wait, matched by whole segments: `/reports`
+    /// covers `/reports/weekly` but not `/reportsX`.
+    pub routes: Vec<String>,
+}
+
+/// Exports and reports, which nobody is staring at a spinner for.
+pub fn default_routes() -> Vec<String> {
+    [
+        "/todos/stream",
+        "/label/export",
+        "/reports",
+        "/admin/reports",
+    ]
+    .into_iter()
+    .map(str::to_string)
+    .collect()
+}
+
+/// Whether the pool is saturated, as of the last probe by [`monitor`].
+#[derive(Debug, Default)]
+pub struct PoolPressure {
+    saturated: AtomicBool,
+}
+
+impl PoolPressure {
+    pub fn is_saturated(&self) -> bool {
+        self.saturated.load(Ordering::Relaxed)
+    }
+
+    /// Record a probe that waited `wait` for a connection, returning whether the pool is
+    /// saturated now. It stays so until waits drop below half of `max_wait`, so shedding
+    /// doesn't flap around the threshold.
+    fn record(&self, wait: Duration, max_wait: Duration) -> bool {
+        let saturated = if self.is_saturated() {
+            wait >= max_wait / 2
+        } else {
+            wait >= max_wait
+        };
+        self.saturated.store(saturated, Ordering::Relaxed);
+        saturated
+    }
+}
+
+/// Time how long getting a connection from `pool` takes, every [`SAMPLE_INTERVAL`].
+pub async fn monitor(pool: PgPool, pressure: Arc<PoolPressure>, max_wait: Duration) {
+    let mut ticker = tokio::time::interval(SAMPLE_INTERVAL);
+    loop {
+        ticker.tick().await;
+        let started = Instant::now();
+        // a probe stuck longer than this says all there is to say
+        let acquired = tokio::time::timeout(max_wait * 2, pool.acquire()).await;
+        let wait = started.elapsed();
+        drop(acquired);
+
+        let was_saturated = pressure.is_saturated();
+        let saturated = pressure.record(wait, max_wait);
+        metrics::gauge!("db_pool_saturated").set(if saturated { 1.0 } else { 0.0 });
+        match (was_saturated, saturated) {
+            (false, true) => tracing::warn!(
+                wait_ms = wait.as_millis() as u64,
+                "database pool saturated, shedding low-priority requests"
+            ),
+            (true, false) => tracing::info!("database pool recovered, no longer shedding"),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Shedder {
+    pressure: Arc<PoolPressure>,
+    routes: Vec<String>,
+}
+
+/// Refuse requests to the `routes` of `shedding` with 503 and `Retry-After` while
+/// `pressure` says the pool is saturated, so the connections left go to interactive
+/// requests. Everything else is let through as usual.
+pub fn shed(router: Router, pressure: Arc<PoolPressure>, shedding: &LoadShedding) -> Router {
+    let shedder = Arc::new(Shedder {
+        pressure,
+        routes: shedding.routes.clone(),
+    });
+    router.layer(middleware::from_fn_with_state(shedder, shed_low_priority))
+}
+
+async fn shed_low_priority(
+    State(shedder): State<Arc<Shedder>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let low_priority = shedder
+        .routes
+        .iter()
+        .find(|prefix| covers(prefix, req.uri().path()));
+    if let Some(prefix) = low_priority.filter(|_| shedder.pressure.is_saturated()) {
+        // labelled with the configured route, clients can't mint a series per path
+        metrics::counter!("requests_shed_total", "path" => prefix.clone()).increment(1);
+        let locale = Locale::from_headers(req.headers());
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(RETRY_AFTER, "5"), (CONTENT_LANGUAGE, locale.tag())],
+            Message::Overloaded.text(locale),
+        )
+            .into_response();
+    }
+    next.run(req).await
+}
+
+/// Whether `path` is `prefix` or lies below it.
+fn covers(prefix: &str, path: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/') || prefix.ends_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[test]
+    fn saturation_has_hysteresis() {
+        let pressure = PoolPressure::default();
+        let max_wait = Duration::from_millis(100);
+        assert!(!pressure.record(Duration::from_millis(60), max_wait));
+        assert!(pressure.record(Duration::from_millis(100), max_wait));
+        assert!(pressure.record(Duration::from_millis(60), max_wait));
+        assert!(!pressure.record(Duration::from_millis(40), max_wait));
+    }
+
+    #[test]
+    fn prefixes_cover_whole_segments() {
+        assert!(covers("/reports", "/reports"));
+        assert!(covers("/reports", "/reports/weekly"));
+        assert!(covers("/reports/", "/reports/weekly"));
+        assert!(!covers("/reports", "/reportsX"));
+        assert!(!covers("/reports", "/report"));
+        assert!(!covers("/reports", "/admin/reports"));
+    }
+
+    #[tokio::test]
+    async fn shed_low_priority_routes_while_saturated() {
+        let router = Router::new()
+            .route("/todos", get(|| async { "todos" }))
+            .route("/todos/stream", get(|| async { "export" }))
+            .route("/todos/streamed", get(|| async { "not an export" }));
+        let pressure = Arc::new(PoolPressure::default());
+        let shedding = LoadShedding {
+            max_wait: Duration::from_millis(100),
+            routes: vec!["/todos/stream".to_string()],
+        };
+        let app = shed(router, pressure.clone(), &shedding);
+        let status = |uri: &'static str| {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let app = app.clone();
+            async move { app.oneshot(req).await.unwrap().status() }
+        };
+
+        assert_eq!(status("/todos/stream").await, StatusCode::OK);
+        pressure.record(Duration::from_secs(1), shedding.max_wait);
+        assert_eq!(
+            status("/todos/stream").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(status("/todos").await, StatusCode::OK);
+        assert_eq!(status("/todos/streamed").await, StatusCode::OK);
+        pressure.record(Duration::ZERO, shedding.max_wait);
+        assert_eq!(status("/todos/stream").await, StatusCode::OK);
+    }
+}
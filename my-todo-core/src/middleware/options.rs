@@ -0,0 +1,122 @@
+use axum::extract::{Request, State};
+use axum::http::header::{HeaderValue, ACCESS_CONTROL_REQUEST_METHOD, ALLOW, ORIGIN};
+use axum::http::{HeaderMap, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use tower::ServiceExt;
+use tower_http::cors::CorsLayer;
+
+/// Answer `OPTIONS` on every route of `router` with `204` and the methods it serves in `Allow`.
+///
+/// Axum already serves `HEAD` for `GET` routes and lists the methods of a route when it
+/// rejects another one with `405`; this reuses that list. It is only added after the route
+/// layers ran, so `router` is wrapped as a whole and should be complete: requests reach it as
+/// the fallback, and layers added afterwards see no [`axum::extract::MatchedPath`].
+///
+/// `cors` takes every `OPTIONS` request for a preflight, so it is added here too and only
+/// gets the real ones, those with `Origin` and `Access-Control-Request-Method`.
+pub fn answer_options(router: Router, cors: CorsLayer) -> Router {
+    Router::new()
+        .fallback_service(router.clone().layer(cors))
+        .layer(middleware::from_fn_with_state(router, options))
+}
+
+fn is_preflight(headers: &HeaderMap) -> bool {
+    headers.contains_key(ORIGIN) && headers.contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+}
+
+async fn options(State(router): State<Router>, req: Request, next: Next) -> Response {
+    if req.method() != Method::OPTIONS || is_preflight(req.headers()) {
+        return next.run(req).await;
+    }
+    let res = match router.oneshot(req).await {
+        Ok(res) => res,
+        Err(never) => match never {},
+    };
+    if res.status() != StatusCode::METHOD_NOT_ALLOWED {
+        return res;
+    }
+    let Some(allow) = res
+        .headers()
+        .get(ALLOW)
+        .and_then(|allow| allow.to_str().ok())
+    else {
+        return res;
+    };
+    match HeaderValue::try_from(format!("{},OPTIONS", allow)) {
+        Ok(allow) => (StatusCode::NO_CONTENT, [(ALLOW, allow)]).into_response(),
+        Err(_) => res,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn options_lists_allowed_methods() {
+        let app = answer_options(
+            Router::new()
+                .route("/todos", get(|| async { "[]" }).post(|| async { "{}" }))
+                .route("/todos/:id", get(|| async { "{}" }).delete(|| async { "" })),
+            CorsLayer::new()
+                .allow_origin(HeaderValue::from_static("http://localhost:3000"))
+                .allow_methods([Method::GET, Method::POST]),
+        );
+        let request = |method: Method, uri: &str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let res = app
+            .clone()
+            .oneshot(request(Method::OPTIONS, "/todos"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(res.headers()[ALLOW], "GET,HEAD,POST,OPTIONS");
+        let res = app
+            .clone()
+            .oneshot(request(Method::OPTIONS, "/todos/1"))
+            .await
+            .unwrap();
+        assert_eq!(res.headers()[ALLOW], "GET,HEAD,DELETE,OPTIONS");
+
+        let res = app
+            .clone()
+            .oneshot(request(Method::HEAD, "/todos"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(res.into_body(), 1_000).await.unwrap();
+        assert!(body.is_empty());
+
+        // other methods are still rejected
+        let res = app
+            .clone()
+            .oneshot(request(Method::PUT, "/todos"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        // preflights are left to CORS
+        let mut preflight = request(Method::OPTIONS, "/todos");
+        let headers = preflight.headers_mut();
+        headers.insert(ORIGIN, HeaderValue::from_static("http://localhost:3000"));
+        headers.insert(
+            ACCESS_CONTROL_REQUEST_METHOD,
+            HeaderValue::from_static("POST"),
+        );
+        let res = app.oneshot(preflight).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["access-control-allow-methods"], "GET,POST");
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use axum::Router;
+
+pub static X_RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+pub static X_RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+pub static X_RATELIMIT_RESET: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+/// Requests a client may make per `window`, `RATE_LIMIT_PER_MINUTE` in the config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub limit: u64,
+    pub window: Duration,
+}
+
+/// Requests counted per client address in fixed windows. Behind a proxy every request comes
+/// from the proxy's address, so they all share one budget.
+#[derive(Debug)]
+struct Accounting {
+    rate_limit: RateLimit,
+    windows: Mutex<Windows>,
+}
+
+#[derive(Debug)]
+struct Windows {
+    by_client: HashMap<IpAddr, (Instant, u64)>,
+    /// Windows that ended are dropped at most once per window, not on every request.
+    swept_at: Instant,
+}
+
+impl Accounting {
+    fn new(rate_limit: RateLimit) -> Self {
+        Accounting {
+            rate_limit,
+            windows: Mutex::new(Windows {
+                by_client: HashMap::new(),
+                swept_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Count a request of `client`, returning the requests it made in the current window and
+    /// when that window ends.
+    fn record(&self, client: IpAddr, now: Instant) -> (u64, Instant) {
+        let window = self.rate_limit.window;
+        let mut windows = self.windows.lock().unwrap();
+        if now.duration_since(windows.swept_at) >= window {
+            windows
+                .by_client
+                .retain(|_, (started, _)| now.duration_since(*started) < window);
+            windows.swept_at = now;
+        }
+        let (started, requests) = windows.by_client.entry(client).or_insert((now, 0));
+        if now.duration_since(*started) >= window {
+            (*started, *requests) = (now, 0);
+        }
+        *requests += 1;
+        (*requests, *started + window)
+    }
+}
+
+/// Tell clients how much of their budget is left with `X-RateLimit-Limit`,
+/// `X-RateLimit-Remaining` and `X-RateLimit-Reset` (seconds until the window ends). Nothing is
+/// refused: the headers let client authors slow down before there are hard limits.
+///
+/// The client address comes from [`ConnectInfo`], so the server has to be started with
+/// `into_make_service_with_connect_info`.
+pub fn annotate(router: Router, rate_limit: RateLimit) -> Router {
+    let accounting = Arc::new(Accounting::new(rate_limit));
+    router.layer(middleware::from_fn_with_state(accounting, add_headers))
+}
+
+async fn add_headers(
+    State(accounting): State<Arc<Accounting>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let client = connect_info
+        .map(|ConnectInfo(addr)| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    let now = Instant::now();
+    let (requests, reset_at) = accounting.record(client, now);
+    let mut res = next.run(req).await;
+
+    let limit = accounting.rate_limit.limit;
+    let reset = reset_at.saturating_duration_since(now);
+    // rounded up, so a client waiting that long is always in the next window
+    let reset_secs = reset.as_secs() + u64::from(reset.subsec_nanos() > 0);
+    let headers = res.headers_mut();
+    headers.insert(X_RATELIMIT_LIMIT.clone(), HeaderValue::from(limit));
+    headers.insert(
+        X_RATELIMIT_REMAINING.clone(),
+        HeaderValue::from(limit.saturating_sub(requests)),
+    );
+    headers.insert(X_RATELIMIT_RESET.clone(), HeaderValue::from(reset_secs));
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::extract::connect_info::MockConnectInfo;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn count_requests_per_client() {
+        let rate_limit = RateLimit {
+            limit: 2,
+            window: Duration::from_secs(60),
+        };
+        let router = Router::new().route("/todos", get(|| async { StatusCode::OK }));
+        let app = annotate(router, rate_limit);
+        let request = |client: [u8; 4]| {
+            let app = app
+                .clone()
+                .layer(MockConnectInfo(SocketAddr::from((client, 50000))));
+            async move {
+                let req = Request::builder()
+                    .uri("/todos")
+                    .body(Body::empty())
+                    .unwrap();
+                let res = app.oneshot(req).await.unwrap();
+                let header = |name: &HeaderName| res.headers()[name].to_str().unwrap().to_string();
+                (header(&X_RATELIMIT_REMAINING), header(&X_RATELIMIT_RESET))
+            }
+        };
+
+        let (remaining, reset) = request([10, 0, 0, 1]).await;
+        assert_eq!(remaining, "1");
+        assert!((59..=60).contains(&reset.parse::<u64>().unwrap()));
+        assert_eq!(request([10, 0, 0, 1]).await.0, "0");
+        // not refused, just reported
+        assert_eq!(request([10, 0, 0, 1]).await.0, "0");
+        assert_eq!(request([10, 0, 0, 2]).await.0, "1");
+    }
+
+    #[test]
+    fn windows_restart() {
+        let accounting = Accounting::new(RateLimit {
+            limit: 5,
+            window: Duration::from_secs(1),
+        });
+        let client = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let start = Instant::now();
+        accounting.record(client, start);
+        assert_eq!(accounting.record(client, start).0, 2);
+
+        let later = start + Duration::from_secs(2);
+        assert_eq!(
+            accounting.record(client, later),
+            (1, later + Duration::from_secs(1))
+        );
+        let other = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        accounting.record(other, later + Duration::from_secs(2));
+        // the sweep dropped the first client's ended window
+        assert_eq!(accounting.windows.lock().unwrap().by_client.len(), 1);
+    }
+}
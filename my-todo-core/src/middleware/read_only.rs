@@ -0,0 +1,86 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::header::{CONTENT_LANGUAGE, RETRY_AFTER};
+use axum::http::{Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+
+use crate::i18n::{Locale, Message};
+
+/// Runtime switch for maintenance windows: while enabled, only safe methods are served.
+#[derive(Debug, Default)]
+pub struct ReadOnlyMode(AtomicBool);
+
+impl ReadOnlyMode {
+    pub fn new(enabled: bool) -> Self {
+        ReadOnlyMode(AtomicBool::new(enabled))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, enabled: bool) {
+        self.0.store(enabled, Ordering::Relaxed);
+    }
+}
+
+pub fn guard(router: Router, mode: Arc<ReadOnlyMode>) -> Router {
+    router.layer(middleware::from_fn_with_state(mode, reject_writes))
+}
+
+async fn reject_writes(
+    State(mode): State<Arc<ReadOnlyMode>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let safe_method = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if mode.is_enabled() && !safe_method {
+        let locale = Locale::from_headers(req.headers());
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(RETRY_AFTER, "60"), (CONTENT_LANGUAGE, locale.tag())],
+            Message::ReadOnly.text(locale),
+        )
+            .into_response();
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn read_only_rejects_writes_only() {
+        let mode = Arc::new(ReadOnlyMode::new(true));
+        let router = Router::new().route(
+            "/todos",
+            get(|| async { StatusCode::OK }).post(|| async { StatusCode::CREATED }),
+        );
+        let app = guard(router, mode.clone());
+        let request = |method: Method| {
+            Request::builder()
+                .uri("/todos")
+                .method(method)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let res = app.clone().oneshot(request(Method::GET)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = app.clone().oneshot(request(Method::POST)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        mode.set(false);
+        let res = app.oneshot(request(Method::POST)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+    }
+}
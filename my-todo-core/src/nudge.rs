@@ -0,0 +1,129 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::config::StaleConfig;
+use crate::events::{self, Event};
+use crate::jobs::Jobs;
+
+pub const JOB: &str = "nudge_stale";
+
+/// On `config.schedule`, nudge open todos untouched for `config.after`: they show up under
+/// `GET /todos?stale=true` and, with `outbox`, a `todo.stale` event is sent once per nudge.
+/// Changing the todo clears its nudge, so it is nudged again when it goes stale again, and
+/// completing it deletes the nudge along with the update.
+/// Schedules nothing without `config.after`.
+pub fn schedule(jobs: Jobs, pool: PgPool, config: &StaleConfig, outbox: bool) -> Jobs {
+    let Some(after) = config.after else {
+        return jobs;
+    };
+    jobs.register(JOB, move |_| {
+        let pool = pool.clone();
+        async move {
+            let nudged = nudge_stale(&pool, after, outbox).await?;
+            if nudged > 0 {
+                tracing::info!("nudged {} stale todos", nudged);
+            }
+            Ok(())
+        }
+    })
+    .recurring(JOB, config.schedule.clone())
+}
+
+/// The number of todos nudged.
+async fn nudge_stale(pool: &PgPool, after: Duration, outbox: bool) -> anyhow::Result<usize> {
+    let cutoff = Utc::now() - chrono::Duration::from_std(after)?;
+    let mut tx = pool.begin().await?;
+    let ids = sqlx::query_scalar::<_, i32>(
+        r#"
+        insert into todo_nudges (todo_id, nudged_at)
+        select todos.id, now()
+        from todos
+                 left outer join todo_nudges n on n.todo_id = todos.id
+        where not todos.completed
+          and todos.updated_at < $1
+          and (n.nudged_at is null or n.nudged_at < todos.updated_at)
+        on conflict (todo_id) do update set nudged_at = excluded.nudged_at
+        returning todo_id
+        "#,
+    )
+    .bind(cutoff)
+    .fetch_all(&mut *tx)
+    .await?;
+    if outbox {
+        for &id in &ids {
+            events::record(&mut tx, &Event::TodoStale { id }).await?;
+        }
+    }
+    tx.commit().await?;
+    metrics::counter!("todos_nudged_total").increment(ids.len() as u64);
+    Ok(ids.len())
+}
+
+#[cfg(test)]
+#[cfg(feature = "db-test")]
+mod test_psql_repo {
+    use super::*;
+    use crate::repositories::test_db::TestDb;
+    use crate::repositories::todo::{
+        CreateTodo, TodoEntity, TodoQuery, TodoRepository, UpdateTodo,
+    };
+
+    fn ids(todos: Vec<TodoEntity>) -> Vec<i32> {
+        todos.into_iter().map(|todo| todo.id).collect()
+    }
+
+    #[tokio::test]
+    async fn nudges_untouched_open_todos_once() {
+        let db = TestDb::new().await;
+        let repo = db.todo_repo();
+        let forgotten = repo
+            .create(CreateTodo::builder("forgotten").build())
+            .await
+            .expect("[create] returned Err");
+        let recent = repo
+            .create(CreateTodo::builder("recent").build())
+            .await
+            .expect("[create] returned Err");
+        sqlx::query("update todos set updated_at = now() - interval '20 days' where id = $1")
+            .bind(forgotten.id)
+            .execute(&db.pool)
+            .await
+            .expect("failed to age todo");
+        let stale = || TodoQuery {
+            stale: Some(true),
+            ..TodoQuery::default()
+        };
+        let fourteen_days = Duration::from_secs(14 * 24 * 3600);
+
+        assert_eq!(nudge_stale(&db.pool, fourteen_days, true).await.unwrap(), 1);
+        assert_eq!(ids(repo.all(stale()).await.unwrap()), vec![forgotten.id]);
+        let not_stale = repo
+            .all(TodoQuery {
+                stale: Some(false),
+                ..TodoQuery::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(ids(not_stale), vec![recent.id]);
+        let events =
+            sqlx::query_scalar::<_, i64>("select count(*) from outbox where topic = 'todo.stale'")
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(events, 1);
+
+        // already nudged
+        assert_eq!(nudge_stale(&db.pool, fourteen_days, true).await.unwrap(), 0);
+
+        // touching it clears the nudge
+        repo.update(
+            forgotten.id,
+            UpdateTodo::builder().text("remembered").build(),
+        )
+        .await
+        .expect("[update] returned Err");
+        assert!(repo.all(stale()).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use sqlx::migrate::Migrator;
+use sqlx::PgPool;
+
+/// The migrations this build was compiled with.
+static MIGRATOR: Migrator = sqlx::migrate!("../migrations");
+
+/// How the schema of a database compares to the migrations of this build.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MigrationStatus {
+    /// The latest migration applied successfully.
+    pub current: Option<i64>,
+    /// Migrations of this build not applied yet.
+    pub pending: Vec<i64>,
+    /// Applied migrations this build doesn't know, left by a newer release.
+    pub unknown: Vec<i64>,
+    /// Migrations that started but didn't finish.
+    pub failed: Vec<i64>,
+    /// Applied migrations whose file has been edited since.
+    pub modified: Vec<i64>,
+}
+
+impl MigrationStatus {
+    pub fn is_current(&self) -> bool {
+        self.pending.is_empty()
+            && self.unknown.is_empty()
+            && self.failed.is_empty()
+            && self.modified.is_empty()
+    }
+}
+
+impl fmt::Display for MigrationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.current {
+            Some(version) => write!(f, "at {}", version)?,
+            None => write!(f, "none applied")?,
+        }
+        for (what, versions) in [
+            ("pending", &self.pending),
+            ("unknown to this build", &self.unknown),
+            ("failed", &self.failed),
+            ("modified since applied", &self.modified),
+        ] {
+            if !versions.is_empty() {
+                let versions = versions.iter().map(i64::to_string).collect::<Vec<_>>();
+                write!(f, ", {}: {}", what, versions.join(" "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compare the migrations recorded in `pool` with those of this build.
+pub async fn migration_status(pool: &PgPool) -> anyhow::Result<MigrationStatus> {
+    let recorded =
+        sqlx::query_scalar::<_, Option<String>>("select to_regclass('_sqlx_migrations')::text")
+            .fetch_one(pool)
+            .await?
+            .is_some();
+    let applied = if recorded {
+        sqlx::query_as::<_, (i64, bool, Vec<u8>)>(
+            "select version, success, checksum from _sqlx_migrations order by version",
+        )
+        .fetch_all(pool)
+        .await?
+    } else {
+        vec![]
+    };
+
+    let known = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| (migration.version, migration.checksum.as_ref()))
+        .collect::<HashMap<_, _>>();
+    let mut status = MigrationStatus::default();
+    for (version, success, checksum) in &applied {
+        match known.get(version) {
+            None => status.unknown.push(*version),
+            Some(_) if !success => status.failed.push(*version),
+            Some(known) if *known != checksum.as_slice() => status.modified.push(*version),
+            Some(_) => {}
+        }
+        if *success {
+            status.current = Some(*version);
+        }
+    }
+    let mut pending = known
+        .keys()
+        .filter(|version| !applied.iter().any(|(applied, ..)| applied == *version))
+        .copied()
+        .collect::<Vec<_>>();
+    pending.sort();
+    status.pending = pending;
+    Ok(status)
+}
+
+/// The outcome of one check of `my-todo --check`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed(String),
+    Failed(String),
+    /// Not run because a check it depends on failed.
+    Skipped,
+}
+
+/// What `my-todo --check` found, one line per check.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub checks: Vec<(&'static str, Outcome)>,
+}
+
+impl Report {
+    pub fn record(&mut self, name: &'static str, outcome: Outcome) {
+        self.checks.push((name, outcome));
+    }
+
+    /// The first check that failed.
+    pub fn failure(&self) -> Option<(&'static str, &str)> {
+        self.checks
+            .iter()
+            .find_map(|(name, outcome)| match outcome {
+                Outcome::Failed(reason) => Some((*name, reason.as_str())),
+                _ => None,
+            })
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, outcome) in &self.checks {
+            match outcome {
+                Outcome::Passed(detail) => writeln!(f, "ok    {:<12}{}", name, detail)?,
+                Outcome::Failed(reason) => writeln!(f, "FAIL  {:<12}{}", name, reason)?,
+                Outcome::Skipped => writeln!(f, "skip  {}", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_lists_every_check() {
+        let mut report = Report::default();
+        report.record(
+            "config",
+            Outcome::Passed("listening on 0.0.0.0:8078".to_string()),
+        );
+        report.record(
+            "database",
+            Outcome::Failed("connection refused".to_string()),
+        );
+        report.record("migrations", Outcome::Skipped);
+        assert_eq!(
+            report.to_string(),
+            "ok    config      listening on 0.0.0.0:8078\n\
+             FAIL  database    connection refused\n\
+             skip  migrations\n"
+        );
+        assert_eq!(report.failure(), Some(("database", "connection refused")));
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "db-test")]
+mod test_psql_repo {
+    use super::*;
+    use crate::repositories::test_db::TestDb;
+
+    #[tokio::test]
+    async fn compare_applied_migrations() {
+        let db = TestDb::new().await;
+        let status = migration_status(&db.pool).await.unwrap();
+        assert!(status.is_current(), "{}", status);
+        let latest = MIGRATOR.iter().map(|migration| migration.version).max();
+        assert_eq!(status.current, latest);
+
+        let latest = latest.unwrap();
+        sqlx::query("delete from _sqlx_migrations where version = $1")
+            .bind(latest)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "insert into _sqlx_migrations (version, description, success, checksum, \
+             execution_time) values (99990101000000, 'from the future', true, '\\x00', 0)",
+        )
+        .execute(&db.pool)
+        .await
+        .unwrap();
+        let status = migration_status(&db.pool).await.unwrap();
+        assert!(!status.is_current());
+        assert_eq!(status.pending, [latest]);
+        assert_eq!(status.unknown, [99990101000000]);
+        assert_eq!(status.current, Some(99990101000000));
+    }
+}
@@ -0,0 +1,36 @@
+//! ULIDs identifying todos and labels in the API, see the `public_ids` migration. The
+//! database generates them on insert.
+
+/// Crockford's base32, without `I`, `L`, `O` and `U`.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const LEN: usize = 26;
+
+/// Whether `value` is a ULID as the database writes them, upper case.
+pub fn is_valid(value: &str) -> bool {
+    value.len() == LEN
+        // 26 characters hold 130 bits, the first one only 3 of them
+        && value.starts_with(|c: char| ('0'..='7').contains(&c))
+        && value.bytes().all(|b| ALPHABET.contains(&b))
+}
+
+/// The in-memory repositories have no random ids to hand out; the serial id, zero padded, is
+/// a valid ULID that keeps their results predictable.
+#[cfg(any(test, feature = "test-util"))]
+pub fn from_serial(id: i32) -> String {
+    format!("{:0>width$}", id, width = LEN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_ulids() {
+        assert!(is_valid("01ARZ3NDEKTSV4RRFFQ69G5FAV"));
+        assert!(is_valid(&from_serial(42)));
+        assert!(!is_valid("01arz3ndektsv4rrffq69g5fav"));
+        assert!(!is_valid("81ARZ3NDEKTSV4RRFFQ69G5FAV"));
+        assert!(!is_valid("01ARZ3NDEKTSV4RRFFQ69G5FAI"));
+        assert!(!is_valid("42"));
+    }
+}
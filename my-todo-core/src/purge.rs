@@ -0,0 +1,227 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::config::PurgeConfig;
+use crate::jobs::Jobs;
+use crate::repositories::todo::{TodoRepository, UpdateTodo};
+use crate::repositories::RepositoryError;
+
+/// Todos looked up per query, so a large backlog is archived in steps.
+const BATCH_SIZE: i64 = 500;
+
+pub const JOB: &str = "archive_completed";
+
+/// What the next purge would archive, as of now.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PurgeReport {
+    /// Todos completed before this are archived. `None` when purging is off.
+    pub cutoff: Option<DateTime<Utc>>,
+    pub count: i64,
+}
+
+/// `GET /purge/preview`, a dry run of the purge for operators, mounted under `/admin`.
+pub fn routes(pool: PgPool, config: &PurgeConfig) -> Router {
+    Router::new()
+        .route("/purge/preview", get(preview))
+        .layer(Extension(pool))
+        .layer(Extension(PurgeAfter(config.completed_after)))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PurgeAfter(Option<Duration>);
+
+async fn preview(
+    Extension(pool): Extension<PgPool>,
+    Extension(PurgeAfter(completed_after)): Extension<PurgeAfter>,
+) -> Result<Json<PurgeReport>, StatusCode> {
+    let Some(completed_after) = completed_after else {
+        return Ok(Json(PurgeReport {
+            cutoff: None,
+            count: 0,
+        }));
+    };
+    let report = count_archivable(&pool, completed_after)
+        .await
+        .map_err(|e| {
+            tracing::error!("failed to preview the purge: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(Json(report))
+}
+
+fn cutoff(older_than: Duration) -> anyhow::Result<DateTime<Utc>> {
+    Ok(Utc::now() - chrono::Duration::from_std(older_than)?)
+}
+
+async fn count_archivable(pool: &PgPool, older_than: Duration) -> anyhow::Result<PurgeReport> {
+    let cutoff = cutoff(older_than)?;
+    let count = sqlx::query_scalar::<_, i64>(
+        r#"select count(*) from todos where completed and not archived and completed_at < $1"#,
+    )
+    .bind(cutoff)
+    .fetch_one(pool)
+    .await?;
+    Ok(PurgeReport {
+        cutoff: Some(cutoff),
+        count,
+    })
+}
+
+/// On `config.schedule`, archive todos completed longer than `config.completed_after` ago.
+/// Schedules nothing without `completed_after`. Nothing is deleted, archived todos are only
+/// left out of listings.
+///
+/// Todos are archived through the repository, so each gets its history entry and outbox event
+/// like an update through the API.
+pub fn schedule<R: TodoRepository>(
+    jobs: Jobs,
+    pool: PgPool,
+    todo_repo: R,
+    config: &PurgeConfig,
+) -> Jobs {
+    let Some(completed_after) = config.completed_after else {
+        return jobs;
+    };
+    let todo_repo = Arc::new(todo_repo);
+    jobs.register(JOB, move |_| {
+        let pool = pool.clone();
+        let todo_repo = todo_repo.clone();
+        async move {
+            let archived = archive_completed(&pool, &todo_repo, completed_after).await?;
+            if archived > 0 {
+                tracing::info!("archived {} completed todos", archived);
+            }
+            Ok(())
+        }
+    })
+    .recurring(JOB, config.schedule.clone())
+}
+
+/// The number of todos archived.
+async fn archive_completed<R: TodoRepository>(
+    pool: &PgPool,
+    todo_repo: &R,
+    older_than: Duration,
+) -> anyhow::Result<usize> {
+    let cutoff = cutoff(older_than)?;
+    let mut archived = 0;
+    loop {
+        let ids = sqlx::query_scalar::<_, i32>(
+            r#"
+            select id from todos where completed and not archived and completed_at < $1
+            order by id limit $2
+            "#,
+        )
+        .bind(cutoff)
+        .bind(BATCH_SIZE)
+        .fetch_all(pool)
+        .await?;
+        for &id in &ids {
+            match todo_repo
+                .update(id, UpdateTodo::builder().archived(true).build())
+                .await
+            {
+                Ok(_) => {
+                    archived += 1;
+                    metrics::counter!("todos_archived_total").increment(1);
+                }
+                // deleted in the meantime
+                Err(e)
+                    if matches!(
+                        e.downcast_ref::<RepositoryError>(),
+                        Some(RepositoryError::NotFound(_))
+                    ) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if (ids.len() as i64) < BATCH_SIZE {
+            return Ok(archived);
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "db-test")]
+mod test_psql_repo {
+    use super::*;
+    use crate::repositories::test_db::TestDb;
+    use crate::repositories::todo::{CreateTodo, TodoEntity, TodoQuery};
+
+    #[tokio::test]
+    async fn archives_old_completed_todos() {
+        let db = TestDb::new().await;
+        let repo = db.todo_repo();
+        let mut ids = vec![];
+        for (text, completed) in [
+            ("done long ago", true),
+            ("open", false),
+            ("done long ago, edited since", true),
+            ("done lately", true),
+        ] {
+            let todo = repo
+                .create(CreateTodo::builder(text).build())
+                .await
+                .expect("[create] returned Err");
+            repo.update(todo.id, UpdateTodo::builder().completed(completed).build())
+                .await
+                .expect("[update] returned Err");
+            ids.push(todo.id);
+        }
+        sqlx::query(
+            "update todos set completed_at = now() - interval '40 days', \
+             updated_at = now() - interval '40 days' where id = any($1)",
+        )
+        .bind(&ids[..2])
+        .execute(&db.pool)
+        .await
+        .expect("failed to age todos");
+        // edits since the completion don't keep a todo from being archived
+        sqlx::query("update todos set completed_at = now() - interval '40 days' where id = $1")
+            .bind(ids[2])
+            .execute(&db.pool)
+            .await
+            .expect("failed to age todo");
+        // nor does an old last change make a recent completion old
+        sqlx::query("update todos set updated_at = now() - interval '40 days' where id = $1")
+            .bind(ids[3])
+            .execute(&db.pool)
+            .await
+            .expect("failed to age todo");
+
+        let older_than = Duration::from_secs(30 * 24 * 3600);
+        let report = count_archivable(&db.pool, older_than)
+            .await
+            .expect("[count_archivable] returned Err");
+        assert_eq!(report.count, 2);
+        let archived = archive_completed(&db.pool, &repo, older_than)
+            .await
+            .expect("[archive_completed] returned Err");
+        assert_eq!(archived, 2);
+        let report = count_archivable(&db.pool, older_than).await.unwrap();
+        assert_eq!(report.count, 0);
+
+        // archived todos are kept, just no longer listed
+        let archived = repo.find(ids[0]).await.expect("[find] returned Err");
+        assert!(archived.archived);
+        assert!(repo.find(ids[2]).await.unwrap().archived);
+        assert!(!repo.find(ids[1]).await.unwrap().archived);
+        assert!(!repo.find(ids[3]).await.unwrap().archived);
+        let listed = |archived| {
+            repo.all(TodoQuery {
+                archived,
+                ..TodoQuery::default()
+            })
+        };
+        let ids_of =
+            |todos: Vec<TodoEntity>| todos.into_iter().map(|todo| todo.id).collect::<Vec<_>>();
+        assert_eq!(ids_of(listed(false).await.unwrap()), [ids[1], ids[3]]);
+        assert_eq!(ids_of(listed(true).await.unwrap()), [ids[0], ids[2]]);
+    }
+}
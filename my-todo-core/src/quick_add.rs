@@ -0,0 +1,232 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::repositories::todo::Priority;
+
+/// Body of `POST /todos/quick`, e.g. `{"text": "pay rent tomorrow 5pm #finance !high"}`.
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct QuickAdd {
+    #[validate(length(min = 1, max = 1024))]
+    pub text: String,
+    /// Dates and times in `text` are read in this offset from UTC, e.g. `540` for JST.
+    #[serde(default)]
+    #[validate(range(min = -1439, max = 1439))]
+    pub utc_offset_minutes: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuickTodo {
+    pub text: String,
+    pub due_at: Option<DateTime<Utc>>,
+    pub labels: Vec<String>,
+    pub priority: Option<Priority>,
+}
+
+/// Pull `#label`, `!priority`, a date (`today`, `tomorrow`, `friday`, `next friday`,
+/// `in 3 days`, `2024-05-01`) and a time (`5pm`, `at 17:30`) out of `input`;
+/// the remaining words become the todo text.
+///
+/// A date without a time is due at the end of that day, a time without a date is due today.
+pub fn parse(input: &str, now: DateTime<FixedOffset>) -> QuickTodo {
+    let tokens = input.split_whitespace().collect::<Vec<&str>>();
+    let today = now.date_naive();
+    let mut words = vec![];
+    let mut labels: Vec<String> = vec![];
+    let mut priority = None;
+    let mut date = None;
+    let mut time = None;
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let rest = &tokens[i..];
+        if let Some(label) = rest[0].strip_prefix('#').filter(|label| !label.is_empty()) {
+            if !labels.iter().any(|known| known == label) {
+                labels.push(label.to_string());
+            }
+            i += 1;
+        } else if let Some(p) = rest[0].strip_prefix('!').and_then(parse_priority) {
+            priority = Some(p);
+            i += 1;
+        } else if let Some((d, used)) = date.is_none().then(|| parse_date(rest, today)).flatten() {
+            date = Some(d);
+            i += used;
+        } else if let Some((t, used)) = time.is_none().then(|| parse_time(rest)).flatten() {
+            time = Some(t);
+            i += used;
+        } else {
+            words.push(rest[0]);
+            i += 1;
+        }
+    }
+
+    let due_at = match (date, time) {
+        (None, None) => None,
+        (date, time) => {
+            let end_of_day = NaiveTime::from_hms_opt(23, 59, 59).unwrap();
+            let local = date.unwrap_or(today).and_time(time.unwrap_or(end_of_day));
+            now.timezone()
+                .from_local_datetime(&local)
+                .single()
+                .map(|due_at| due_at.with_timezone(&Utc))
+        }
+    };
+    QuickTodo {
+        text: words.join(" "),
+        due_at,
+        labels,
+        priority,
+    }
+}
+
+fn parse_priority(word: &str) -> Option<Priority> {
+    match word.to_ascii_lowercase().as_str() {
+        "low" => Some(Priority::Low),
+        "medium" => Some(Priority::Medium),
+        "high" => Some(Priority::High),
+        _ => None,
+    }
+}
+
+/// The date at the start of `tokens` and how many tokens it spans.
+fn parse_date(tokens: &[&str], today: NaiveDate) -> Option<(NaiveDate, usize)> {
+    let lower = tokens
+        .iter()
+        .take(3)
+        .map(|token| token.to_ascii_lowercase())
+        .collect::<Vec<String>>();
+    match lower.iter().map(String::as_str).collect::<Vec<&str>>()[..] {
+        ["today", ..] => Some((today, 1)),
+        ["tomorrow", ..] => Some((today + Duration::days(1), 1)),
+        ["next", weekday, ..] => {
+            let days = days_until(weekday, today)?;
+            // "next friday" on a friday is a week away, not today
+            Some((today + Duration::days(if days == 0 { 7 } else { days }), 2))
+        }
+        ["in", amount, unit] => {
+            let amount = amount.parse::<i64>().ok()?;
+            let days = match unit {
+                "day" | "days" => amount,
+                "week" | "weeks" => amount * 7,
+                _ => return None,
+            };
+            Some((today + Duration::days(days), 3))
+        }
+        [word, ..] => match days_until(word, today) {
+            Some(days) => Some((today + Duration::days(days), 1)),
+            None => NaiveDate::parse_from_str(word, "%Y-%m-%d")
+                .ok()
+                .map(|date| (date, 1)),
+        },
+        [] => None,
+    }
+}
+
+/// Days from `today` until the weekday named `word`, `0` if that is today.
+fn days_until(word: &str, today: NaiveDate) -> Option<i64> {
+    let weekday = match word {
+        "monday" => chrono::Weekday::Mon,
+        "tuesday" => chrono::Weekday::Tue,
+        "wednesday" => chrono::Weekday::Wed,
+        "thursday" => chrono::Weekday::Thu,
+        "friday" => chrono::Weekday::Fri,
+        "saturday" => chrono::Weekday::Sat,
+        "sunday" => chrono::Weekday::Sun,
+        _ => return None,
+    };
+    let days =
+        weekday.num_days_from_monday() as i64 - today.weekday().num_days_from_monday() as i64;
+    Some(days.rem_euclid(7))
+}
+
+/// The time at the start of `tokens`, optionally preceded by `at`, and how many tokens it spans.
+fn parse_time(tokens: &[&str]) -> Option<(NaiveTime, usize)> {
+    match tokens {
+        [at, clock, ..] if at.eq_ignore_ascii_case("at") => parse_clock(clock).map(|t| (t, 2)),
+        [clock, ..] => parse_clock(clock).map(|t| (t, 1)),
+        [] => None,
+    }
+}
+
+/// `5pm`, `5:30am`, `17:00`
+fn parse_clock(token: &str) -> Option<NaiveTime> {
+    let lower = token.to_ascii_lowercase();
+    let (clock, pm) = match (lower.strip_suffix("am"), lower.strip_suffix("pm")) {
+        (Some(clock), _) => (clock, Some(false)),
+        (_, Some(clock)) => (clock, Some(true)),
+        _ => (lower.as_str(), None),
+    };
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) => (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?),
+        // a bare number is only a time with am/pm, "buy 2 apples" is not
+        None if pm.is_some() => (clock.parse::<u32>().ok()?, 0),
+        None => return None,
+    };
+    let hour = match pm {
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some(pm) => hour % 12 + if pm { 12 } else { 0 },
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jst_now() -> DateTime<FixedOffset> {
+        // a Wednesday
+        DateTime::parse_from_rfc3339("2024-05-01T10:00:00+09:00").unwrap()
+    }
+
+    fn utc(s: &str) -> Option<DateTime<Utc>> {
+        Some(DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc))
+    }
+
+    #[test]
+    fn parse_labels_priority_and_due_date() {
+        let todo = parse("pay rent tomorrow 5pm #finance !high", jst_now());
+        assert_eq!(
+            todo,
+            QuickTodo {
+                text: "pay rent".to_string(),
+                due_at: utc("2024-05-02T17:00:00+09:00"),
+                labels: vec!["finance".to_string()],
+                priority: Some(Priority::High),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_dates() {
+        let due = |input: &str| parse(input, jst_now()).due_at;
+        assert_eq!(due("call mom"), None);
+        assert_eq!(due("call mom today"), utc("2024-05-01T23:59:59+09:00"));
+        assert_eq!(due("call mom at 18:30"), utc("2024-05-01T18:30:00+09:00"));
+        assert_eq!(due("call mom friday 9am"), utc("2024-05-03T09:00:00+09:00"));
+        assert_eq!(
+            due("call mom next wednesday"),
+            utc("2024-05-08T23:59:59+09:00")
+        );
+        assert_eq!(due("call mom in 2 weeks"), utc("2024-05-15T23:59:59+09:00"));
+        assert_eq!(
+            due("call mom 2024-06-01 12am"),
+            utc("2024-06-01T00:00:00+09:00")
+        );
+    }
+
+    #[test]
+    fn unrecognized_words_stay_in_text() {
+        let todo = parse(
+            "buy 2 apples and sun cream at the market #food #food !urgent",
+            jst_now(),
+        );
+        assert_eq!(
+            todo.text,
+            "buy 2 apples and sun cream at the market !urgent"
+        );
+        assert_eq!(todo.labels, vec!["food".to_string()]);
+        assert_eq!(todo.due_at, None);
+        assert_eq!(todo.priority, None);
+    }
+}
@@ -0,0 +1,160 @@
+use std::sync::{Arc, RwLock};
+
+use crate::config::{AppConfig, ConfigError};
+use crate::middleware::read_only::ReadOnlyMode;
+
+/// The configuration as of the last reload. Layers read it per request, so a reload takes effect
+/// with the next one. Reloads are rare, so a read lock is all the synchronization needed.
+#[derive(Debug)]
+pub struct LiveConfig(RwLock<Arc<AppConfig>>);
+
+impl LiveConfig {
+    pub fn new(config: AppConfig) -> Self {
+        LiveConfig(RwLock::new(Arc::new(config)))
+    }
+
+    pub fn current(&self) -> Arc<AppConfig> {
+        self.0.read().unwrap().clone()
+    }
+
+    fn replace(&self, config: AppConfig) {
+        *self.0.write().unwrap() = Arc::new(config);
+    }
+}
+
+type Source = Box<dyn Fn() -> Result<AppConfig, ConfigError> + Send + Sync>;
+type SetLogLevel = Box<dyn Fn(&str) -> anyhow::Result<()> + Send + Sync>;
+
+/// Re-reads the configuration on SIGHUP or `POST /admin/reload`.
+///
+/// CORS origins (`CLIENT_URL`), the log level (`RUST_LOG`) and `READ_ONLY` apply right away,
+/// everything else keeps its startup value until a restart. `READ_ONLY` only applies when its
+/// value changed, so a reload doesn't undo a switch made through `/admin/read-only`.
+pub struct Reloader {
+    source: Source,
+    live: Arc<LiveConfig>,
+    read_only: Arc<ReadOnlyMode>,
+    set_log_level: SetLogLevel,
+}
+
+impl Reloader {
+    pub fn new(
+        source: impl Fn() -> Result<AppConfig, ConfigError> + Send + Sync + 'static,
+        live: Arc<LiveConfig>,
+        read_only: Arc<ReadOnlyMode>,
+        set_log_level: impl Fn(&str) -> anyhow::Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        Reloader {
+            source: Box::new(source),
+            live,
+            read_only,
+            set_log_level: Box::new(set_log_level),
+        }
+    }
+
+    /// An invalid configuration is rejected as a whole and the current one stays in place.
+    pub fn reload(&self) -> anyhow::Result<()> {
+        let config = (self.source)()?;
+        let previous = self.live.current();
+        if config.log_level != previous.log_level {
+            (self.set_log_level)(&config.log_level)?;
+        }
+        if config.read_only != previous.read_only {
+            self.read_only.set(config.read_only);
+            tracing::warn!("read-only mode set to {}", config.read_only);
+        }
+        self.live.replace(config);
+        tracing::info!("configuration reloaded");
+        Ok(())
+    }
+}
+
+/// Reload the configuration on every SIGHUP.
+#[cfg(unix)]
+pub async fn on_sighup(reloader: Arc<Reloader>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            tracing::error!("can't listen for SIGHUP: {}", e);
+            return;
+        }
+    };
+    while hangups.recv().await.is_some() {
+        if let Err(e) = reloader.reload() {
+            tracing::error!(
+                "configuration reload failed, keeping the current one: {}",
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Mutex;
+
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    pub(crate) fn config(pairs: &[(&str, &str)]) -> Result<AppConfig, ConfigError> {
+        let base = [
+            ("DATABASE_URL", "db"),
+            ("CLIENT_URL", "http://localhost:3000"),
+        ];
+        let pairs = [&base[..], pairs].concat();
+        AppConfig::from_lookup(|key| {
+            pairs
+                .iter()
+                .rev()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.to_string())
+        })
+    }
+
+    #[test]
+    fn reload_applies_live_settings() {
+        let env = Arc::new(Mutex::new(vec![]));
+        let log_levels = Arc::new(Mutex::new(vec![]));
+        let live = Arc::new(LiveConfig::new(config(&[]).unwrap()));
+        let read_only = Arc::new(ReadOnlyMode::default());
+        let reloader = Reloader::new(
+            {
+                let env = env.clone();
+                move || config(&env.lock().unwrap())
+            },
+            live.clone(),
+            read_only.clone(),
+            {
+                let log_levels = log_levels.clone();
+                move |level| {
+                    log_levels.lock().unwrap().push(level.to_string());
+                    Ok(())
+                }
+            },
+        );
+
+        *env.lock().unwrap() = vec![
+            ("CLIENT_URL", "https://todo.example.com"),
+            ("RUST_LOG", "debug"),
+            ("READ_ONLY", "true"),
+        ];
+        reloader.reload().expect("[reload] returned Err");
+        let origin = HeaderValue::from_static("https://todo.example.com");
+        assert!(live.current().cors.allow_origins[0].matches(&origin));
+        assert_eq!(*log_levels.lock().unwrap(), vec!["debug".to_string()]);
+        assert!(read_only.is_enabled());
+
+        // an operator switched read-only off, an unrelated reload keeps it that way
+        read_only.set(false);
+        reloader.reload().expect("[reload] returned Err");
+        assert!(!read_only.is_enabled());
+        assert_eq!(log_levels.lock().unwrap().len(), 1);
+
+        *env.lock().unwrap() = vec![("CLIENT_URL", "")];
+        assert!(reloader.reload().is_err());
+        assert!(live.current().cors.allow_origins[0].matches(&origin));
+    }
+}
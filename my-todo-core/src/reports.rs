@@ -0,0 +1,228 @@
+use std::sync::Arc;
+
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::repositories::report::ReportRepository;
+use crate::repositories::RepositoryError;
+
+/// A saved analytical query, run with `GET /admin/reports/:name`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SavedReport {
+    pub name: &'static str,
+    pub description: &'static str,
+    #[serde(skip)]
+    pub sql: &'static str,
+}
+
+pub const SAVED_REPORTS: &[SavedReport] = &[
+    SavedReport {
+        name: "completion_by_weekday",
+        description: "Todos completed per ISO weekday (1 is Monday), from their history",
+        sql: r#"
+            select extract(isodow from occurred_at)::int as weekday, count(*) as completed
+            from (select occurred_at,
+                         (data ->> 'completed')::boolean as completed,
+                         lag((data ->> 'completed')::boolean)
+                         over (partition by todo_id order by id) as was_completed
+                  from todo_events
+                  where kind in ('created', 'updated')) changes
+            where completed and not coalesce(was_completed, false)
+            group by weekday
+            order by weekday"#,
+    },
+    SavedReport {
+        name: "created_per_week",
+        description: "Todos created per week, deleted ones included",
+        sql: r#"
+            select date_trunc('week', occurred_at) as week, count(*) as created
+            from todo_events
+            where kind = 'created'
+            group by week
+            order by week"#,
+    },
+    SavedReport {
+        name: "open_by_label",
+        description: "Open and total todos per label",
+        sql: r#"
+            select labels.name as label,
+                   count(todos.id) filter (where not todos.completed) as open,
+                   count(todos.id) as total
+            from labels
+                     left outer join todo_labels tl on tl.label_id = labels.id
+                     left outer join todos on todos.id = tl.todo_id
+            group by labels.name
+            order by labels.name"#,
+    },
+];
+
+/// `POST /reports/query`, an ad hoc query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawQuery {
+    pub sql: String,
+}
+
+/// Reporting for power users without database credentials, mounted under `/admin`:
+/// `GET /reports` lists the [`SAVED_REPORTS`], `GET /reports/:name` runs one and
+/// `POST /reports/query` runs any `select`. Rows come back as JSON objects.
+///
+/// Ad hoc queries are refused unless `ad_hoc_queries` is set, which should only be the case
+/// when `repo` connects with a role of its own: a read-only transaction doesn't keep the main
+/// role from reading whatever it may, or a superuser from calling functions with side effects.
+pub fn routes<R: ReportRepository>(repo: R, ad_hoc_queries: bool) -> Router {
+    Router::new()
+        .route("/reports", get(saved_reports))
+        .route("/reports/query", post(run_query::<R>))
+        .route("/reports/:name", get(run_saved_report::<R>))
+        .layer(Extension(Arc::new(repo)))
+        .layer(Extension(AdHocQueries(ad_hoc_queries)))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct AdHocQueries(bool);
+
+async fn saved_reports() -> Json<&'static [SavedReport]> {
+    Json(SAVED_REPORTS)
+}
+
+async fn run_saved_report<R: ReportRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, Response> {
+    let report = SAVED_REPORTS
+        .iter()
+        .find(|report| report.name == name)
+        .ok_or_else(|| StatusCode::NOT_FOUND.into_response())?;
+    let rows = repo.execute_raw(report.sql).await.map_err(report_error)?;
+    Ok(Json(rows))
+}
+
+async fn run_query<R: ReportRepository>(
+    Extension(repo): Extension<Arc<R>>,
+    Extension(AdHocQueries(allowed)): Extension<AdHocQueries>,
+    Json(query): Json<RawQuery>,
+) -> Result<impl IntoResponse, Response> {
+    if !allowed {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "ad hoc queries need a separate REPORT_DATABASE_URL",
+        )
+            .into_response());
+    }
+    let rows = repo.execute_raw(&query.sql).await.map_err(report_error)?;
+    Ok(Json(rows))
+}
+
+/// The database's complaint about a query goes back to its author.
+fn report_error(e: anyhow::Error) -> Response {
+    match e.downcast_ref::<RepositoryError>() {
+        Some(RepositoryError::InvalidQuery(message)) => {
+            (StatusCode::BAD_REQUEST, message.clone()).into_response()
+        }
+        _ => {
+            tracing::error!("report failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Method, Request};
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::repositories::mock::MockReportRepository;
+
+    async fn send(app: &Router, method: Method, uri: &str, body: &str) -> (StatusCode, String) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let status = res.status();
+        let body = axum::body::to_bytes(res.into_body(), 10_000).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn run_saved_and_raw_reports() {
+        let repo = Arc::new(MockReportRepository::default().expect_execute_raw(|sql| {
+            if sql.contains("isodow") {
+                Ok(vec![json!({"weekday": 1, "completed": 3})])
+            } else if sql == "select 1 as one" {
+                Ok(vec![json!({"one": 1})])
+            } else {
+                Err(RepositoryError::InvalidQuery("syntax error".to_string()).into())
+            }
+        }));
+        let app = routes(repo.clone(), true);
+
+        let (status, body) = send(&app, Method::GET, "/reports", "").await;
+        assert_eq!(status, StatusCode::OK);
+        let saved: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(saved.as_array().unwrap().len(), SAVED_REPORTS.len());
+        assert!(saved[0].get("sql").is_none());
+
+        let (status, body) = send(&app, Method::GET, "/reports/completion_by_weekday", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"[{"completed":3,"weekday":1}]"#);
+        let (status, _) = send(&app, Method::GET, "/reports/unknown", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let query = |sql: &str| json!({ "sql": sql }).to_string();
+        let (status, body) = send(
+            &app,
+            Method::POST,
+            "/reports/query",
+            &query("select 1 as one"),
+        )
+        .await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, r#"[{"one":1}]"#));
+        let (status, body) = send(&app, Method::POST, "/reports/query", &query("selec")).await;
+        assert_eq!(
+            (status, body.as_str()),
+            (StatusCode::BAD_REQUEST, "syntax error")
+        );
+
+        // sharing the main connection, only the saved reports run
+        let app = routes(repo, false);
+        let (status, _) = send(
+            &app,
+            Method::POST,
+            "/reports/query",
+            &query("select 1 as one"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&app, Method::GET, "/reports/completion_by_weekday", "").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "db-test")]
+mod test_psql_repo {
+    use super::*;
+    use crate::repositories::report::ReportRepositoryForDb;
+    use crate::repositories::test_db::TestDb;
+
+    #[tokio::test]
+    async fn saved_reports_run() {
+        let db = TestDb::new().await;
+        let repo = ReportRepositoryForDb::new(db.pool.clone());
+        for report in SAVED_REPORTS {
+            repo.execute_raw(report.sql)
+                .await
+                .unwrap_or_else(|e| panic!("[{}] returned Err: {:?}", report.name, e));
+        }
+    }
+}
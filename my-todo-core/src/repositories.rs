@@ -0,0 +1,85 @@
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+pub mod cipher;
+pub mod ingest_hook;
+pub mod instrumented;
+pub mod label;
+pub mod label_cache;
+pub mod report;
+pub mod sequence;
+pub mod share_link;
+pub mod todo;
+pub mod todo_events;
+
+#[cfg(test)]
+pub mod mock;
+#[cfg(test)]
+#[cfg(feature = "db-test")]
+pub(crate) mod test_db;
+
+#[derive(Error, Debug)]
+pub enum RepositoryError {
+    #[error("Unexpected error: {0}")]
+    Unexpected(String),
+    #[error("Not found id: {0}")]
+    NotFound(i32),
+    #[error("Duplicated error: {0}")]
+    DuplicatedLabel(i32),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Invalid reference: {0}")]
+    InvalidReference(String),
+    #[error("Label {id} is attached to {todo_count} todo(s)")]
+    LabelInUse { id: i32, todo_count: i64 },
+    /// A raw query the database refused, see [`report::ReportRepository`].
+    #[error("Invalid query: {0}")]
+    InvalidQuery(String),
+}
+
+impl From<sqlx::Error> for RepositoryError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                RepositoryError::Conflict(db.message().to_string())
+            }
+            sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+                RepositoryError::InvalidReference(db.message().to_string())
+            }
+            _ => RepositoryError::Unexpected(e.to_string()),
+        }
+    }
+}
+
+pub const DEFAULT_SLOW_QUERY_THRESHOLD: Duration = Duration::from_millis(200);
+
+/// Logs how long a repository operation took when dropped,
+/// at warn level once it exceeds the slow query threshold.
+pub(crate) struct QueryTimer {
+    operation: &'static str,
+    threshold: Duration,
+    started: Instant,
+}
+
+impl QueryTimer {
+    pub(crate) fn start(operation: &'static str, threshold: Duration) -> Self {
+        QueryTimer {
+            operation,
+            threshold,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Drop for QueryTimer {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed();
+        let elapsed_ms = elapsed.as_millis() as u64;
+        if elapsed > self.threshold {
+            tracing::warn!(operation = self.operation, elapsed_ms, "slow query");
+        } else {
+            tracing::debug!(operation = self.operation, elapsed_ms, "query finished");
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt;
+use std::str::FromStr;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+
+use crate::repositories::RepositoryError;
+
+/// Marks an encrypted value, followed by the base64 of nonce, ciphertext and tag.
+const PREFIX: &str = "enc:v1:";
+
+/// A 256-bit key, given base64 encoded in `TODO_ENCRYPTION_KEY`. Never printed.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; 32]);
+
+impl FromStr for EncryptionKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = STANDARD
+            .decode(s.trim())
+            .map_err(|e| format!("not base64: {}", e))?;
+        let key = bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| format!("must be 32 bytes, got {}", bytes.len()))?;
+        Ok(EncryptionKey(key))
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// Encrypts todo fields with AES-256-GCM before they are stored, so a database dump doesn't
+/// reveal them.
+///
+/// The field name is authenticated along with the value, so a ciphertext copied into another
+/// field fails to decrypt. Values stored before encryption was turned on are read as they are.
+pub struct FieldCipher(LessSafeKey);
+
+impl FieldCipher {
+    pub fn new(key: &EncryptionKey) -> Self {
+        let key = UnboundKey::new(&AES_256_GCM, &key.0).expect("AES-256 keys are 32 bytes");
+        FieldCipher(LessSafeKey::new(key))
+    }
+
+    pub fn encrypt(&self, field: &str, plaintext: &str) -> String {
+        let nonce = rand::random::<[u8; NONCE_LEN]>();
+        let mut sealed = plaintext.as_bytes().to_vec();
+        self.0
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(field),
+                &mut sealed,
+            )
+            .expect("the plaintext of a todo fits AES-GCM");
+        format!(
+            "{}{}",
+            PREFIX,
+            STANDARD.encode([&nonce[..], &sealed].concat())
+        )
+    }
+
+    pub fn decrypt(&self, field: &str, stored: &str) -> Result<String, RepositoryError> {
+        let Some(encoded) = stored.strip_prefix(PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let invalid = || RepositoryError::Unexpected(format!("can't decrypt todo {}", field));
+        let mut sealed = STANDARD.decode(encoded).map_err(|_| invalid())?;
+        if sealed.len() < NONCE_LEN {
+            return Err(invalid());
+        }
+        let mut ciphertext = sealed.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&sealed).map_err(|_| invalid())?;
+        let plaintext = self
+            .0
+            .open_in_place(nonce, Aad::from(field), &mut ciphertext)
+            .map_err(|_| invalid())?;
+        String::from_utf8(plaintext.to_vec()).map_err(|_| invalid())
+    }
+}
+
+impl fmt::Debug for FieldCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FieldCipher(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher() -> FieldCipher {
+        FieldCipher::new(&EncryptionKey([7; 32]))
+    }
+
+    #[test]
+    fn round_trip() {
+        let cipher = cipher();
+        let sealed = cipher.encrypt("text", "buy milk");
+        assert!(sealed.starts_with(PREFIX));
+        assert!(!sealed.contains("milk"));
+        // a fresh nonce every time
+        assert_ne!(sealed, cipher.encrypt("text", "buy milk"));
+        assert_eq!(cipher.decrypt("text", &sealed).unwrap(), "buy milk");
+
+        assert!(cipher.decrypt("description", &sealed).is_err());
+        let other = FieldCipher::new(&EncryptionKey([8; 32]));
+        assert!(other.decrypt("text", &sealed).is_err());
+        // stored before encryption was enabled
+        assert_eq!(cipher.decrypt("text", "buy milk").unwrap(), "buy milk");
+    }
+
+    #[test]
+    fn parse_key() {
+        let key = STANDARD.encode([1u8; 32]);
+        assert_eq!(key.parse::<EncryptionKey>(), Ok(EncryptionKey([1; 32])));
+        assert!(STANDARD.encode([1u8; 16]).parse::<EncryptionKey>().is_err());
+        assert!("not a key".parse::<EncryptionKey>().is_err());
+        assert_eq!(
+            format!("{:?}", key.parse::<EncryptionKey>().unwrap()),
+            "EncryptionKey(..)"
+        );
+    }
+}
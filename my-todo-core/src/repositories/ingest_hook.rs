@@ -0,0 +1,86 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{FromRow, PgPool};
+
+use crate::middleware::csrf::new_token;
+use crate::repositories::share_link::hash;
+use crate::repositories::RepositoryError;
+
+/// How a hook turns the JSON posted to it into a todo, as JSONPath expressions like
+/// `$.issue.title`, see [`crate::ingest::JsonPath`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IngestMapping {
+    /// The first string or number selected becomes the text.
+    pub text: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Every string selected names a label, created when missing, e.g.
+    /// `$.issue.labels[*].name`.
+    #[serde(default)]
+    pub labels: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
+pub struct IngestHook {
+    pub id: i32,
+    /// Where the hook is used, e.g. `github issues`.
+    pub name: String,
+    #[sqlx(json)]
+    pub mapping: IngestMapping,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A new hook, returning it along with its token.
+pub(crate) async fn create(
+    pool: &PgPool,
+    name: &str,
+    mapping: &IngestMapping,
+) -> anyhow::Result<(IngestHook, String)> {
+    let token = new_token();
+    let hook = sqlx::query_as::<_, IngestHook>(
+        r#"
+        insert into ingest_hooks (token_hash, name, mapping) values ($1, $2, $3)
+        returning id, name, mapping, created_at
+        "#,
+    )
+    .bind(hash(&token))
+    .bind(name)
+    .bind(Json(mapping))
+    .fetch_one(pool)
+    .await
+    .map_err(RepositoryError::from)?;
+    Ok((hook, token))
+}
+
+/// The hook with `token`, `None` when it was deleted or never created.
+pub(crate) async fn find(pool: &PgPool, token: &str) -> anyhow::Result<Option<IngestHook>> {
+    let hook = sqlx::query_as::<_, IngestHook>(
+        r#"select id, name, mapping, created_at from ingest_hooks where token_hash = $1"#,
+    )
+    .bind(hash(token))
+    .fetch_optional(pool)
+    .await
+    .map_err(RepositoryError::from)?;
+    Ok(hook)
+}
+
+pub(crate) async fn all(pool: &PgPool) -> anyhow::Result<Vec<IngestHook>> {
+    let hooks = sqlx::query_as::<_, IngestHook>(
+        r#"select id, name, mapping, created_at from ingest_hooks order by id"#,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(RepositoryError::from)?;
+    Ok(hooks)
+}
+
+/// Delete the hook, returning whether there was one.
+pub(crate) async fn delete(pool: &PgPool, id: i32) -> anyhow::Result<bool> {
+    let deleted = sqlx::query(r#"delete from ingest_hooks where id = $1"#)
+        .bind(id)
+        .execute(pool)
+        .await
+        .map_err(RepositoryError::from)?;
+    Ok(deleted.rows_affected() > 0)
+}
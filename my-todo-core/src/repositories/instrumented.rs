@@ -0,0 +1,227 @@
+use std::future::Future;
+use std::time::Instant;
+
+use axum::async_trait;
+use futures_util::stream::{BoxStream, StreamExt};
+
+use crate::repositories::label::{CreateLabel, Label, LabelDefaults, LabelRepository};
+use crate::repositories::todo::{
+    CreateTodo, TodoCounts, TodoEntity, TodoQuery, TodoRepository, UpdateTodo,
+};
+
+/// Wraps a repository to measure every call made through it, labelled by `repository` and
+/// `method`: `repository_calls_total`, `repository_errors_total` and
+/// `repository_call_duration_seconds`. A stream counts as one call, lasting until it ends or
+/// is dropped, with an error for every failed item.
+///
+/// Unlike the `slow query` logs of [`crate::repositories::QueryTimer`], this sees the
+/// repository from the outside, so it covers any implementation and the layers around it,
+/// e.g. [`crate::repositories::label_cache::CachedLabelRepository`].
+#[derive(Debug, Clone)]
+pub struct InstrumentedRepository<R> {
+    inner: R,
+    name: &'static str,
+}
+
+impl<R> InstrumentedRepository<R> {
+    /// `name` goes into the `repository` label, e.g. `todo`.
+    pub fn new(inner: R, name: &'static str) -> Self {
+        InstrumentedRepository { inner, name }
+    }
+
+    fn start(&self, method: &'static str) -> Call {
+        Call {
+            repository: self.name,
+            method,
+            started: Instant::now(),
+        }
+    }
+
+    async fn observe<T>(
+        &self,
+        method: &'static str,
+        call: impl Future<Output = anyhow::Result<T>>,
+    ) -> anyhow::Result<T> {
+        let timer = self.start(method);
+        let result = call.await;
+        if result.is_err() {
+            timer.failed();
+        }
+        result
+    }
+
+    fn observe_stream<T: Send + 'static>(
+        &self,
+        method: &'static str,
+        stream: BoxStream<'static, anyhow::Result<T>>,
+    ) -> BoxStream<'static, anyhow::Result<T>> {
+        let timer = self.start(method);
+        Box::pin(stream.inspect(move |item| {
+            if item.is_err() {
+                timer.failed();
+            }
+        }))
+    }
+}
+
+/// Counts and times the call when dropped.
+struct Call {
+    repository: &'static str,
+    method: &'static str,
+    started: Instant,
+}
+
+impl Call {
+    fn labels(&self) -> [(&'static str, &'static str); 2] {
+        [("repository", self.repository), ("method", self.method)]
+    }
+
+    fn failed(&self) {
+        metrics::counter!("repository_errors_total", &self.labels()).increment(1);
+    }
+}
+
+impl Drop for Call {
+    fn drop(&mut self) {
+        let labels = self.labels();
+        metrics::counter!("repository_calls_total", &labels).increment(1);
+        metrics::histogram!("repository_call_duration_seconds", &labels)
+            .record(self.started.elapsed().as_secs_f64());
+    }
+}
+
+#[async_trait]
+impl<R: TodoRepository> TodoRepository for InstrumentedRepository<R> {
+    async fn create(&self, todo: CreateTodo) -> anyhow::Result<TodoEntity> {
+        self.observe("create", self.inner.create(todo)).await
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<TodoEntity> {
+        self.observe("find", self.inner.find(id)).await
+    }
+
+    async fn all(&self, query: TodoQuery) -> anyhow::Result<Vec<TodoEntity>> {
+        self.observe("all", self.inner.all(query)).await
+    }
+
+    async fn next(&self) -> anyhow::Result<Option<TodoEntity>> {
+        self.observe("next", self.inner.next()).await
+    }
+
+    fn stream(&self, query: TodoQuery) -> BoxStream<'static, anyhow::Result<TodoEntity>> {
+        self.observe_stream("stream", self.inner.stream(query))
+    }
+
+    fn stream_counted(
+        &self,
+        query: TodoQuery,
+    ) -> BoxStream<'static, anyhow::Result<(TodoEntity, TodoCounts)>> {
+        self.observe_stream("stream_counted", self.inner.stream_counted(query))
+    }
+
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        self.observe("delete", self.inner.delete(id)).await
+    }
+
+    async fn update(&self, id: i32, todo: UpdateTodo) -> anyhow::Result<TodoEntity> {
+        self.observe("update", self.inner.update(id, todo)).await
+    }
+
+    async fn resolve(&self, public_id: &str) -> anyhow::Result<Option<i32>> {
+        self.observe("resolve", self.inner.resolve(public_id)).await
+    }
+}
+
+#[async_trait]
+impl<R: LabelRepository> LabelRepository for InstrumentedRepository<R> {
+    async fn create(&self, label: CreateLabel) -> anyhow::Result<Label> {
+        self.observe("create", self.inner.create(label)).await
+    }
+
+    async fn find_or_create(&self, label: CreateLabel) -> anyhow::Result<(Label, bool)> {
+        self.observe("find_or_create", self.inner.find_or_create(label))
+            .await
+    }
+
+    async fn all(&self) -> anyhow::Result<Vec<Label>> {
+        self.observe("all", self.inner.all()).await
+    }
+
+    async fn delete(&self, id: i32, force: bool) -> anyhow::Result<()> {
+        self.observe("delete", self.inner.delete(id, force)).await
+    }
+
+    async fn assign(&self, id: i32, todo_ids: Vec<i32>) -> anyhow::Result<Vec<i32>> {
+        self.observe("assign", self.inner.assign(id, todo_ids))
+            .await
+    }
+
+    async fn unassign(&self, id: i32, todo_ids: Vec<i32>) -> anyhow::Result<Vec<i32>> {
+        self.observe("unassign", self.inner.unassign(id, todo_ids))
+            .await
+    }
+
+    async fn resolve(&self, public_id: &str) -> anyhow::Result<Option<i32>> {
+        self.observe("resolve", self.inner.resolve(public_id)).await
+    }
+
+    async fn archive(&self, id: i32, archived: bool) -> anyhow::Result<Label> {
+        self.observe("archive", self.inner.archive(id, archived))
+            .await
+    }
+
+    async fn defaults(&self, id: i32) -> anyhow::Result<LabelDefaults> {
+        self.observe("defaults", self.inner.defaults(id)).await
+    }
+
+    async fn set_defaults(
+        &self,
+        id: i32,
+        defaults: LabelDefaults,
+    ) -> anyhow::Result<LabelDefaults> {
+        self.observe("set_defaults", self.inner.set_defaults(id, defaults))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics_exporter_prometheus::PrometheusBuilder;
+
+    use super::*;
+    use crate::repositories::mock::MockLabelRepository;
+    use crate::repositories::RepositoryError;
+
+    #[test]
+    fn count_calls_and_errors() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let repo = InstrumentedRepository::new(
+            MockLabelRepository::default()
+                .expect_all(|_| Ok(vec![]))
+                .expect_delete(|(id, _)| Err(RepositoryError::NotFound(id).into())),
+            "label",
+        );
+        // the recorder is only set for this thread, so run the calls on it
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                repo.all().await.unwrap();
+                repo.all().await.unwrap();
+                repo.delete(7, false).await.unwrap_err();
+            })
+        });
+
+        let rendered = recorder.handle().render();
+        for expected in [
+            r#"repository_calls_total{repository="label",method="all"} 2"#,
+            r#"repository_calls_total{repository="label",method="delete"} 1"#,
+            r#"repository_errors_total{repository="label",method="delete"} 1"#,
+            r#"repository_call_duration_seconds_count{repository="label",method="all"} 2"#,
+        ] {
+            assert!(rendered.contains(expected), "{} in {}", expected, rendered);
+        }
+        assert!(!rendered.contains(r#"repository_errors_total{repository="label",method="all"}"#));
+    }
+}
@@ -0,0 +1,1011 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx;
+use sqlx::PgConnection;
+use validator::Validate;
+
+use crate::events::{self, Event};
+use crate::repositories::todo::{Priority, Todo};
+use crate::repositories::todo_events::{self, TodoChange, TodoSnapshot};
+use crate::repositories::{QueryTimer, RepositoryError, DEFAULT_SLOW_QUERY_THRESHOLD};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, sqlx::FromRow)]
+pub struct Label {
+    pub id: i32,
+    /// A ULID, like [`TodoEntity::public_id`](crate::repositories::todo::TodoEntity::public_id).
+    pub public_id: String,
+    pub name: String,
+    /// Hidden from `GET /label` unless asked for, but still shown on the todos carrying it.
+    pub archived: bool,
+}
+
+/// Object safe like [`TodoRepository`](crate::repositories::todo::TodoRepository).
+#[async_trait]
+pub trait LabelRepository: std::marker::Send + std::marker::Sync + 'static {
+    async fn create(&self, label: CreateLabel) -> anyhow::Result<Label>;
+    /// The label with `label.name`, created when there is none yet, and whether it was created.
+    async fn find_or_create(&self, label: CreateLabel) -> anyhow::Result<(Label, bool)>;
+    async fn all(&self) -> anyhow::Result<Vec<Label>>;
+    /// Refuses with `RepositoryError::LabelInUse` while todos carry the label, unless `force`
+    /// detaches it from them first.
+    async fn delete(&self, id: i32, force: bool) -> anyhow::Result<()>;
+    /// Put the label on those of `todo_ids` that exist and lack it, returning them.
+    async fn assign(&self, id: i32, todo_ids: Vec<i32>) -> anyhow::Result<Vec<i32>>;
+    /// Take the label off those of `todo_ids` that carry it, returning them.
+    async fn unassign(&self, id: i32, todo_ids: Vec<i32>) -> anyhow::Result<Vec<i32>>;
+    /// The id of the label with `public_id`, `None` when there is none.
+    async fn resolve(&self, public_id: &str) -> anyhow::Result<Option<i32>>;
+    /// Archive the label, or bring it back when `archived` is false.
+    async fn archive(&self, id: i32, archived: bool) -> anyhow::Result<Label>;
+    async fn defaults(&self, id: i32) -> anyhow::Result<LabelDefaults>;
+    /// Replace the defaults of the label. Todos carrying it already are left as they are.
+    async fn set_defaults(&self, id: i32, defaults: LabelDefaults)
+        -> anyhow::Result<LabelDefaults>;
+}
+
+#[async_trait]
+impl<R: LabelRepository + ?Sized> LabelRepository for Arc<R> {
+    async fn create(&self, label: CreateLabel) -> anyhow::Result<Label> {
+        (**self).create(label).await
+    }
+
+    async fn find_or_create(&self, label: CreateLabel) -> anyhow::Result<(Label, bool)> {
+        (**self).find_or_create(label).await
+    }
+
+    async fn all(&self) -> anyhow::Result<Vec<Label>> {
+        (**self).all().await
+    }
+
+    async fn delete(&self, id: i32, force: bool) -> anyhow::Result<()> {
+        (**self).delete(id, force).await
+    }
+
+    async fn assign(&self, id: i32, todo_ids: Vec<i32>) -> anyhow::Result<Vec<i32>> {
+        (**self).assign(id, todo_ids).await
+    }
+
+    async fn unassign(&self, id: i32, todo_ids: Vec<i32>) -> anyhow::Result<Vec<i32>> {
+        (**self).unassign(id, todo_ids).await
+    }
+
+    async fn resolve(&self, public_id: &str) -> anyhow::Result<Option<i32>> {
+        (**self).resolve(public_id).await
+    }
+
+    async fn archive(&self, id: i32, archived: bool) -> anyhow::Result<Label> {
+        (**self).archive(id, archived).await
+    }
+
+    async fn defaults(&self, id: i32) -> anyhow::Result<LabelDefaults> {
+        (**self).defaults(id).await
+    }
+
+    async fn set_defaults(
+        &self,
+        id: i32,
+        defaults: LabelDefaults,
+    ) -> anyhow::Result<LabelDefaults> {
+        (**self).set_defaults(id, defaults).await
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Validate)]
+pub struct CreateLabel {
+    /// Unique regardless of case: "Work" is refused while "work" exists.
+    #[validate(length(min = 1, max = 255, code = "name_length"))]
+    pub name: String,
+}
+
+/// What attaching the label fills in on a todo that leaves it unset, see [`apply_defaults`].
+/// Set with `PUT /label/:id/defaults`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Validate, sqlx::FromRow)]
+pub struct LabelDefaults {
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    /// The todo is due this many minutes after the label was attached, up to a year.
+    #[validate(range(min = 1, max = 525600, code = "due_offset_range"))]
+    #[serde(default)]
+    pub due_in_minutes: Option<i32>,
+}
+
+/// Fill in the [`LabelDefaults`] of `label_ids` on those of `todo_ids` that leave them unset,
+/// returning the todos that changed. Where the labels disagree, the highest priority and the
+/// soonest due date win. Run it on the transaction attaching the labels.
+pub(crate) async fn apply_defaults(
+    conn: &mut PgConnection,
+    todo_ids: &[i32],
+    label_ids: &[i32],
+) -> Result<Vec<Todo>, RepositoryError> {
+    if todo_ids.is_empty() || label_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    let todos = sqlx::query_as::<_, Todo>(
+        r#"
+        update todos
+        set priority   = coalesce(todos.priority, d.priority),
+            due_at     = coalesce(todos.due_at, now() + make_interval(mins => d.due_in_minutes)),
+            updated_at = now()
+        from (select max(default_priority) as priority, min(default_due_minutes) as due_in_minutes
+              from labels
+              where id = any($2)) d
+        where todos.id = any($1)
+          and ((todos.priority is null and d.priority is not null)
+            or (todos.due_at is null and d.due_in_minutes is not null))
+        returning todos.*
+        "#,
+    )
+    .bind(todo_ids)
+    .bind(label_ids)
+    .fetch_all(conn)
+    .await?;
+    Ok(todos)
+}
+
+/// Todos to (un)assign a label to at once, and in responses the ones that changed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Validate)]
+pub struct LabelAssignment {
+    #[validate(length(min = 1, max = 200, code = "todo_ids_length"))]
+    pub todo_ids: Vec<i32>,
+}
+
+/// A label as `GET /label/export` writes it and `POST /label/import` reads it: without ids,
+/// which differ between databases. Labels are matched by name.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ExportedLabel {
+    pub name: String,
+    #[serde(default)]
+    pub archived: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Validate)]
+pub struct LabelExport {
+    #[validate(length(max = 500, code = "labels_length"))]
+    pub labels: Vec<ExportedLabel>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LabelRepositoryForDb {
+    pool: sqlx::PgPool,
+    slow_query_threshold: Duration,
+    outbox: bool,
+}
+
+#[allow(dead_code)]
+impl LabelRepositoryForDb {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        LabelRepositoryForDb {
+            pool,
+            slow_query_threshold: DEFAULT_SLOW_QUERY_THRESHOLD,
+            outbox: false,
+        }
+    }
+
+    pub fn with_slow_query_threshold(self, slow_query_threshold: Duration) -> Self {
+        LabelRepositoryForDb {
+            slow_query_threshold,
+            ..self
+        }
+    }
+
+    /// Record an [`Event`] in the outbox with every change.
+    pub fn with_outbox(self, outbox: bool) -> Self {
+        LabelRepositoryForDb { outbox, ..self }
+    }
+
+    /// Lock the label like [`LabelRepository::delete`] does, so it stays while todos are
+    /// (un)assigned.
+    async fn lock(conn: &mut sqlx::PgConnection, id: i32) -> Result<(), RepositoryError> {
+        sqlx::query(r#"select id from labels where id = $1 for update"#)
+            .bind(id)
+            .fetch_optional(conn)
+            .await?
+            .ok_or(RepositoryError::NotFound(id))?;
+        Ok(())
+    }
+
+    async fn find_by_name(&self, name: &str) -> anyhow::Result<Option<Label>> {
+        let select_query = r#"select * from labels where lower(name) = lower($1)"#;
+        let label = sqlx::query_as::<_, Label>(select_query)
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(RepositoryError::from)?;
+        Ok(label)
+    }
+}
+
+#[async_trait]
+impl LabelRepository for LabelRepositoryForDb {
+    #[tracing::instrument(name = "labels.create", skip(self, label))]
+    async fn create(&self, label: CreateLabel) -> anyhow::Result<Label> {
+        let _timer = QueryTimer::start("labels.create", self.slow_query_threshold);
+        let mut tx = self.pool.begin().await?;
+        let insert_query = r#"
+        insert into labels (name) values ($1)
+        on conflict ((lower(name))) do nothing
+        returning *
+        "#;
+        let created = sqlx::query_as::<_, Label>(insert_query)
+            .bind(label.name.clone())
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(RepositoryError::from)?;
+        let Some(label) = created else {
+            return Err(match self.find_by_name(&label.name).await? {
+                Some(existing) => RepositoryError::DuplicatedLabel(existing.id),
+                // deleted again in the meantime
+                None => RepositoryError::Conflict(label.name),
+            }
+            .into());
+        };
+        if self.outbox {
+            let event = Event::LabelCreated {
+                label: label.clone(),
+            };
+            events::record(&mut tx, &event).await?;
+        }
+        tx.commit().await.map_err(RepositoryError::from)?;
+        Ok(label)
+    }
+
+    #[tracing::instrument(name = "labels.find_or_create", skip(self, label))]
+    async fn find_or_create(&self, label: CreateLabel) -> anyhow::Result<(Label, bool)> {
+        if let Some(existing) = self.find_by_name(&label.name).await? {
+            return Ok((existing, false));
+        }
+        match self.create(label.clone()).await {
+            Ok(created) => Ok((created, true)),
+            Err(e) => match e.downcast_ref::<RepositoryError>() {
+                // created concurrently
+                Some(RepositoryError::DuplicatedLabel(_)) => {
+                    let existing = self.find_by_name(&label.name).await?;
+                    Ok((existing.ok_or(e)?, false))
+                }
+                _ => Err(e),
+            },
+        }
+    }
+
+    #[tracing::instrument(name = "labels.all", skip(self))]
+    async fn all(&self) -> anyhow::Result<Vec<Label>> {
+        let _timer = QueryTimer::start("labels.all", self.slow_query_threshold);
+        let select_query = r#"select * from labels"#;
+        let labels = sqlx::query_as::<_, Label>(select_query)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(labels)
+    }
+
+    #[tracing::instrument(name = "labels.delete", skip(self))]
+    async fn delete(&self, id: i32, force: bool) -> anyhow::Result<()> {
+        let _timer = QueryTimer::start("labels.delete", self.slow_query_threshold);
+        let mut tx = self.pool.begin().await?;
+
+        // Lock the label, so no todo can be tagged with it until we are done.
+        Self::lock(&mut tx, id).await?;
+
+        let todo_count: i64 =
+            sqlx::query_scalar(r#"select count(*) from todo_labels where label_id = $1"#)
+                .bind(id)
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(RepositoryError::from)?;
+        if todo_count > 0 {
+            if !force {
+                return Err(RepositoryError::LabelInUse { id, todo_count }.into());
+            }
+            todo_events::append_label_detached(&mut tx, id).await?;
+            sqlx::query(r#"delete from todo_labels where label_id = $1"#)
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(RepositoryError::from)?;
+        }
+
+        let result = sqlx::query(r#"delete from labels where id = $1"#)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(RepositoryError::from)?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+        if self.outbox {
+            events::record(&mut tx, &Event::LabelDeleted { id }).await?;
+        }
+        tx.commit().await.map_err(RepositoryError::from)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "labels.assign", skip(self, todo_ids))]
+    async fn assign(&self, id: i32, todo_ids: Vec<i32>) -> anyhow::Result<Vec<i32>> {
+        let _timer = QueryTimer::start("labels.assign", self.slow_query_threshold);
+        let mut tx = self.pool.begin().await?;
+        Self::lock(&mut tx, id).await?;
+        let mut assigned: Vec<i32> = sqlx::query_scalar(
+            r#"
+            insert into todo_labels (todo_id, label_id)
+            select todos.id, $1 from todos where todos.id = any($2)
+            on conflict do nothing
+            returning todo_id
+            "#,
+        )
+        .bind(id)
+        .bind(&todo_ids)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(RepositoryError::from)?;
+        assigned.sort_unstable();
+        if !assigned.is_empty() {
+            let change = TodoChange::LabelAttached { label_id: id };
+            todo_events::append_all(&mut tx, &assigned, &change).await?;
+            for todo in apply_defaults(&mut tx, &assigned, &[id]).await? {
+                let label_ids = sqlx::query_scalar(
+                    r#"select label_id from todo_labels where todo_id = $1 order by label_id"#,
+                )
+                .bind(todo.id)
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(RepositoryError::from)?;
+                let change = TodoChange::Updated(TodoSnapshot::new(&todo, label_ids));
+                todo_events::append(&mut tx, todo.id, &change).await?;
+            }
+            if self.outbox {
+                let event = Event::LabelAssigned {
+                    id,
+                    todo_ids: assigned.clone(),
+                };
+                events::record(&mut tx, &event).await?;
+            }
+        }
+        tx.commit().await.map_err(RepositoryError::from)?;
+        Ok(assigned)
+    }
+
+    #[tracing::instrument(name = "labels.unassign", skip(self, todo_ids))]
+    async fn unassign(&self, id: i32, todo_ids: Vec<i32>) -> anyhow::Result<Vec<i32>> {
+        let _timer = QueryTimer::start("labels.unassign", self.slow_query_threshold);
+        let mut tx = self.pool.begin().await?;
+        Self::lock(&mut tx, id).await?;
+        let mut unassigned: Vec<i32> = sqlx::query_scalar(
+            r#"
+            delete from todo_labels where label_id = $1 and todo_id = any($2)
+            returning todo_id
+            "#,
+        )
+        .bind(id)
+        .bind(&todo_ids)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(RepositoryError::from)?;
+        unassigned.sort_unstable();
+        if !unassigned.is_empty() {
+            let change = TodoChange::LabelDetached { label_id: id };
+            todo_events::append_all(&mut tx, &unassigned, &change).await?;
+            if self.outbox {
+                let event = Event::LabelUnassigned {
+                    id,
+                    todo_ids: unassigned.clone(),
+                };
+                events::record(&mut tx, &event).await?;
+            }
+        }
+        tx.commit().await.map_err(RepositoryError::from)?;
+        Ok(unassigned)
+    }
+
+    #[tracing::instrument(name = "labels.resolve", skip(self))]
+    async fn resolve(&self, public_id: &str) -> anyhow::Result<Option<i32>> {
+        let _timer = QueryTimer::start("labels.resolve", self.slow_query_threshold);
+        let id = sqlx::query_scalar(r#"select id from labels where public_id = $1"#)
+            .bind(public_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(RepositoryError::from)?;
+        Ok(id)
+    }
+
+    #[tracing::instrument(name = "labels.archive", skip(self))]
+    async fn archive(&self, id: i32, archived: bool) -> anyhow::Result<Label> {
+        let _timer = QueryTimer::start("labels.archive", self.slow_query_threshold);
+        let mut tx = self.pool.begin().await?;
+        let label = sqlx::query_as::<_, Label>(
+            r#"update labels set archived = $2 where id = $1 returning *"#,
+        )
+        .bind(id)
+        .bind(archived)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(RepositoryError::from)?
+        .ok_or(RepositoryError::NotFound(id))?;
+        if self.outbox {
+            let event = if archived {
+                Event::LabelArchived { id }
+            } else {
+                Event::LabelUnarchived { id }
+            };
+            events::record(&mut tx, &event).await?;
+        }
+        tx.commit().await.map_err(RepositoryError::from)?;
+        Ok(label)
+    }
+
+    #[tracing::instrument(name = "labels.defaults", skip(self))]
+    async fn defaults(&self, id: i32) -> anyhow::Result<LabelDefaults> {
+        let _timer = QueryTimer::start("labels.defaults", self.slow_query_threshold);
+        let defaults = sqlx::query_as::<_, LabelDefaults>(
+            r#"
+            select default_priority as priority, default_due_minutes as due_in_minutes
+            from labels where id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(RepositoryError::from)?
+        .ok_or(RepositoryError::NotFound(id))?;
+        Ok(defaults)
+    }
+
+    #[tracing::instrument(name = "labels.set_defaults", skip(self))]
+    async fn set_defaults(
+        &self,
+        id: i32,
+        defaults: LabelDefaults,
+    ) -> anyhow::Result<LabelDefaults> {
+        let _timer = QueryTimer::start("labels.set_defaults", self.slow_query_threshold);
+        let defaults = sqlx::query_as::<_, LabelDefaults>(
+            r#"
+            update labels set default_priority = $2, default_due_minutes = $3
+            where id = $1
+            returning default_priority as priority, default_due_minutes as due_in_minutes
+            "#,
+        )
+        .bind(id)
+        .bind(defaults.priority)
+        .bind(defaults.due_in_minutes)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(RepositoryError::from)?
+        .ok_or(RepositoryError::NotFound(id))?;
+        Ok(defaults)
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_inmemory_repo {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    };
+
+    use axum::async_trait;
+
+    use crate::repositories::label::CreateLabel;
+    use crate::repositories::sequence::{IdGenerator, Sequence};
+    use crate::repositories::RepositoryError;
+
+    use super::*;
+
+    impl Label {
+        pub fn new(id: i32, name: String) -> Self {
+            Self {
+                id,
+                public_id: crate::public_id::from_serial(id),
+                name,
+                archived: false,
+            }
+        }
+    }
+
+    type LabelHashMap = HashMap<i32, Label>;
+
+    #[derive(Debug, Clone, Default)]
+    pub struct LabelRepositoryForMemory {
+        store: Arc<RwLock<LabelHashMap>>,
+        ids: Arc<Sequence>,
+        /// Kept but never applied: todos are not linked to labels in memory.
+        defaults: Arc<RwLock<HashMap<i32, LabelDefaults>>>,
+    }
+
+    impl LabelRepositoryForMemory {
+        pub fn new() -> Self {
+            LabelRepositoryForMemory {
+                store: Arc::default(),
+                ids: Arc::default(),
+                defaults: Arc::default(),
+            }
+        }
+
+        fn write_store_ref(&self) -> RwLockWriteGuard<'_, LabelHashMap> {
+            self.store.write().unwrap()
+        }
+
+        fn read_store_ref(&self) -> RwLockReadGuard<'_, LabelHashMap> {
+            self.store.read().unwrap()
+        }
+    }
+
+    fn find_by_name<'a>(store: &'a LabelHashMap, name: &str) -> Option<&'a Label> {
+        let name = name.to_lowercase();
+        store
+            .values()
+            .find(|label| label.name.to_lowercase() == name)
+    }
+
+    #[async_trait]
+    impl LabelRepository for LabelRepositoryForMemory {
+        async fn create(&self, payload: CreateLabel) -> anyhow::Result<Label> {
+            let mut store = self.write_store_ref();
+            if let Some(label) = find_by_name(&store, &payload.name) {
+                return Err(RepositoryError::DuplicatedLabel(label.id).into());
+            }
+            let id = self.ids.next_id();
+            let label = Label::new(id, payload.name);
+            store.insert(id, label.clone());
+            Ok(label)
+        }
+
+        async fn find_or_create(&self, payload: CreateLabel) -> anyhow::Result<(Label, bool)> {
+            if let Some(label) = find_by_name(&self.read_store_ref(), &payload.name) {
+                return Ok((label.clone(), false));
+            }
+            Ok((self.create(payload).await?, true))
+        }
+
+        async fn all(&self) -> anyhow::Result<Vec<Label>> {
+            let store = self.read_store_ref();
+            let labels = Vec::from_iter(store.values().cloned());
+            Ok(labels)
+        }
+
+        async fn delete(&self, id: i32, _force: bool) -> anyhow::Result<()> {
+            // todos are not linked to labels in memory, so a label is never in use
+            let mut store = self.write_store_ref();
+            store.remove(&id).ok_or(RepositoryError::NotFound(id))?;
+            self.defaults.write().unwrap().remove(&id);
+            Ok(())
+        }
+
+        async fn assign(&self, id: i32, _todo_ids: Vec<i32>) -> anyhow::Result<Vec<i32>> {
+            // todos are not linked to labels in memory, so nothing changes
+            self.read_store_ref()
+                .get(&id)
+                .ok_or(RepositoryError::NotFound(id))?;
+            Ok(vec![])
+        }
+
+        async fn unassign(&self, id: i32, _todo_ids: Vec<i32>) -> anyhow::Result<Vec<i32>> {
+            self.read_store_ref()
+                .get(&id)
+                .ok_or(RepositoryError::NotFound(id))?;
+            Ok(vec![])
+        }
+
+        async fn resolve(&self, public_id: &str) -> anyhow::Result<Option<i32>> {
+            let store = self.read_store_ref();
+            let label = store.values().find(|label| label.public_id == public_id);
+            Ok(label.map(|label| label.id))
+        }
+
+        async fn archive(&self, id: i32, archived: bool) -> anyhow::Result<Label> {
+            let mut store = self.write_store_ref();
+            let label = store.get_mut(&id).ok_or(RepositoryError::NotFound(id))?;
+            label.archived = archived;
+            Ok(label.clone())
+        }
+
+        async fn defaults(&self, id: i32) -> anyhow::Result<LabelDefaults> {
+            self.read_store_ref()
+                .get(&id)
+                .ok_or(RepositoryError::NotFound(id))?;
+            let defaults = self.defaults.read().unwrap().get(&id).cloned();
+            Ok(defaults.unwrap_or_default())
+        }
+
+        async fn set_defaults(
+            &self,
+            id: i32,
+            defaults: LabelDefaults,
+        ) -> anyhow::Result<LabelDefaults> {
+            self.read_store_ref()
+                .get(&id)
+                .ok_or(RepositoryError::NotFound(id))?;
+            self.defaults.write().unwrap().insert(id, defaults.clone());
+            Ok(defaults)
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[tokio::test]
+        async fn label_crud_scenario() {
+            let name = "label name".to_string();
+            let id = 1;
+            let expected = Label::new(id, name.clone());
+
+            let repo = LabelRepositoryForMemory::new();
+
+            // create
+            let label = repo
+                .create(CreateLabel { name })
+                .await
+                .expect("failed create label");
+            assert_eq!(expected, label);
+
+            // all
+            let labels = repo.all().await.expect("failed get all labels");
+            assert_eq!(vec![label.clone()], labels);
+
+            // find or create
+            let found = repo
+                .find_or_create(CreateLabel {
+                    name: "Label Name".to_string(),
+                })
+                .await
+                .expect("failed find or create label");
+            assert_eq!(found, (label, false));
+            let (created, is_new) = repo
+                .find_or_create(CreateLabel {
+                    name: "other".to_string(),
+                })
+                .await
+                .expect("failed find or create label");
+            assert!(is_new);
+            repo.delete(created.id, false)
+                .await
+                .expect("failed delete label");
+
+            // delete
+            repo.delete(id, false).await.expect("failed delete label");
+            let labels = repo.all().await.expect("failed get all labels");
+            assert_eq!(labels.len(), 0);
+
+            // ids are not handed out again after a deletion
+            let label = repo
+                .create(CreateLabel {
+                    name: "again".to_string(),
+                })
+                .await
+                .expect("failed create label");
+            assert_eq!(label.id, 3);
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "db-test")]
+mod test_psql_repo {
+
+    use super::*;
+    use crate::repositories::test_db::TestDb;
+    use crate::repositories::todo::{CreateTodo, TodoRepository, UpdateTodo};
+
+    #[tokio::test]
+    async fn label_names_ignore_case() {
+        let db = TestDb::new().await;
+        let repo = db.label_repo();
+
+        let work = repo
+            .create(CreateLabel {
+                name: "[label_names_ignore_case] Work".to_string(),
+            })
+            .await
+            .expect("[create] returned Err");
+        let err = repo
+            .create(CreateLabel {
+                name: "[label_names_ignore_case] WORK".to_string(),
+            })
+            .await
+            .expect_err("[create] of a duplicate returned Ok");
+        assert!(matches!(
+            err.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::DuplicatedLabel(id)) if *id == work.id
+        ));
+
+        let found = repo
+            .find_or_create(CreateLabel {
+                name: "[label_names_ignore_case] work".to_string(),
+            })
+            .await
+            .expect("[find_or_create] returned Err");
+        assert_eq!(found, (work.clone(), false));
+        let (created, is_new) = repo
+            .find_or_create(CreateLabel {
+                name: "[label_names_ignore_case] home".to_string(),
+            })
+            .await
+            .expect("[find_or_create] returned Err");
+        assert!(is_new);
+
+        for id in [work.id, created.id] {
+            repo.delete(id, false).await.expect("[delete] returned Err");
+        }
+    }
+
+    #[tokio::test]
+    async fn bulk_assignment() {
+        let db = TestDb::new().await;
+        let pool = db.pool.clone();
+        let repo = db.label_repo();
+        let todo_repo = db.todo_repo();
+
+        let label = repo
+            .create(CreateLabel {
+                name: "[bulk_assignment] label".to_string(),
+            })
+            .await
+            .expect("[create] returned Err");
+        let mut todo_ids = vec![];
+        for text in ["first", "second"] {
+            let todo = todo_repo
+                .create(CreateTodo::new(
+                    format!("[bulk_assignment] {}", text),
+                    vec![],
+                ))
+                .await
+                .expect("[create] todo returned Err");
+            todo_ids.push(todo.id);
+        }
+
+        // unknown todos are skipped, todos already tagged are left as they are
+        let assigned = repo
+            .assign(label.id, vec![todo_ids[1], i32::MAX])
+            .await
+            .expect("[assign] returned Err");
+        assert_eq!(assigned, vec![todo_ids[1]]);
+        let assigned = repo
+            .assign(label.id, todo_ids.clone())
+            .await
+            .expect("[assign] returned Err");
+        assert_eq!(assigned, vec![todo_ids[0]]);
+        let todo = todo_repo.find(todo_ids[0]).await.unwrap();
+        assert_eq!(todo.labels, vec![label.clone()]);
+        let revisions = todo_repo.revisions(todo_ids[0]).await.unwrap();
+        assert_eq!(revisions[1].kind, "label_attached");
+        assert_eq!(revisions[1].todo.label_ids, vec![label.id]);
+
+        let unassigned = repo
+            .unassign(label.id, todo_ids.clone())
+            .await
+            .expect("[unassign] returned Err");
+        assert_eq!(unassigned, todo_ids);
+        let attached: i64 =
+            sqlx::query_scalar("SELECT count(*) FROM todo_labels WHERE label_id = $1")
+                .bind(label.id)
+                .fetch_one(&pool)
+                .await
+                .expect("failed to count todo_labels");
+        assert_eq!(attached, 0);
+
+        let err = repo
+            .assign(i32::MAX, todo_ids.clone())
+            .await
+            .expect_err("[assign] of an unknown label returned Ok");
+        assert!(matches!(
+            err.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(_))
+        ));
+
+        for id in todo_ids {
+            todo_repo
+                .delete(id)
+                .await
+                .expect("[delete] todo returned Err");
+        }
+        repo.delete(label.id, false)
+            .await
+            .expect("[delete] returned Err");
+    }
+
+    #[tokio::test]
+    async fn defaults_fill_in_attached_todos() {
+        let db = TestDb::new().await;
+        let repo = db.label_repo();
+        let todo_repo = db.todo_repo();
+        let create = |name: &str| {
+            let name = name.to_string();
+            repo.create(CreateLabel { name })
+        };
+        let urgent = create("urgent").await.unwrap();
+        let weekly = create("weekly").await.unwrap();
+        let plain = create("plain").await.unwrap();
+        let defaults = |priority, due_in_minutes| LabelDefaults {
+            priority,
+            due_in_minutes,
+        };
+        repo.set_defaults(urgent.id, defaults(Some(Priority::High), Some(60)))
+            .await
+            .expect("[set_defaults] returned Err");
+        repo.set_defaults(weekly.id, defaults(Some(Priority::Low), Some(7 * 24 * 60)))
+            .await
+            .expect("[set_defaults] returned Err");
+        assert_eq!(
+            repo.defaults(weekly.id).await.unwrap(),
+            defaults(Some(Priority::Low), Some(7 * 24 * 60))
+        );
+        assert_eq!(repo.defaults(plain.id).await.unwrap(), defaults(None, None));
+        let err = repo.defaults(i32::MAX).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(_))
+        ));
+
+        // the highest priority and the soonest due date win
+        let before = chrono::Utc::now();
+        let todo = todo_repo
+            .create(
+                CreateTodo::builder("ship it")
+                    .labels(vec![weekly.id, urgent.id])
+                    .build(),
+            )
+            .await
+            .expect("[create] todo returned Err");
+        assert_eq!(todo.priority, Some(Priority::High));
+        let due_at = todo.due_at.expect("due_at unset");
+        assert!(due_at >= before + chrono::Duration::minutes(59));
+        assert!(due_at <= chrono::Utc::now() + chrono::Duration::minutes(60));
+
+        // values the todo has are kept
+        let todo = todo_repo
+            .create(
+                CreateTodo::builder("someday")
+                    .priority(Priority::Medium)
+                    .labels(vec![urgent.id])
+                    .build(),
+            )
+            .await
+            .expect("[create] todo returned Err");
+        assert_eq!(todo.priority, Some(Priority::Medium));
+        assert!(todo.due_at.is_some());
+
+        // only labels newly attached by an update apply
+        let todo = todo_repo
+            .create(
+                CreateTodo::builder("tidy up")
+                    .labels(vec![plain.id])
+                    .build(),
+            )
+            .await
+            .expect("[create] todo returned Err");
+        assert_eq!((todo.priority, todo.due_at), (None, None));
+        let todo = todo_repo
+            .update(
+                todo.id,
+                UpdateTodo::builder()
+                    .labels(vec![plain.id, weekly.id])
+                    .build(),
+            )
+            .await
+            .expect("[update] todo returned Err");
+        assert_eq!(todo.priority, Some(Priority::Low));
+
+        // and so do bulk assignments, recorded in the history
+        let todo = todo_repo
+            .create(CreateTodo::builder("call back").build())
+            .await
+            .expect("[create] todo returned Err");
+        repo.assign(urgent.id, vec![todo.id])
+            .await
+            .expect("[assign] returned Err");
+        let found = todo_repo.find(todo.id).await.unwrap();
+        assert_eq!(found.priority, Some(Priority::High));
+        let revisions = todo_repo.revisions(todo.id).await.unwrap();
+        let last = revisions.last().unwrap();
+        assert_eq!(last.kind, "updated");
+        assert_eq!(last.todo.priority, Some(Priority::High));
+        assert_eq!(last.todo.label_ids, vec![urgent.id]);
+    }
+
+    #[tokio::test]
+    async fn archived_labels_stay_on_todos() {
+        let db = TestDb::new().await;
+        let repo = db.label_repo();
+        let todo_repo = db.todo_repo();
+
+        let label = repo
+            .create(CreateLabel {
+                name: "[archived_labels_stay_on_todos] label".to_string(),
+            })
+            .await
+            .expect("[create] returned Err");
+        let todo = todo_repo
+            .create(CreateTodo::new(
+                "[archived_labels_stay_on_todos]".to_string(),
+                vec![label.id],
+            ))
+            .await
+            .expect("[create] todo returned Err");
+
+        let archived = repo
+            .archive(label.id, true)
+            .await
+            .expect("[archive] returned Err");
+        assert!(archived.archived);
+        assert_eq!(repo.all().await.unwrap(), vec![archived.clone()]);
+        let found = todo_repo.find(todo.id).await.unwrap();
+        assert_eq!(found.labels, vec![archived.clone()]);
+        let listed = todo_repo.all(Default::default()).await.unwrap();
+        assert_eq!(listed[0].labels, vec![archived]);
+
+        let restored = repo
+            .archive(label.id, false)
+            .await
+            .expect("[archive] returned Err");
+        assert_eq!(restored, label);
+        let err = repo.archive(i32::MAX, true).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn delete_label_in_use() {
+        let db = TestDb::new().await;
+        let pool = db.pool.clone();
+        let repo = db.label_repo();
+
+        let label = repo
+            .create(CreateLabel {
+                name: "[delete_label_in_use] label".to_string(),
+            })
+            .await
+            .expect("[create] returned Err");
+        let todo_id: i32 = sqlx::query_scalar(
+            r#"
+            INSERT INTO todos (text) VALUES ('[delete_label_in_use] text') RETURNING id
+            "#,
+        )
+        .fetch_one(&pool)
+        .await
+        .expect("failed to insert todo");
+        sqlx::query("INSERT INTO todo_labels (todo_id, label_id) VALUES ($1, $2)")
+            .bind(todo_id)
+            .bind(label.id)
+            .execute(&pool)
+            .await
+            .expect("failed to attach label");
+
+        // refused while attached
+        let err = repo
+            .delete(label.id, false)
+            .await
+            .expect_err("[delete] of label in use returned Ok");
+        assert!(matches!(
+            err.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::LabelInUse { todo_count: 1, .. })
+        ));
+
+        // force detaches and deletes
+        repo.delete(label.id, true)
+            .await
+            .expect("[delete] with force returned Err");
+        let err = repo
+            .delete(label.id, false)
+            .await
+            .expect_err("[delete] of deleted label returned Ok");
+        assert!(matches!(
+            err.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(_))
+        ));
+        let attached: i64 =
+            sqlx::query_scalar("SELECT count(*) FROM todo_labels WHERE todo_id = $1")
+                .bind(todo_id)
+                .fetch_one(&pool)
+                .await
+                .expect("failed to count todo_labels");
+        assert_eq!(attached, 0);
+
+        let _ = sqlx::query("DELETE FROM todos WHERE id = $1")
+            .bind(todo_id)
+            .execute(&pool)
+            .await;
+    }
+}
@@ -0,0 +1,165 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::async_trait;
+
+use crate::repositories::label::{CreateLabel, Label, LabelDefaults, LabelRepository};
+
+pub const DEFAULT_LABEL_CACHE_TTL: Duration = Duration::from_secs(10);
+
+/// Keeps the result of [`LabelRepository::all`] for `ttl`, as nearly every page load lists
+/// the labels while they rarely change. Creating, archiving or deleting a label through this
+/// repository drops the cached list; changes made elsewhere, e.g. by another instance, show up
+/// once the list expired.
+///
+/// Hits and misses are counted in `label_cache_hits_total` and `label_cache_misses_total`.
+#[derive(Debug, Clone)]
+pub struct CachedLabelRepository<R> {
+    inner: R,
+    ttl: Duration,
+    cache: Arc<Mutex<Cache>>,
+}
+
+#[derive(Debug, Default)]
+struct Cache {
+    /// Bumped by every invalidation, so a list read before it is not stored after it.
+    generation: u64,
+    labels: Option<(Instant, Vec<Label>)>,
+}
+
+impl<R: LabelRepository> CachedLabelRepository<R> {
+    /// A `ttl` of zero turns caching off.
+    pub fn new(inner: R, ttl: Duration) -> Self {
+        CachedLabelRepository {
+            inner,
+            ttl,
+            cache: Arc::default(),
+        }
+    }
+
+    fn invalidate(&self) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.generation += 1;
+        cache.labels = None;
+    }
+}
+
+#[async_trait]
+impl<R: LabelRepository> LabelRepository for CachedLabelRepository<R> {
+    async fn create(&self, label: CreateLabel) -> anyhow::Result<Label> {
+        let label = self.inner.create(label).await?;
+        self.invalidate();
+        Ok(label)
+    }
+
+    async fn find_or_create(&self, label: CreateLabel) -> anyhow::Result<(Label, bool)> {
+        let (label, created) = self.inner.find_or_create(label).await?;
+        if created {
+            self.invalidate();
+        }
+        Ok((label, created))
+    }
+
+    async fn all(&self) -> anyhow::Result<Vec<Label>> {
+        let generation = {
+            let cache = self.cache.lock().unwrap();
+            match &cache.labels {
+                Some((cached_at, labels)) if cached_at.elapsed() < self.ttl => {
+                    metrics::counter!("label_cache_hits_total").increment(1);
+                    return Ok(labels.clone());
+                }
+                _ => cache.generation,
+            }
+        };
+        metrics::counter!("label_cache_misses_total").increment(1);
+        let labels = self.inner.all().await?;
+        let mut cache = self.cache.lock().unwrap();
+        if cache.generation == generation && !self.ttl.is_zero() {
+            cache.labels = Some((Instant::now(), labels.clone()));
+        }
+        Ok(labels)
+    }
+
+    async fn delete(&self, id: i32, force: bool) -> anyhow::Result<()> {
+        self.inner.delete(id, force).await?;
+        self.invalidate();
+        Ok(())
+    }
+
+    async fn assign(&self, id: i32, todo_ids: Vec<i32>) -> anyhow::Result<Vec<i32>> {
+        self.inner.assign(id, todo_ids).await
+    }
+
+    async fn unassign(&self, id: i32, todo_ids: Vec<i32>) -> anyhow::Result<Vec<i32>> {
+        self.inner.unassign(id, todo_ids).await
+    }
+
+    async fn resolve(&self, public_id: &str) -> anyhow::Result<Option<i32>> {
+        self.inner.resolve(public_id).await
+    }
+
+    async fn archive(&self, id: i32, archived: bool) -> anyhow::Result<Label> {
+        let label = self.inner.archive(id, archived).await?;
+        self.invalidate();
+        Ok(label)
+    }
+
+    async fn defaults(&self, id: i32) -> anyhow::Result<LabelDefaults> {
+        self.inner.defaults(id).await
+    }
+
+    async fn set_defaults(
+        &self,
+        id: i32,
+        defaults: LabelDefaults,
+    ) -> anyhow::Result<LabelDefaults> {
+        self.inner.set_defaults(id, defaults).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::repositories::mock::MockLabelRepository;
+
+    fn counting_repo(reads: Arc<AtomicUsize>) -> MockLabelRepository {
+        MockLabelRepository::default()
+            .expect_all(move |_| {
+                let read = reads.fetch_add(1, Ordering::SeqCst) as i32;
+                Ok(vec![Label::new(read, format!("read {}", read))])
+            })
+            .expect_create(|label| Ok(Label::new(9, label.name)))
+            .expect_delete(|_| Ok(()))
+    }
+
+    #[tokio::test]
+    async fn cache_label_list() {
+        let reads = Arc::new(AtomicUsize::new(0));
+        let repo =
+            CachedLabelRepository::new(counting_repo(reads.clone()), Duration::from_secs(60));
+
+        let first = repo.all().await.unwrap();
+        assert_eq!(repo.all().await.unwrap(), first);
+        assert_eq!(reads.load(Ordering::SeqCst), 1);
+
+        let name = "new".to_string();
+        repo.create(CreateLabel { name }).await.unwrap();
+        assert_ne!(repo.all().await.unwrap(), first);
+        assert_eq!(reads.load(Ordering::SeqCst), 2);
+
+        repo.delete(9, false).await.unwrap();
+        repo.all().await.unwrap();
+        assert_eq!(reads.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn zero_ttl_disables_cache() {
+        let reads = Arc::new(AtomicUsize::new(0));
+        let repo = CachedLabelRepository::new(counting_repo(reads.clone()), Duration::ZERO);
+        repo.all().await.unwrap();
+        repo.all().await.unwrap();
+        assert_eq!(reads.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,337 @@
+//! Hand-written mocks of the repository traits, in the spirit of mockall's `automock`:
+//! every method answers through the closure given to its `expect_*` builder and panics when
+//! called without one. Lets handler tests reach error paths (database down, not found,
+//! conflict) that the in-memory repositories never produce.
+
+use axum::async_trait;
+use futures_util::stream::{self, BoxStream};
+
+use crate::repositories::label::{CreateLabel, Label, LabelDefaults, LabelRepository};
+use crate::repositories::report::ReportRepository;
+use crate::repositories::todo::{
+    CreateTodo, TodoCounts, TodoEntity, TodoQuery, TodoRepository, UpdateTodo,
+};
+
+type Handler<A, T> = Box<dyn Fn(A) -> anyhow::Result<T> + Send + Sync>;
+/// Label id and todo ids in, changed todo ids out.
+type AssignHandler = Handler<(i32, Vec<i32>), Vec<i32>>;
+
+fn call<A, T>(handler: &Option<Handler<A, T>>, method: &str, arg: A) -> anyhow::Result<T> {
+    let handler = handler
+        .as_ref()
+        .unwrap_or_else(|| panic!("unexpected call to {}", method));
+    handler(arg)
+}
+
+#[derive(Default)]
+pub struct MockTodoRepository {
+    create: Option<Handler<CreateTodo, TodoEntity>>,
+    find: Option<Handler<i32, TodoEntity>>,
+    all: Option<Handler<TodoQuery, Vec<TodoEntity>>>,
+    next: Option<Handler<(), Option<TodoEntity>>>,
+    stream: Option<Handler<TodoQuery, Vec<anyhow::Result<TodoEntity>>>>,
+    delete: Option<Handler<i32, ()>>,
+    update: Option<Handler<(i32, UpdateTodo), TodoEntity>>,
+    resolve: Option<Handler<String, Option<i32>>>,
+}
+
+impl MockTodoRepository {
+    pub fn expect_create(
+        mut self,
+        f: impl Fn(CreateTodo) -> anyhow::Result<TodoEntity> + Send + Sync + 'static,
+    ) -> Self {
+        self.create = Some(Box::new(f));
+        self
+    }
+
+    pub fn expect_find(
+        mut self,
+        f: impl Fn(i32) -> anyhow::Result<TodoEntity> + Send + Sync + 'static,
+    ) -> Self {
+        self.find = Some(Box::new(f));
+        self
+    }
+
+    pub fn expect_all(
+        mut self,
+        f: impl Fn(TodoQuery) -> anyhow::Result<Vec<TodoEntity>> + Send + Sync + 'static,
+    ) -> Self {
+        self.all = Some(Box::new(f));
+        self
+    }
+
+    pub fn expect_next(
+        mut self,
+        f: impl Fn(()) -> anyhow::Result<Option<TodoEntity>> + Send + Sync + 'static,
+    ) -> Self {
+        self.next = Some(Box::new(f));
+        self
+    }
+
+    /// `f` returns the items of the stream.
+    pub fn expect_stream(
+        mut self,
+        f: impl Fn(TodoQuery) -> anyhow::Result<Vec<anyhow::Result<TodoEntity>>> + Send + Sync + 'static,
+    ) -> Self {
+        self.stream = Some(Box::new(f));
+        self
+    }
+
+    pub fn expect_delete(
+        mut self,
+        f: impl Fn(i32) -> anyhow::Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.delete = Some(Box::new(f));
+        self
+    }
+
+    pub fn expect_update(
+        mut self,
+        f: impl Fn((i32, UpdateTodo)) -> anyhow::Result<TodoEntity> + Send + Sync + 'static,
+    ) -> Self {
+        self.update = Some(Box::new(f));
+        self
+    }
+
+    pub fn expect_resolve(
+        mut self,
+        f: impl Fn(String) -> anyhow::Result<Option<i32>> + Send + Sync + 'static,
+    ) -> Self {
+        self.resolve = Some(Box::new(f));
+        self
+    }
+}
+
+#[async_trait]
+impl TodoRepository for MockTodoRepository {
+    async fn create(&self, todo: CreateTodo) -> anyhow::Result<TodoEntity> {
+        call(&self.create, "TodoRepository::create", todo)
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<TodoEntity> {
+        call(&self.find, "TodoRepository::find", id)
+    }
+
+    async fn all(&self, query: TodoQuery) -> anyhow::Result<Vec<TodoEntity>> {
+        call(&self.all, "TodoRepository::all", query)
+    }
+
+    async fn next(&self) -> anyhow::Result<Option<TodoEntity>> {
+        call(&self.next, "TodoRepository::next", ())
+    }
+
+    fn stream(&self, query: TodoQuery) -> BoxStream<'static, anyhow::Result<TodoEntity>> {
+        match call(&self.stream, "TodoRepository::stream", query) {
+            // unfold, like the database stream, panics when polled after its end
+            Ok(items) => Box::pin(stream::unfold(items.into_iter(), |mut items| async move {
+                items.next().map(|item| (item, items))
+            })),
+            Err(e) => Box::pin(stream::once(async { Err(e) })),
+        }
+    }
+
+    /// Answered by the `stream` expectation, counting the todos it returns.
+    fn stream_counted(
+        &self,
+        query: TodoQuery,
+    ) -> BoxStream<'static, anyhow::Result<(TodoEntity, TodoCounts)>> {
+        match call(&self.stream, "TodoRepository::stream", query) {
+            Ok(items) => {
+                let counts = TodoCounts::of(items.iter().filter_map(|item| item.as_ref().ok()));
+                let items = items
+                    .into_iter()
+                    .map(|item| item.map(|todo| (todo, counts)))
+                    .collect::<Vec<_>>();
+                Box::pin(stream::unfold(items.into_iter(), |mut items| async move {
+                    items.next().map(|item| (item, items))
+                }))
+            }
+            Err(e) => Box::pin(stream::once(async { Err(e) })),
+        }
+    }
+
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        call(&self.delete, "TodoRepository::delete", id)
+    }
+
+    async fn update(&self, id: i32, todo: UpdateTodo) -> anyhow::Result<TodoEntity> {
+        call(&self.update, "TodoRepository::update", (id, todo))
+    }
+
+    async fn resolve(&self, public_id: &str) -> anyhow::Result<Option<i32>> {
+        let public_id = public_id.to_string();
+        call(&self.resolve, "TodoRepository::resolve", public_id)
+    }
+}
+
+#[derive(Default)]
+pub struct MockLabelRepository {
+    create: Option<Handler<CreateLabel, Label>>,
+    find_or_create: Option<Handler<CreateLabel, (Label, bool)>>,
+    all: Option<Handler<(), Vec<Label>>>,
+    delete: Option<Handler<(i32, bool), ()>>,
+    assign: Option<AssignHandler>,
+    unassign: Option<AssignHandler>,
+    resolve: Option<Handler<String, Option<i32>>>,
+    archive: Option<Handler<(i32, bool), Label>>,
+    defaults: Option<Handler<i32, LabelDefaults>>,
+    set_defaults: Option<Handler<(i32, LabelDefaults), LabelDefaults>>,
+}
+
+impl MockLabelRepository {
+    pub fn expect_create(
+        mut self,
+        f: impl Fn(CreateLabel) -> anyhow::Result<Label> + Send + Sync + 'static,
+    ) -> Self {
+        self.create = Some(Box::new(f));
+        self
+    }
+
+    pub fn expect_find_or_create(
+        mut self,
+        f: impl Fn(CreateLabel) -> anyhow::Result<(Label, bool)> + Send + Sync + 'static,
+    ) -> Self {
+        self.find_or_create = Some(Box::new(f));
+        self
+    }
+
+    pub fn expect_all(
+        mut self,
+        f: impl Fn(()) -> anyhow::Result<Vec<Label>> + Send + Sync + 'static,
+    ) -> Self {
+        self.all = Some(Box::new(f));
+        self
+    }
+
+    pub fn expect_delete(
+        mut self,
+        f: impl Fn((i32, bool)) -> anyhow::Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.delete = Some(Box::new(f));
+        self
+    }
+
+    pub fn expect_assign(
+        mut self,
+        f: impl Fn((i32, Vec<i32>)) -> anyhow::Result<Vec<i32>> + Send + Sync + 'static,
+    ) -> Self {
+        self.assign = Some(Box::new(f));
+        self
+    }
+
+    pub fn expect_unassign(
+        mut self,
+        f: impl Fn((i32, Vec<i32>)) -> anyhow::Result<Vec<i32>> + Send + Sync + 'static,
+    ) -> Self {
+        self.unassign = Some(Box::new(f));
+        self
+    }
+
+    pub fn expect_resolve(
+        mut self,
+        f: impl Fn(String) -> anyhow::Result<Option<i32>> + Send + Sync + 'static,
+    ) -> Self {
+        self.resolve = Some(Box::new(f));
+        self
+    }
+
+    pub fn expect_archive(
+        mut self,
+        f: impl Fn((i32, bool)) -> anyhow::Result<Label> + Send + Sync + 'static,
+    ) -> Self {
+        self.archive = Some(Box::new(f));
+        self
+    }
+
+    pub fn expect_defaults(
+        mut self,
+        f: impl Fn(i32) -> anyhow::Result<LabelDefaults> + Send + Sync + 'static,
+    ) -> Self {
+        self.defaults = Some(Box::new(f));
+        self
+    }
+
+    pub fn expect_set_defaults(
+        mut self,
+        f: impl Fn((i32, LabelDefaults)) -> anyhow::Result<LabelDefaults> + Send + Sync + 'static,
+    ) -> Self {
+        self.set_defaults = Some(Box::new(f));
+        self
+    }
+}
+
+#[async_trait]
+impl LabelRepository for MockLabelRepository {
+    async fn create(&self, label: CreateLabel) -> anyhow::Result<Label> {
+        call(&self.create, "LabelRepository::create", label)
+    }
+
+    async fn find_or_create(&self, label: CreateLabel) -> anyhow::Result<(Label, bool)> {
+        call(
+            &self.find_or_create,
+            "LabelRepository::find_or_create",
+            label,
+        )
+    }
+
+    async fn all(&self) -> anyhow::Result<Vec<Label>> {
+        call(&self.all, "LabelRepository::all", ())
+    }
+
+    async fn delete(&self, id: i32, force: bool) -> anyhow::Result<()> {
+        call(&self.delete, "LabelRepository::delete", (id, force))
+    }
+
+    async fn assign(&self, id: i32, todo_ids: Vec<i32>) -> anyhow::Result<Vec<i32>> {
+        call(&self.assign, "LabelRepository::assign", (id, todo_ids))
+    }
+
+    async fn unassign(&self, id: i32, todo_ids: Vec<i32>) -> anyhow::Result<Vec<i32>> {
+        call(&self.unassign, "LabelRepository::unassign", (id, todo_ids))
+    }
+
+    async fn resolve(&self, public_id: &str) -> anyhow::Result<Option<i32>> {
+        let public_id = public_id.to_string();
+        call(&self.resolve, "LabelRepository::resolve", public_id)
+    }
+
+    async fn archive(&self, id: i32, archived: bool) -> anyhow::Result<Label> {
+        call(&self.archive, "LabelRepository::archive", (id, archived))
+    }
+
+    async fn defaults(&self, id: i32) -> anyhow::Result<LabelDefaults> {
+        call(&self.defaults, "LabelRepository::defaults", id)
+    }
+
+    async fn set_defaults(
+        &self,
+        id: i32,
+        defaults: LabelDefaults,
+    ) -> anyhow::Result<LabelDefaults> {
+        let arg = (id, defaults);
+        call(&self.set_defaults, "LabelRepository::set_defaults", arg)
+    }
+}
+
+#[derive(Default)]
+pub struct MockReportRepository {
+    execute_raw: Option<Handler<String, Vec<serde_json::Value>>>,
+}
+
+impl MockReportRepository {
+    pub fn expect_execute_raw(
+        mut self,
+        f: impl Fn(String) -> anyhow::Result<Vec<serde_json::Value>> + Send + Sync + 'static,
+    ) -> Self {
+        self.execute_raw = Some(Box::new(f));
+        self
+    }
+}
+
+#[async_trait]
+impl ReportRepository for MockReportRepository {
+    async fn execute_raw(&self, sql: &str) -> anyhow::Result<Vec<serde_json::Value>> {
+        let sql = sql.to_string();
+        call(&self.execute_raw, "ReportRepository::execute_raw", sql)
+    }
+}
@@ -0,0 +1,136 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::async_trait;
+use serde_json::Value;
+use sqlx::types::Json;
+use sqlx::PgPool;
+
+use crate::repositories::{QueryTimer, RepositoryError, DEFAULT_SLOW_QUERY_THRESHOLD};
+
+/// Reports are cut off after this long, so an expensive one can't tie up the database.
+pub const DEFAULT_REPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Analytical queries written by operators, see [`crate::reports`].
+#[async_trait]
+pub trait ReportRepository: Send + Sync + 'static {
+    /// Run the single `select` in `sql`, returning its rows as JSON objects. Nothing it does
+    /// is written, and a query the database refuses fails with
+    /// `RepositoryError::InvalidQuery`.
+    async fn execute_raw(&self, sql: &str) -> anyhow::Result<Vec<Value>>;
+}
+
+#[async_trait]
+impl<R: ReportRepository + ?Sized> ReportRepository for Arc<R> {
+    async fn execute_raw(&self, sql: &str) -> anyhow::Result<Vec<Value>> {
+        (**self).execute_raw(sql).await
+    }
+}
+
+/// Best given a pool connected as a role that can only read. Either way every query runs in a
+/// read-only transaction that is rolled back, with a statement timeout.
+#[derive(Debug, Clone)]
+pub struct ReportRepositoryForDb {
+    pool: PgPool,
+    timeout: Duration,
+}
+
+impl ReportRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            timeout: DEFAULT_REPORT_TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+}
+
+#[async_trait]
+impl ReportRepository for ReportRepositoryForDb {
+    #[tracing::instrument(name = "reports.execute_raw", skip(self))]
+    async fn execute_raw(&self, sql: &str) -> anyhow::Result<Vec<Value>> {
+        let _timer = QueryTimer::start("reports.execute_raw", DEFAULT_SLOW_QUERY_THRESHOLD);
+        let sql = sql.trim().trim_end_matches(';');
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("set transaction read only")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("select set_config('statement_timeout', $1, true)")
+            .bind(format!("{}ms", self.timeout.as_millis()))
+            .execute(&mut *tx)
+            .await?;
+        // A prepared statement holds one statement only, so `sql` can't smuggle in another
+        // after closing the subquery.
+        let rows = sqlx::query_scalar::<_, Json<Vec<Value>>>(&format!(
+            "select coalesce(json_agg(report), '[]'::json) from ({}) report",
+            sql
+        ))
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) => RepositoryError::InvalidQuery(db.message().to_string()),
+            e => RepositoryError::from(e),
+        })?;
+        tx.rollback().await?;
+        Ok(rows.0)
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "db-test")]
+mod test_psql_repo {
+    use super::*;
+    use crate::repositories::test_db::TestDb;
+    use crate::repositories::todo::{CreateTodo, TodoRepository};
+
+    #[tokio::test]
+    async fn execute_raw_reads_only() {
+        let db = TestDb::new().await;
+        db.todo_repo()
+            .create(CreateTodo::builder("[execute_raw] text").build())
+            .await
+            .expect("[create] returned Err");
+        let repo = ReportRepositoryForDb::new(db.pool.clone());
+
+        let rows = repo
+            .execute_raw("select text, completed from todos;")
+            .await
+            .expect("[execute_raw] returned Err");
+        assert_eq!(
+            rows,
+            vec![serde_json::json!({"text": "[execute_raw] text", "completed": false})]
+        );
+        let rows = repo.execute_raw("select 1 where false").await.unwrap();
+        assert!(rows.is_empty());
+
+        let refused = [
+            "with deleted as (delete from todos returning id) select * from deleted",
+            "select 1) report; delete from todos; select (1",
+            "select pg_sleep(1)",
+            "selec 1",
+        ];
+        let repo = repo.with_timeout(Duration::from_millis(100));
+        for sql in refused {
+            let err = repo.execute_raw(sql).await.unwrap_err();
+            assert!(
+                matches!(
+                    err.downcast_ref::<RepositoryError>(),
+                    Some(RepositoryError::InvalidQuery(_))
+                ),
+                "{}: {:?}",
+                sql,
+                err
+            );
+        }
+        assert_eq!(
+            sqlx::query_scalar::<_, i64>("select count(*) from todos")
+                .fetch_one(&db.pool)
+                .await
+                .unwrap(),
+            1
+        );
+    }
+}
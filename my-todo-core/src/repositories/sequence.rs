@@ -0,0 +1,37 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Hands out the ids of new records where no database does, as in the in-memory
+/// repositories.
+pub trait IdGenerator: Debug + Send + Sync + 'static {
+    type Id;
+
+    fn next_id(&self) -> Self::Id;
+}
+
+/// 1, 2, 3… like a `serial` column: an id is never handed out twice, even once the record
+/// holding it has been deleted.
+#[derive(Debug, Default)]
+pub struct Sequence {
+    last: AtomicI32,
+}
+
+impl IdGenerator for Sequence {
+    type Id = i32;
+
+    fn next_id(&self) -> i32 {
+        self.last.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_up_from_one() {
+        let sequence = Sequence::default();
+        let ids = (0..3).map(|_| sequence.next_id()).collect::<Vec<i32>>();
+        assert_eq!(ids, [1, 2, 3]);
+    }
+}
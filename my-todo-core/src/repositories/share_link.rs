@@ -0,0 +1,93 @@
+use ring::digest::{digest, SHA256};
+use sqlx::PgPool;
+
+use crate::middleware::csrf::new_token;
+use crate::repositories::RepositoryError;
+
+/// What a share link opens, see [`crate::share`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShareTarget {
+    Todo(i32),
+    /// The todos with the label, as they are when the link is opened.
+    Label(i32),
+    /// The todos a `GET /todos` query string selects.
+    Filter(String),
+}
+
+impl ShareTarget {
+    fn columns(&self) -> (Option<i32>, Option<i32>, Option<&str>) {
+        match self {
+            ShareTarget::Todo(id) => (Some(*id), None, None),
+            ShareTarget::Label(id) => (None, Some(*id), None),
+            ShareTarget::Filter(filter) => (None, None, Some(filter)),
+        }
+    }
+}
+
+/// Only the hash is stored, so a copy of the database doesn't hand out working links.
+pub(crate) fn hash(token: &str) -> Vec<u8> {
+    digest(&SHA256, token.as_bytes()).as_ref().to_vec()
+}
+
+/// A new link to `target`, returning its token. Fails with `RepositoryError::NotFound` when
+/// `target` does not exist.
+pub(crate) async fn create(pool: &PgPool, target: &ShareTarget) -> anyhow::Result<String> {
+    let token = new_token();
+    let (todo_id, label_id, filter) = target.columns();
+    sqlx::query(
+        r#"insert into share_links (token_hash, todo_id, label_id, filter) values ($1, $2, $3, $4)"#,
+    )
+    .bind(hash(&token))
+    .bind(todo_id)
+    .bind(label_id)
+    .bind(filter)
+    .execute(pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+                RepositoryError::NotFound(todo_id.or(label_id).unwrap_or_default())
+            }
+            e => RepositoryError::from(e),
+        })?;
+    Ok(token)
+}
+
+/// What `token` opens, `None` when it was revoked or never issued.
+pub(crate) async fn find(pool: &PgPool, token: &str) -> anyhow::Result<Option<ShareTarget>> {
+    let row = sqlx::query_as::<_, (Option<i32>, Option<i32>, Option<String>)>(
+        r#"select todo_id, label_id, filter from share_links where token_hash = $1"#,
+    )
+    .bind(hash(token))
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.and_then(|columns| match columns {
+        (Some(todo_id), _, _) => Some(ShareTarget::Todo(todo_id)),
+        (_, Some(label_id), _) => Some(ShareTarget::Label(label_id)),
+        (_, _, Some(filter)) => Some(ShareTarget::Filter(filter)),
+        (None, None, None) => None,
+    }))
+}
+
+/// Revoke every link to `target`, returning how many there were.
+pub(crate) async fn revoke(pool: &PgPool, target: &ShareTarget) -> anyhow::Result<u64> {
+    let (todo_id, label_id, filter) = target.columns();
+    let revoked = sqlx::query(
+        r#"delete from share_links where todo_id is not distinct from $1
+                and label_id is not distinct from $2 and filter is not distinct from $3"#,
+    )
+    .bind(todo_id)
+    .bind(label_id)
+    .bind(filter)
+    .execute(pool)
+    .await?;
+    Ok(revoked.rows_affected())
+}
+
+/// Revoke the link with `token` alone, returning whether there was one.
+pub(crate) async fn revoke_token(pool: &PgPool, token: &str) -> anyhow::Result<bool> {
+    let revoked = sqlx::query(r#"delete from share_links where token_hash = $1"#)
+        .bind(hash(token))
+        .execute(pool)
+        .await?;
+    Ok(revoked.rows_affected() > 0)
+}
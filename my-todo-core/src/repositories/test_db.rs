@@ -0,0 +1,89 @@
+use std::env;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use dotenvy::dotenv;
+use sqlx::postgres::PgConnectOptions;
+use sqlx::{Connection, PgConnection, PgPool};
+
+use crate::repositories::label::LabelRepositoryForDb;
+use crate::repositories::todo::TodoRepositoryForDb;
+
+static NEXT_DB: AtomicUsize = AtomicUsize::new(0);
+
+/// A freshly migrated database of its own for a `db-test`, created next to the one in
+/// `DATABASE_URL` and dropped again with the `TestDb`, so tests running in parallel never
+/// see each other's rows.
+pub struct TestDb {
+    pub pool: PgPool,
+    server: PgConnectOptions,
+    name: String,
+}
+
+impl TestDb {
+    pub async fn new() -> Self {
+        dotenv().ok();
+        let database_url = env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let server = PgConnectOptions::from_str(&database_url)
+            .unwrap_or_else(|_| panic!("invalid [DATABASE_URL]: [{}]", database_url));
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let name = format!(
+            "my_todo_test_{}_{}_{}",
+            std::process::id(),
+            started,
+            NEXT_DB.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let mut conn = PgConnection::connect_with(&server)
+            .await
+            .unwrap_or_else(|_| panic!("failed to connect database: [{}]", database_url));
+        sqlx::query(&format!(r#"create database "{}""#, name))
+            .execute(&mut conn)
+            .await
+            .expect("failed to create test database");
+        conn.close().await.ok();
+
+        let pool = PgPool::connect_with(server.clone().database(&name))
+            .await
+            .expect("failed to connect test database");
+        sqlx::migrate!("../migrations")
+            .run(&pool)
+            .await
+            .expect("failed to migrate test database");
+        TestDb { pool, server, name }
+    }
+
+    pub fn todo_repo(&self) -> TodoRepositoryForDb {
+        TodoRepositoryForDb::new(self.pool.clone())
+    }
+
+    pub fn label_repo(&self) -> LabelRepositoryForDb {
+        LabelRepositoryForDb::new(self.pool.clone())
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        let server = self.server.clone();
+        let drop_database = format!(r#"drop database if exists "{}" with (force)"#, self.name);
+        // Drop can't await, and the test's runtime may be single threaded.
+        let dropped = std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?
+                .block_on(async move {
+                    let mut conn = PgConnection::connect_with(&server).await?;
+                    sqlx::query(&drop_database).execute(&mut conn).await?;
+                    anyhow::Ok(())
+                })
+        })
+        .join();
+        if !matches!(dropped, Ok(Ok(()))) {
+            eprintln!("failed to drop test database {}", self.name);
+        }
+    }
+}
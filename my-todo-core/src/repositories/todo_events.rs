@@ -0,0 +1,443 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{FromRow, PgConnection, PgPool};
+
+use crate::repositories::todo::{Priority, Todo};
+use crate::repositories::RepositoryError;
+
+/// Full state of a todo after a change, so replaying events never needs the previous state
+/// to be intact.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TodoSnapshot {
+    /// Missing in events recorded before todos had public ids.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_id: Option<String>,
+    pub text: String,
+    pub description: Option<String>,
+    pub completed: bool,
+    pub due_at: Option<DateTime<Utc>>,
+    pub priority: Option<Priority>,
+    /// Missing in events recorded before todos could be starred.
+    #[serde(default)]
+    pub starred: bool,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub estimate_minutes: Option<i32>,
+    /// Missing in events recorded before completions were timed.
+    #[serde(default)]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub archived: bool,
+    pub label_ids: Vec<i32>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl TodoSnapshot {
+    pub(crate) fn new(todo: &Todo, label_ids: Vec<i32>) -> Self {
+        TodoSnapshot {
+            public_id: Some(todo.public_id.clone()),
+            text: todo.text.clone(),
+            description: todo.description.clone(),
+            completed: todo.completed,
+            due_at: todo.due_at,
+            priority: todo.priority,
+            starred: todo.starred,
+            color: todo.color.clone(),
+            icon: todo.icon.clone(),
+            estimate_minutes: todo.estimate_minutes,
+            completed_at: todo.completed_at,
+            archived: todo.archived,
+            label_ids,
+            created_at: todo.created_at,
+            updated_at: todo.updated_at,
+        }
+    }
+}
+
+/// One row of `todo_events`, stored as its `kind` and `data`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "data", rename_all = "snake_case")]
+pub enum TodoChange {
+    Created(TodoSnapshot),
+    Updated(TodoSnapshot),
+    Deleted {},
+    /// A bulk assignment put the label on the todo.
+    LabelAttached {
+        label_id: i32,
+    },
+    /// A forced label delete or a bulk unassignment took the label off the todo.
+    LabelDetached {
+        label_id: i32,
+    },
+}
+
+impl TodoChange {
+    /// Apply a label change to the todo as it was before; other changes are ignored.
+    fn relabel(&self, todo: &mut TodoSnapshot) {
+        match self {
+            TodoChange::LabelAttached { label_id } if !todo.label_ids.contains(label_id) => {
+                todo.label_ids.push(*label_id);
+            }
+            TodoChange::LabelDetached { label_id } => {
+                todo.label_ids.retain(|id| id != label_id);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct TodoEventRow {
+    todo_id: i32,
+    kind: String,
+    data: Json<serde_json::Value>,
+}
+
+impl TryFrom<TodoEventRow> for (i32, TodoChange) {
+    type Error = serde_json::Error;
+
+    fn try_from(row: TodoEventRow) -> Result<Self, Self::Error> {
+        let change = serde_json::from_value(serde_json::json!({
+            "kind": row.kind,
+            "data": row.data.0,
+        }))?;
+        Ok((row.todo_id, change))
+    }
+}
+
+/// Append `change` to the history on `conn`, which should be the transaction making the change.
+pub(crate) async fn append(
+    conn: &mut PgConnection,
+    todo_id: i32,
+    change: &TodoChange,
+) -> Result<(), RepositoryError> {
+    let mut value =
+        serde_json::to_value(change).map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+    let data = value["data"].take();
+    sqlx::query(r#"insert into todo_events (todo_id, kind, data) values ($1, $2, $3)"#)
+        .bind(todo_id)
+        .bind(value["kind"].as_str())
+        .bind(Json(data))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// [`append`] the same `change` to the history of each of `todo_ids`.
+pub(crate) async fn append_all(
+    conn: &mut PgConnection,
+    todo_ids: &[i32],
+    change: &TodoChange,
+) -> Result<(), RepositoryError> {
+    let mut value =
+        serde_json::to_value(change).map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+    let data = value["data"].take();
+    sqlx::query(
+        r#"
+        insert into todo_events (todo_id, kind, data)
+        select todo_id, $2, $3 from unnest($1::int[]) as todo_id
+        "#,
+    )
+    .bind(todo_ids)
+    .bind(value["kind"].as_str())
+    .bind(Json(data))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Record [`TodoChange::LabelDetached`] for every todo carrying `label_id`.
+pub(crate) async fn append_label_detached(
+    conn: &mut PgConnection,
+    label_id: i32,
+) -> Result<(), RepositoryError> {
+    sqlx::query(
+        r#"
+        insert into todo_events (todo_id, kind, data)
+        select todo_id, 'label_detached', jsonb_build_object('label_id', label_id)
+        from todo_labels
+        where label_id = $1
+        "#,
+    )
+    .bind(label_id)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// A todo as it was after one of its changes, numbered from 1 in the order they happened.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Revision {
+    pub number: usize,
+    /// The change that led to it, `created`, `updated`, `label_attached` or `label_detached`.
+    pub kind: String,
+    pub occurred_at: DateTime<Utc>,
+    pub todo: TodoSnapshot,
+}
+
+/// Every revision of `todo_id`, oldest first; empty for a todo that never existed. Text and
+/// description are returned as stored.
+pub(crate) async fn revisions(pool: &PgPool, todo_id: i32) -> anyhow::Result<Vec<Revision>> {
+    let rows = sqlx::query_as::<_, (String, Json<serde_json::Value>, DateTime<Utc>)>(
+        r#"select kind, data, occurred_at from todo_events where todo_id = $1 order by id"#,
+    )
+    .bind(todo_id)
+    .fetch_all(pool)
+    .await?;
+    let mut revisions = Vec::<Revision>::new();
+    for (kind, data, occurred_at) in rows {
+        let row = TodoEventRow {
+            todo_id,
+            kind: kind.clone(),
+            data,
+        };
+        let (_, change) = <(i32, TodoChange)>::try_from(row)?;
+        let todo = match change {
+            TodoChange::Created(snapshot) | TodoChange::Updated(snapshot) => snapshot,
+            TodoChange::LabelAttached { .. } | TodoChange::LabelDetached { .. } => {
+                let Some(last) = revisions.last() else {
+                    continue;
+                };
+                let mut todo = last.todo.clone();
+                change.relabel(&mut todo);
+                todo
+            }
+            TodoChange::Deleted {} => break,
+        };
+        revisions.push(Revision {
+            number: revisions.len() + 1,
+            kind,
+            occurred_at,
+            todo,
+        });
+    }
+    Ok(revisions)
+}
+
+/// Every change that happened before `until`, in order, with when it happened.
+pub(crate) async fn history_until(
+    pool: &PgPool,
+    until: DateTime<Utc>,
+) -> anyhow::Result<Vec<(i32, TodoChange, DateTime<Utc>)>> {
+    let rows = sqlx::query_as::<_, (i32, String, Json<serde_json::Value>, DateTime<Utc>)>(
+        r#"select todo_id, kind, data, occurred_at from todo_events where occurred_at < $1 order by id"#,
+    )
+    .bind(until)
+    .fetch_all(pool)
+    .await?;
+    let mut history = Vec::with_capacity(rows.len());
+    for (todo_id, kind, data, occurred_at) in rows {
+        let (todo_id, change) = <(i32, TodoChange)>::try_from(TodoEventRow {
+            todo_id,
+            kind,
+            data,
+        })?;
+        history.push((todo_id, change, occurred_at));
+    }
+    Ok(history)
+}
+
+/// Replay `events` (in the order they happened) into the current todos by id.
+pub fn project(events: impl IntoIterator<Item = (i32, TodoChange)>) -> BTreeMap<i32, TodoSnapshot> {
+    let mut todos = BTreeMap::new();
+    for (todo_id, change) in events {
+        match change {
+            TodoChange::Created(snapshot) | TodoChange::Updated(snapshot) => {
+                todos.insert(todo_id, snapshot);
+            }
+            TodoChange::Deleted {} => {
+                todos.remove(&todo_id);
+            }
+            TodoChange::LabelAttached { .. } | TodoChange::LabelDetached { .. } => {
+                if let Some(todo) = todos.get_mut(&todo_id) {
+                    change.relabel(todo);
+                }
+            }
+        }
+    }
+    todos
+}
+
+/// Rebuild `todos` and `todo_labels` from `todo_events`, returning how many todos exist.
+/// Labels that were deleted since are left out.
+pub async fn rebuild_projection(pool: &PgPool) -> anyhow::Result<usize> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(r#"lock table todos, todo_labels in exclusive mode"#)
+        .execute(&mut *tx)
+        .await?;
+    let events = sqlx::query_as::<_, TodoEventRow>(
+        r#"select todo_id, kind, data from todo_events order by id"#,
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .map(<(i32, TodoChange)>::try_from)
+    .collect::<Result<Vec<(i32, TodoChange)>, serde_json::Error>>()?;
+    let todos = project(events);
+    // events recorded before todos had public ids don't carry them
+    let public_ids: BTreeMap<i32, String> =
+        sqlx::query_as::<_, (i32, String)>(r#"select id, public_id from todos"#)
+            .fetch_all(&mut *tx)
+            .await?
+            .into_iter()
+            .collect();
+
+    sqlx::query(r#"delete from todo_labels"#)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(r#"delete from todos"#)
+        .execute(&mut *tx)
+        .await?;
+    // ids are inserted explicitly, so the sequence keeps handing out unused ones
+    for (id, todo) in &todos {
+        sqlx::query(
+            r#"
+            insert into todos (id, text, description, completed, due_at, priority, starred, color, icon, estimate_minutes, created_at, updated_at, public_id, completed_at, archived)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, coalesce($13, generate_ulid()),
+                    case when $4 then coalesce($14, $12) end, $15)
+            "#,
+        )
+        .bind(id)
+        .bind(&todo.text)
+        .bind(&todo.description)
+        .bind(todo.completed)
+        .bind(todo.due_at)
+        .bind(todo.priority)
+        .bind(todo.starred)
+        .bind(&todo.color)
+        .bind(&todo.icon)
+        .bind(todo.estimate_minutes)
+        .bind(todo.created_at)
+        .bind(todo.updated_at)
+        .bind(todo.public_id.as_ref().or(public_ids.get(id)))
+        .bind(todo.completed_at)
+        .bind(todo.archived)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            insert into todo_labels (todo_id, label_id)
+            select $1, id from labels where id = any($2)
+            "#,
+        )
+        .bind(id)
+        .bind(&todo.label_ids)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(todos.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(text: &str, label_ids: Vec<i32>) -> TodoSnapshot {
+        let now = Utc::now();
+        TodoSnapshot {
+            public_id: None,
+            text: text.to_string(),
+            description: None,
+            completed: false,
+            due_at: None,
+            priority: None,
+            starred: false,
+            color: None,
+            icon: None,
+            estimate_minutes: None,
+            completed_at: None,
+            archived: false,
+            label_ids,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn replay_history() {
+        let todos = project(vec![
+            (1, TodoChange::Created(snapshot("first", vec![1, 2]))),
+            (2, TodoChange::Created(snapshot("second", vec![]))),
+            (
+                1,
+                TodoChange::Updated(snapshot("first, edited", vec![1, 2])),
+            ),
+            (1, TodoChange::LabelDetached { label_id: 1 }),
+            (1, TodoChange::LabelAttached { label_id: 3 }),
+            (1, TodoChange::LabelAttached { label_id: 3 }),
+            (2, TodoChange::Deleted {}),
+        ]);
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[&1].text, "first, edited");
+        assert_eq!(todos[&1].label_ids, vec![2, 3]);
+    }
+
+    #[test]
+    fn decode_stored_change() {
+        let row = TodoEventRow {
+            todo_id: 3,
+            kind: "label_detached".to_string(),
+            data: Json(serde_json::json!({"label_id": 7})),
+        };
+        assert_eq!(
+            <(i32, TodoChange)>::try_from(row).unwrap(),
+            (3, TodoChange::LabelDetached { label_id: 7 })
+        );
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "db-test")]
+mod test_psql_repo {
+
+    use super::*;
+    use crate::repositories::test_db::TestDb;
+    use crate::repositories::todo::{CreateTodo, TodoRepository};
+
+    #[tokio::test]
+    async fn history_is_append_only() {
+        let db = TestDb::new().await;
+        let pool = db.pool.clone();
+        let repo = db.todo_repo();
+
+        let created = repo
+            .create(CreateTodo::new(
+                "[history_is_append_only] text".to_string(),
+                vec![],
+            ))
+            .await
+            .expect("[create] returned Err");
+        repo.delete(created.id)
+            .await
+            .expect("[delete] returned Err");
+
+        let events = sqlx::query_as::<_, TodoEventRow>(
+            r#"select todo_id, kind, data from todo_events where todo_id = $1 order by id"#,
+        )
+        .bind(created.id)
+        .fetch_all(&pool)
+        .await
+        .expect("failed to fetch todo_events")
+        .into_iter()
+        .map(|row| <(i32, TodoChange)>::try_from(row).unwrap().1)
+        .collect::<Vec<TodoChange>>();
+        assert!(matches!(
+            &events[..],
+            [TodoChange::Created(snapshot), TodoChange::Deleted {}] if snapshot.text == created.text
+        ));
+
+        let res = sqlx::query(r#"delete from todo_events where todo_id = $1"#)
+            .bind(created.id)
+            .execute(&pool)
+            .await;
+        assert!(res.is_err());
+    }
+}
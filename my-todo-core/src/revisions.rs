@@ -0,0 +1,355 @@
+use std::sync::Arc;
+
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use serde::Serialize;
+
+use crate::handlers::{error_status, PathId};
+use crate::repositories::todo::TodoRepositoryForDb;
+use crate::repositories::todo_events::Revision;
+
+/// Fields left out of diffs: the timestamps change with every revision or along with
+/// `completed`, and the public id never does but is missing in revisions recorded before
+/// there were any.
+const NOT_DIFFED: [&str; 4] = ["public_id", "created_at", "updated_at", "completed_at"];
+
+/// Field-by-field changes between two revisions of a todo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RevisionDiff {
+    pub from: usize,
+    pub to: usize,
+    pub changes: Vec<FieldChange>,
+    /// Line diff of the description when it changed, see [`line_diff`].
+    pub description_diff: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub from: serde_json::Value,
+    pub to: serde_json::Value,
+}
+
+/// `GET /todos/:id/revisions` lists every state a todo went through, and
+/// `GET /todos/:id/revisions/:a/diff/:b` compares two of them for a "what changed" view.
+/// History lives in the database, so these are served by [`TodoRepositoryForDb`] only.
+pub fn routes(todo_repo: TodoRepositoryForDb) -> Router {
+    Router::new()
+        .route("/todos/:id/revisions", get(list))
+        .route("/todos/:id/revisions/:a/diff/:b", get(compare))
+        .layer(Extension(Arc::new(todo_repo)))
+}
+
+async fn list(
+    Extension(repo): Extension<Arc<TodoRepositoryForDb>>,
+    Path(id): Path<PathId>,
+) -> Result<Json<Vec<Revision>>, StatusCode> {
+    let id = id.todo(&*repo).await?;
+    repo.revisions(id)
+        .await
+        .map(Json)
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+async fn compare(
+    Extension(repo): Extension<Arc<TodoRepositoryForDb>>,
+    Path((id, a, b)): Path<(PathId, usize, usize)>,
+) -> Result<Json<RevisionDiff>, StatusCode> {
+    let id = id.todo(&*repo).await?;
+    let revisions = repo
+        .revisions(id)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    let revision = |number: usize| {
+        number
+            .checked_sub(1)
+            .and_then(|i| revisions.get(i))
+            .ok_or(StatusCode::NOT_FOUND)
+    };
+    Ok(Json(diff(revision(a)?, revision(b)?)))
+}
+
+/// Compare every field but those [`NOT_DIFFED`], in alphabetical order.
+pub fn diff(from: &Revision, to: &Revision) -> RevisionDiff {
+    let as_map = |revision: &Revision| match serde_json::to_value(&revision.todo) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => unreachable!("a snapshot serializes to an object"),
+    };
+    let (old, new) = (as_map(from), as_map(to));
+    let mut fields = old.keys().chain(new.keys()).collect::<Vec<&String>>();
+    fields.sort();
+    fields.dedup();
+    let changes = fields
+        .into_iter()
+        .filter(|field| !NOT_DIFFED.contains(&field.as_str()))
+        .filter_map(|field| {
+            let before = old.get(field).cloned().unwrap_or_default();
+            let after = new.get(field).cloned().unwrap_or_default();
+            (before != after).then(|| FieldChange {
+                field: field.clone(),
+                from: before,
+                to: after,
+            })
+        })
+        .collect();
+    let description_diff = (from.todo.description != to.todo.description).then(|| {
+        line_diff(
+            from.todo.description.as_deref().unwrap_or_default(),
+            to.todo.description.as_deref().unwrap_or_default(),
+        )
+    });
+    RevisionDiff {
+        from: from.number,
+        to: to.number,
+        changes,
+        description_diff,
+    }
+}
+
+/// Pairs of changed lines [`line_diff`] compares at most, keeping its table at a few MB.
+const MAX_DIFF_CELLS: usize = 1_000_000;
+
+/// Every line of `old` and `new`, prefixed with `-` when removed, `+` when added and a space
+/// when kept, like `diff -U` without hunk headers. Kept lines are those the two share at the
+/// start and end, and a longest common subsequence of the lines in between. When there are
+/// more than [`MAX_DIFF_CELLS`] pairs of those, a 10000-line description can have 10^8, they
+/// are all shown as removed and added instead.
+pub fn line_diff(old: &str, new: &str) -> String {
+    let old = old.lines().collect::<Vec<&str>>();
+    let new = new.lines().collect::<Vec<&str>>();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let kept_end = &old[old.len() - suffix..];
+
+    let mut diff = String::new();
+    let mut line = |prefix: char, text: &str| {
+        diff.push(prefix);
+        diff.push_str(text);
+        diff.push('\n');
+    };
+    old[..prefix].iter().for_each(|text| line(' ', text));
+    let (old, new) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    let (mut i, mut j) = (0, 0);
+    if (old.len() + 1).saturating_mul(new.len() + 1) <= MAX_DIFF_CELLS {
+        // common[i][j]: length of the longest common subsequence of old[i..] and new[j..],
+        // at most 10000 lines
+        let mut common = vec![vec![0u16; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                common[i][j] = if old[i] == new[j] {
+                    common[i + 1][j + 1] + 1
+                } else {
+                    common[i + 1][j].max(common[i][j + 1])
+                };
+            }
+        }
+        while i < old.len() && j < new.len() {
+            if old[i] == new[j] {
+                line(' ', old[i]);
+                i += 1;
+                j += 1;
+            } else if common[i + 1][j] >= common[i][j + 1] {
+                line('-', old[i]);
+                i += 1;
+            } else {
+                line('+', new[j]);
+                j += 1;
+            }
+        }
+    }
+    old[i..].iter().for_each(|text| line('-', text));
+    new[j..].iter().for_each(|text| line('+', text));
+    kept_end.iter().for_each(|text| line(' ', text));
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::repositories::todo_events::TodoSnapshot;
+
+    fn revision(number: usize, text: &str, description: Option<&str>) -> Revision {
+        let now = Utc::now();
+        Revision {
+            number,
+            kind: "updated".to_string(),
+            occurred_at: now,
+            todo: TodoSnapshot {
+                public_id: Some(crate::public_id::from_serial(1)),
+                text: text.to_string(),
+                description: description.map(str::to_string),
+                completed: false,
+                due_at: None,
+                priority: None,
+                starred: false,
+                color: None,
+                icon: None,
+                estimate_minutes: None,
+                completed_at: None,
+                archived: false,
+                label_ids: vec![1],
+                created_at: now,
+                updated_at: now,
+            },
+        }
+    }
+
+    #[test]
+    fn diff_revisions() {
+        let first = revision(1, "buy milk", Some("whole\nlow fat"));
+        let mut second = revision(2, "buy oat milk", Some("whole\noat\n"));
+        second.todo.completed = true;
+        second.todo.label_ids = vec![1, 2];
+
+        let diff = diff(&first, &second);
+        assert_eq!((diff.from, diff.to), (1, 2));
+        let fields = diff
+            .changes
+            .iter()
+            .map(|change| change.field.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            fields,
+            vec!["completed", "description", "label_ids", "text"]
+        );
+        assert_eq!(
+            diff.changes[3],
+            FieldChange {
+                field: "text".to_string(),
+                from: "buy milk".into(),
+                to: "buy oat milk".into(),
+            }
+        );
+        assert_eq!(
+            diff.description_diff.as_deref(),
+            Some(" whole\n-low fat\n+oat\n")
+        );
+
+        let same = super::diff(&first, &first);
+        assert!(same.changes.is_empty());
+        assert_eq!(same.description_diff, None);
+    }
+
+    #[test]
+    fn diff_lines() {
+        assert_eq!(line_diff("", ""), "");
+        assert_eq!(line_diff("", "a"), "+a\n");
+        assert_eq!(line_diff("a\nb\nc", "a\nc\nd"), " a\n-b\n c\n+d\n");
+        assert_eq!(line_diff("a\nb", "b\na"), "-a\n b\n+a\n");
+        assert_eq!(line_diff("a\nb\nc", "a\nb\nb\nc"), " a\n b\n+b\n c\n");
+    }
+
+    #[test]
+    fn diff_long_descriptions() {
+        // 10000 lines that only differ at the end
+        let old = format!("{}a", "\n".repeat(9999));
+        let new = format!("{}b", "\n".repeat(9999));
+        let diff = line_diff(&old, &new);
+        assert_eq!(diff.lines().count(), 10001);
+        assert!(diff.ends_with(" \n-a\n+b\n"));
+
+        // too many changed lines to compare them all
+        let old = (0..2000).map(|i| format!("{}\n", i)).collect::<String>();
+        let new = (0..2000)
+            .rev()
+            .map(|i| format!("{}\n", i))
+            .collect::<String>();
+        let diff = line_diff(&old, &new);
+        let removed = diff
+            .lines()
+            .take_while(|line| line.starts_with('-'))
+            .count();
+        assert_eq!(removed, 2000);
+        assert_eq!(
+            diff.lines().filter(|line| line.starts_with('+')).count(),
+            2000
+        );
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "db-test")]
+mod test_psql_repo {
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::repositories::test_db::TestDb;
+    use crate::repositories::todo::{CreateTodo, TodoRepository, UpdateTodo};
+
+    #[tokio::test]
+    async fn diff_stored_revisions() {
+        let db = TestDb::new().await;
+        let key = "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY="
+            .parse()
+            .unwrap();
+        let repo = db.todo_repo().with_encryption(Some(&key));
+        let todo = repo
+            .create(CreateTodo::builder("buy milk").description("whole").build())
+            .await
+            .unwrap();
+        repo.update(
+            todo.id,
+            UpdateTodo::builder()
+                .text("buy oat milk")
+                .completed(true)
+                .build(),
+        )
+        .await
+        .unwrap();
+        let app = routes(repo);
+        let get = |uri: String| {
+            let app = app.clone();
+            async move {
+                let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+                let res = app.oneshot(req).await.unwrap();
+                let status = res.status();
+                let body = axum::body::to_bytes(res.into_body(), 100_000)
+                    .await
+                    .unwrap();
+                (status, serde_json::from_slice(&body).unwrap_or_default())
+            }
+        };
+
+        let (status, revisions): (_, serde_json::Value) =
+            get(format!("/todos/{}/revisions", todo.id)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(revisions[0]["kind"], "created");
+        assert_eq!(revisions[1]["todo"]["text"], "buy oat milk");
+
+        let (status, diff) = get(format!("/todos/{}/revisions/1/diff/2", todo.id)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            diff,
+            serde_json::json!({
+                "from": 1,
+                "to": 2,
+                "changes": [
+                    {"field": "completed", "from": false, "to": true},
+                    {"field": "text", "from": "buy milk", "to": "buy oat milk"},
+                ],
+                "description_diff": null,
+            })
+        );
+
+        let (status, _) = get(format!("/todos/{}/revisions/1/diff/3", todo.id)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = get(format!("/todos/{}/revisions/0/diff/1", todo.id)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = get("/todos/999/revisions".to_string()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
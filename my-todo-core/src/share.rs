@@ -0,0 +1,583 @@
+use std::fmt::Write;
+
+use axum::extract::{Path, Query};
+use axum::http::header::{CACHE_CONTROL, REFERRER_POLICY};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Extension, Json, Router};
+use chrono::{DateTime, Utc};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::handlers::pagination::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use crate::handlers::{error_status, PathId};
+use crate::repositories::label::{LabelRepository, LabelRepositoryForDb};
+use crate::repositories::share_link::{self, ShareTarget};
+use crate::repositories::todo::{
+    Priority, TodoEntity, TodoQuery, TodoRepository, TodoRepositoryForDb,
+};
+use crate::ui::escape;
+
+/// A new share link. The token is only ever returned here.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub token: String,
+    /// `/shared/{token}`
+    pub path: String,
+}
+
+/// What `GET /shared/:token` shows: no ids, only what a reader outside needs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedTodos {
+    /// The name of a shared label, `None` for a single todo.
+    pub label: Option<String>,
+    pub todos: Vec<SharedTodo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedTodo {
+    pub text: String,
+    pub description: Option<String>,
+    pub completed: bool,
+    pub due_at: Option<DateTime<Utc>>,
+    pub priority: Option<Priority>,
+    pub labels: Vec<String>,
+}
+
+impl From<TodoEntity> for SharedTodo {
+    fn from(todo: TodoEntity) -> Self {
+        SharedTodo {
+            text: todo.text,
+            description: todo.description,
+            completed: todo.completed,
+            due_at: todo.due_at,
+            priority: todo.priority,
+            labels: todo.labels.into_iter().map(|label| label.name).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SharedFormat {
+    #[default]
+    Json,
+    Html,
+}
+
+#[derive(Debug, Deserialize)]
+struct SharedQuery {
+    #[serde(default)]
+    format: SharedFormat,
+}
+
+#[derive(Clone)]
+struct Share {
+    pool: PgPool,
+    todo_repo: TodoRepositoryForDb,
+    label_repo: LabelRepositoryForDb,
+}
+
+/// Read-only links to share a todo, or the list of todos with a label, with people outside.
+/// `POST /todos/:id/share-link` and `POST /label/:id/share-link` create one, the matching
+/// `DELETE` revokes every link to the todo or label, and `GET /shared/:token` opens one
+/// without any credentials, `format=html` as a page.
+///
+/// Embed tokens are share links to a listing filter instead, for iframes and the like:
+/// `POST /embed/tokens` issues one, `DELETE /embed/tokens/:token` revokes it and
+/// `GET /embed/todos?filter=…&token=…` shows the filter's todos, as a page unless
+/// `format=json`. At most [`MAX_PAGE_SIZE`] of them, the first [`DEFAULT_PAGE_SIZE`] when the
+/// filter has no `limit`.
+pub fn routes(
+    pool: PgPool,
+    todo_repo: TodoRepositoryForDb,
+    label_repo: LabelRepositoryForDb,
+) -> Router {
+    Router::new()
+        .route(
+            "/todos/:id/share-link",
+            post(share_todo).delete(revoke_todo),
+        )
+        .route(
+            "/label/:id/share-link",
+            post(share_label).delete(revoke_label),
+        )
+        .route("/shared/:token", get(shared))
+        .route("/embed/tokens", post(create_embed_token))
+        .route("/embed/tokens/:token", delete(revoke_embed_token))
+        .route("/embed/todos", get(embedded_todos))
+        .layer(Extension(Share {
+            pool,
+            todo_repo,
+            label_repo,
+        }))
+}
+
+async fn share_todo(
+    Extension(share): Extension<Share>,
+    Path(id): Path<PathId>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let id = id.todo(&share.todo_repo).await?;
+    create(&share.pool, &ShareTarget::Todo(id)).await
+}
+
+async fn share_label(
+    Extension(share): Extension<Share>,
+    Path(id): Path<PathId>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let id = id.label(&share.label_repo).await?;
+    create(&share.pool, &ShareTarget::Label(id)).await
+}
+
+async fn create(pool: &PgPool, target: &ShareTarget) -> Result<impl IntoResponse, StatusCode> {
+    let token = share_link::create(pool, target)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    let path = format!("/shared/{}", token);
+    Ok((StatusCode::CREATED, Json(ShareLink { token, path })))
+}
+
+async fn revoke_todo(
+    Extension(share): Extension<Share>,
+    Path(id): Path<PathId>,
+) -> Result<StatusCode, StatusCode> {
+    let id = id.todo(&share.todo_repo).await?;
+    revoke(&share.pool, &ShareTarget::Todo(id)).await
+}
+
+async fn revoke_label(
+    Extension(share): Extension<Share>,
+    Path(id): Path<PathId>,
+) -> Result<StatusCode, StatusCode> {
+    let id = id.label(&share.label_repo).await?;
+    revoke(&share.pool, &ShareTarget::Label(id)).await
+}
+
+/// No content whether there were links or not, revoking is idempotent.
+async fn revoke(pool: &PgPool, target: &ShareTarget) -> Result<StatusCode, StatusCode> {
+    share_link::revoke(pool, target)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+async fn shared(
+    Extension(share): Extension<Share>,
+    Path(token): Path<String>,
+    Query(query): Query<SharedQuery>,
+) -> Result<Response, StatusCode> {
+    let target = find(&share, &token).await?;
+    let shared = open(&share, target).await?;
+    Ok(respond(shared, query.format))
+}
+
+/// `POST /embed/tokens`, a token for the todos a `GET /todos` query string selects.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateEmbedToken {
+    /// e.g. `completed=false&label_id=3`
+    pub filter: String,
+}
+
+/// A new embed token and the URL to put in an iframe.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbedToken {
+    pub token: String,
+    /// `/embed/todos?filter=…&token=…`
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbedQuery {
+    token: String,
+    /// When given, it has to select what the token's filter does.
+    filter: Option<String>,
+    #[serde(default = "EmbedQuery::default_format")]
+    format: SharedFormat,
+}
+
+impl EmbedQuery {
+    /// Embeds are mostly iframes.
+    fn default_format() -> SharedFormat {
+        SharedFormat::Html
+    }
+}
+
+/// The [`TodoQuery`] of a query string, without its leading `?`.
+fn parse_filter(filter: &str) -> Option<TodoQuery> {
+    let uri = format!("/?{}", filter.trim_start_matches('?'))
+        .parse()
+        .ok()?;
+    Query::<TodoQuery>::try_from_uri(&uri)
+        .ok()
+        .map(|Query(query)| query)
+}
+
+async fn create_embed_token(
+    Extension(share): Extension<Share>,
+    Json(payload): Json<CreateEmbedToken>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let filter = payload.filter.trim_start_matches('?').to_string();
+    parse_filter(&filter).ok_or(StatusCode::BAD_REQUEST)?;
+    let token = share_link::create(&share.pool, &ShareTarget::Filter(filter.clone()))
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    let path = format!(
+        "/embed/todos?filter={}&token={}",
+        utf8_percent_encode(&filter, NON_ALPHANUMERIC),
+        token
+    );
+    Ok((StatusCode::CREATED, Json(EmbedToken { token, path })))
+}
+
+async fn revoke_embed_token(
+    Extension(share): Extension<Share>,
+    Path(token): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    share_link::revoke_token(&share.pool, &token)
+        .await
+        .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?
+        .then_some(StatusCode::NO_CONTENT)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// `GET /embed/todos?filter=…&token=…`: the todos of the token's filter. Any other filter is
+/// forbidden, so the token can't be used to read more than it was issued for.
+async fn embedded_todos(
+    Extension(share): Extension<Share>,
+    Query(query): Query<EmbedQuery>,
+) -> Result<Response, StatusCode> {
+    let target = find(&share, &query.token).await?;
+    let ShareTarget::Filter(filter) = &target else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    if let Some(requested) = &query.filter {
+        if parse_filter(requested) != parse_filter(filter) {
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+    let shared = open(&share, target).await?;
+    Ok(respond(shared, query.format))
+}
+
+fn internal(e: anyhow::Error) -> StatusCode {
+    tracing::error!("failed to open a share link: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+async fn find(share: &Share, token: &str) -> Result<ShareTarget, StatusCode> {
+    share_link::find(&share.pool, token)
+        .await
+        .map_err(internal)?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn open(share: &Share, target: ShareTarget) -> Result<SharedTodos, StatusCode> {
+    Ok(match target {
+        ShareTarget::Todo(id) => {
+            let todo = share
+                .todo_repo
+                .find(id)
+                .await
+                .map_err(|e| error_status(&e, StatusCode::INTERNAL_SERVER_ERROR))?;
+            SharedTodos {
+                label: None,
+                todos: vec![todo.into()],
+            }
+        }
+        ShareTarget::Label(id) => {
+            let label = share
+                .label_repo
+                .all()
+                .await
+                .map_err(internal)?
+                .into_iter()
+                .find(|label| label.id == id)
+                .ok_or(StatusCode::NOT_FOUND)?;
+            let todos = share
+                .todo_repo
+                .all(TodoQuery {
+                    label_id: Some(id),
+                    ..TodoQuery::default()
+                })
+                .await
+                .map_err(internal)?;
+            SharedTodos {
+                label: Some(label.name),
+                todos: todos.into_iter().map(SharedTodo::from).collect(),
+            }
+        }
+        ShareTarget::Filter(filter) => {
+            // checked when the token was issued
+            let query = parse_filter(&filter).ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+            let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+            let todos = share
+                .todo_repo
+                .all(TodoQuery {
+                    limit: Some(limit),
+                    ..query
+                })
+                .await
+                .map_err(internal)?;
+            SharedTodos {
+                label: None,
+                todos: todos.into_iter().map(SharedTodo::from).collect(),
+            }
+        }
+    })
+}
+
+fn respond(shared: SharedTodos, format: SharedFormat) -> Response {
+    // a revoked link must stop working everywhere, and the token not leak through links
+    let headers = [
+        (CACHE_CONTROL, "no-store"),
+        (REFERRER_POLICY, "no-referrer"),
+    ];
+    match format {
+        SharedFormat::Json => (headers, Json(shared)).into_response(),
+        SharedFormat::Html => (headers, Html(render(&shared))).into_response(),
+    }
+}
+
+fn render(shared: &SharedTodos) -> String {
+    let title = match (&shared.label, shared.todos.as_slice()) {
+        (Some(label), _) => escape(label),
+        (None, [todo]) => escape(&todo.text),
+        (None, _) => "Todos".to_string(),
+    };
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><meta name=\"robots\" content=\"noindex\"><title>{title}</title></head>\n<body>\n<h1>{title}</h1>\n<ul>\n"
+    );
+    for todo in &shared.todos {
+        let _ = write!(
+            page,
+            "<li>{}{}",
+            if todo.completed {
+                "&#x2611; "
+            } else {
+                "&#x2610; "
+            },
+            escape(&todo.text)
+        );
+        if let Some(due_at) = todo.due_at {
+            let _ = write!(
+                page,
+                " <small>due {}</small>",
+                due_at.format("%Y-%m-%d %H:%M UTC")
+            );
+        }
+        if let Some(description) = &todo.description {
+            let _ = write!(page, "<p>{}</p>", escape(description));
+        }
+        page.push_str("</li>\n");
+    }
+    page.push_str("</ul>\n</body>\n</html>\n");
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_escapes() {
+        let shared = SharedTodos {
+            label: Some("<groceries>".to_string()),
+            todos: vec![SharedTodo {
+                text: "milk & eggs".to_string(),
+                description: None,
+                completed: true,
+                due_at: Some("2030-06-03T09:00:00Z".parse().unwrap()),
+                priority: None,
+                labels: vec!["<groceries>".to_string()],
+            }],
+        };
+        let page = render(&shared);
+        assert!(page.contains("<h1>&lt;groceries&gt;</h1>"));
+        assert!(page
+            .contains("<li>&#x2611; milk &amp; eggs <small>due 2030-06-03 09:00 UTC</small></li>"));
+    }
+
+    #[test]
+    fn parse_filters() {
+        let query = parse_filter("?completed=false&label_id=3").unwrap();
+        assert_eq!(query.completed, Some(false));
+        assert_eq!(query.label_id, Some(3));
+        assert_eq!(
+            parse_filter("label_id=3&completed=false"),
+            Some(query.clone())
+        );
+        assert_eq!(parse_filter(""), Some(TodoQuery::default()));
+        assert_eq!(parse_filter("label_id=groceries"), None);
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "db-test")]
+mod test_psql_repo {
+    use axum::body::Body;
+    use axum::http::{Method, Request};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::repositories::label::CreateLabel;
+    use crate::repositories::test_db::TestDb;
+    use crate::repositories::todo::{CreateTodo, UpdateTodo};
+
+    async fn send(app: &Router, method: Method, uri: &str) -> (StatusCode, Vec<u8>) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let status = res.status();
+        let body = axum::body::to_bytes(res.into_body(), 100_000)
+            .await
+            .unwrap();
+        (status, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn share_and_revoke() {
+        let db = TestDb::new().await;
+        let (todo_repo, label_repo) = (db.todo_repo(), db.label_repo());
+        let label = label_repo
+            .create(CreateLabel {
+                name: "groceries".to_string(),
+            })
+            .await
+            .unwrap();
+        let milk = todo_repo
+            .create(CreateTodo::builder("milk").labels(vec![label.id]).build())
+            .await
+            .unwrap();
+        todo_repo
+            .create(CreateTodo::builder("eggs").labels(vec![label.id]).build())
+            .await
+            .unwrap();
+        let app = routes(db.pool.clone(), todo_repo, label_repo);
+
+        let (status, body) = send(
+            &app,
+            Method::POST,
+            &format!("/todos/{}/share-link", milk.id),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let todo_link: ShareLink = serde_json::from_slice(&body).unwrap();
+        let uri = format!("/label/{}/share-link", label.public_id);
+        let (status, body) = send(&app, Method::POST, &uri).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let label_link: ShareLink = serde_json::from_slice(&body).unwrap();
+        assert_ne!(todo_link.token, label_link.token);
+
+        let (status, body) = send(&app, Method::GET, &todo_link.path).await;
+        assert_eq!(status, StatusCode::OK);
+        let shared: SharedTodos = serde_json::from_slice(&body).unwrap();
+        assert_eq!(shared.label, None);
+        assert_eq!(shared.todos[0].text, "milk");
+        assert_eq!(shared.todos[0].labels, vec!["groceries"]);
+        let (status, body) = send(&app, Method::GET, &label_link.path).await;
+        assert_eq!(status, StatusCode::OK);
+        let shared: SharedTodos = serde_json::from_slice(&body).unwrap();
+        assert_eq!(shared.label.as_deref(), Some("groceries"));
+        assert_eq!(shared.todos.len(), 2);
+        let (status, body) = send(
+            &app,
+            Method::GET,
+            &format!("{}?format=html", label_link.path),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(String::from_utf8(body)
+            .unwrap()
+            .contains("<h1>groceries</h1>"));
+
+        let (status, _) = send(
+            &app,
+            Method::DELETE,
+            &format!("/todos/{}/share-link", milk.id),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&app, Method::GET, &todo_link.path).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&app, Method::GET, &label_link.path).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = send(&app, Method::POST, "/todos/999/share-link").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&app, Method::GET, "/shared/unknown").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn embed_a_filter() {
+        let db = TestDb::new().await;
+        let todo_repo = db.todo_repo();
+        for text in ["open", "done"] {
+            let todo = todo_repo
+                .create(CreateTodo::builder(text).build())
+                .await
+                .unwrap();
+            if text == "done" {
+                todo_repo
+                    .update(todo.id, UpdateTodo::builder().completed(true).build())
+                    .await
+                    .unwrap();
+            }
+        }
+        let app = routes(db.pool.clone(), todo_repo, db.label_repo());
+        let create = |filter: &str| {
+            let req = Request::builder()
+                .method(Method::POST)
+                .uri("/embed/tokens")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "filter": filter }).to_string(),
+                ))
+                .unwrap();
+            app.clone().oneshot(req)
+        };
+
+        let res = create("label_id=groceries").await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let res = create("completed=false").await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(res.into_body(), 10_000).await.unwrap();
+        let embed: EmbedToken = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            embed.path,
+            format!(
+                "/embed/todos?filter=completed%3Dfalse&token={}",
+                embed.token
+            )
+        );
+
+        let (status, body) = send(&app, Method::GET, &embed.path).await;
+        assert_eq!(status, StatusCode::OK);
+        let page = String::from_utf8(body).unwrap();
+        assert!(page.contains("<h1>open</h1>"));
+        assert!(!page.contains("done"));
+        let uri = format!("/embed/todos?token={}&format=json", embed.token);
+        let (status, body) = send(&app, Method::GET, &uri).await;
+        assert_eq!(status, StatusCode::OK);
+        let shared: SharedTodos = serde_json::from_slice(&body).unwrap();
+        assert_eq!(shared.todos.len(), 1);
+
+        // the token doesn't open any other filter
+        let uri = format!("/embed/todos?filter=completed%3Dtrue&token={}", embed.token);
+        let (status, _) = send(&app, Method::GET, &uri).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let uri = format!("/embed/tokens/{}", embed.token);
+        let (status, _) = send(&app, Method::DELETE, &uri).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&app, Method::GET, &embed.path).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&app, Method::DELETE, &uri).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::config::SloConfig;
+
+/// The window is kept as this many slots, the oldest dropped as a new one starts.
+const SLOTS: u64 = 60;
+
+/// Upper bounds of the latency histogram, in milliseconds. Slower requests land in one more
+/// bucket past the last.
+const LATENCY_BOUNDS_MS: [u64; 11] = [5, 10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000];
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Slot {
+    /// Which slot since the tracker started this one counts, older ones are stale.
+    epoch: u64,
+    requests: u64,
+    errors: u64,
+    /// Requests answered within the latency target.
+    fast: u64,
+    max_ms: u64,
+    latencies: [u64; LATENCY_BOUNDS_MS.len() + 1],
+}
+
+impl Slot {
+    fn add(&mut self, other: &Slot) {
+        self.requests += other.requests;
+        self.errors += other.errors;
+        self.fast += other.fast;
+        self.max_ms = self.max_ms.max(other.max_ms);
+        for (count, other) in self.latencies.iter_mut().zip(other.latencies) {
+            *count += other;
+        }
+    }
+
+    /// The latency `quantile` of the requests are faster than, to the histogram's precision.
+    fn percentile(&self, quantile: f64) -> u64 {
+        let rank = ((self.requests as f64 * quantile).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, count) in self.latencies.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let bound = LATENCY_BOUNDS_MS.get(i).copied().unwrap_or(u64::MAX);
+                return bound.min(self.max_ms);
+            }
+        }
+        self.max_ms
+    }
+}
+
+/// Success rate and latency of each route over the last `SLO_WINDOW_MINUTES`, kept in
+/// memory so `GET /admin/slo` works without a Prometheus server. Requests answered with a
+/// 5xx count as errors, those refused for the client's own fault don't.
+#[derive(Debug)]
+pub struct SloTracker {
+    config: SloConfig,
+    started: Instant,
+    routes: Mutex<HashMap<(String, String), Vec<Slot>>>,
+}
+
+impl SloTracker {
+    pub fn new(config: SloConfig) -> Self {
+        SloTracker {
+            config,
+            started: Instant::now(),
+            routes: Mutex::default(),
+        }
+    }
+
+    fn epoch(&self, now: Instant) -> u64 {
+        let slot = self.config.window.as_nanos() / SLOTS as u128;
+        (now.saturating_duration_since(self.started).as_nanos() / slot.max(1)) as u64
+    }
+
+    fn record(&self, method: String, path: String, failed: bool, latency: Duration, now: Instant) {
+        let epoch = self.epoch(now);
+        let ms = latency.as_millis() as u64;
+        let mut routes = self.routes.lock().unwrap();
+        let slots = routes
+            .entry((method, path))
+            .or_insert_with(|| vec![Slot::default(); SLOTS as usize]);
+        let slot = &mut slots[(epoch % SLOTS) as usize];
+        if slot.epoch != epoch {
+            *slot = Slot {
+                epoch,
+                ..Slot::default()
+            };
+        }
+        slot.requests += 1;
+        slot.errors += u64::from(failed);
+        slot.fast += u64::from(latency <= self.config.latency_target);
+        slot.max_ms = slot.max_ms.max(ms);
+        let bucket = LATENCY_BOUNDS_MS
+            .iter()
+            .position(|bound| ms <= *bound)
+            .unwrap_or(LATENCY_BOUNDS_MS.len());
+        slot.latencies[bucket] += 1;
+    }
+
+    fn summary(&self, now: Instant) -> SloSummary {
+        let epoch = self.epoch(now);
+        let target = self.config.target_percent / 100.0;
+        let mut routes = self
+            .routes
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|((method, path), slots)| {
+                let mut total = Slot::default();
+                slots
+                    .iter()
+                    .filter(|slot| slot.epoch + SLOTS > epoch)
+                    .for_each(|slot| total.add(slot));
+                (total.requests > 0).then(|| {
+                    let error_rate = total.errors as f64 / total.requests as f64;
+                    RouteSlo {
+                        method: method.clone(),
+                        path: path.clone(),
+                        requests: total.requests,
+                        errors: total.errors,
+                        success_percent: 100.0 * (1.0 - error_rate),
+                        within_latency_target_percent: 100.0 * total.fast as f64
+                            / total.requests as f64,
+                        p50_ms: total.percentile(0.5),
+                        p90_ms: total.percentile(0.9),
+                        p99_ms: total.percentile(0.99),
+                        error_budget_remaining_percent: 100.0 * (1.0 - error_rate / (1.0 - target)),
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        routes.sort_by(|a, b| (&a.path, &a.method).cmp(&(&b.path, &b.method)));
+        SloSummary {
+            window_secs: self.config.window.as_secs(),
+            target_percent: self.config.target_percent,
+            latency_target_ms: self.config.latency_target.as_millis() as u64,
+            routes,
+        }
+    }
+}
+
+/// What `GET /admin/slo` reports.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SloSummary {
+    pub window_secs: u64,
+    pub target_percent: f64,
+    pub latency_target_ms: u64,
+    /// Only routes requested within the window.
+    pub routes: Vec<RouteSlo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteSlo {
+    pub method: String,
+    /// The matched route, e.g. `/todos/:id`.
+    pub path: String,
+    pub requests: u64,
+    pub errors: u64,
+    pub success_percent: f64,
+    pub within_latency_target_percent: f64,
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+    /// What is left of the errors the target allows, negative once it's overspent.
+    pub error_budget_remaining_percent: f64,
+}
+
+/// Record every request to the routes of `router` with `tracker`.
+pub fn track(router: Router, tracker: Arc<SloTracker>) -> Router {
+    router.layer(middleware::from_fn_with_state(tracker, track_request))
+}
+
+async fn track_request(
+    State(tracker): State<Arc<SloTracker>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = req.method().to_string();
+    let started = Instant::now();
+
+    let res = next.run(req).await;
+
+    let failed = res.status().is_server_error();
+    tracker.record(method, path, failed, started.elapsed(), Instant::now());
+    res
+}
+
+/// `GET /slo`, mounted under `/admin`.
+pub fn routes(tracker: Arc<SloTracker>) -> Router {
+    Router::new()
+        .route("/slo", get(slo))
+        .layer(Extension(tracker))
+}
+
+async fn slo(Extension(tracker): Extension<Arc<SloTracker>>) -> Json<SloSummary> {
+    Json(tracker.summary(Instant::now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn tracker() -> SloTracker {
+        SloTracker::new(SloConfig {
+            window: Duration::from_secs(60),
+            target_percent: 99.0,
+            latency_target: Duration::from_millis(100),
+        })
+    }
+
+    #[test]
+    fn summarize_the_window() {
+        let tracker = tracker();
+        let started = tracker.started;
+        let record = |failed, ms, at| {
+            tracker.record(
+                "GET".to_string(),
+                "/todos".to_string(),
+                failed,
+                Duration::from_millis(ms),
+                started + Duration::from_secs(at),
+            )
+        };
+        // pushed out of the window by the time of the summary
+        record(true, 9_000, 0);
+        for _ in 0..197 {
+            record(false, 20, 30);
+        }
+        record(false, 300, 40);
+        record(true, 42_000, 50);
+        record(false, 80, 61);
+
+        let summary = tracker.summary(started + Duration::from_secs(61));
+        assert_eq!(summary.routes.len(), 1);
+        let route = &summary.routes[0];
+        assert_eq!((route.requests, route.errors), (200, 1));
+        assert_eq!(route.success_percent, 99.5);
+        assert_eq!(route.within_latency_target_percent, 99.0);
+        assert_eq!((route.p50_ms, route.p90_ms, route.p99_ms), (25, 25, 100));
+        assert_eq!(route.error_budget_remaining_percent.round(), 50.0);
+
+        let summary = tracker.summary(started + Duration::from_secs(200));
+        assert!(summary.routes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn count_server_errors_by_route() {
+        let tracker = Arc::new(tracker());
+        let router = Router::new()
+            .route("/todos/:id", get(|| async { StatusCode::NOT_FOUND }))
+            .route(
+                "/label",
+                get(|| async { StatusCode::INTERNAL_SERVER_ERROR }),
+            );
+        let app = track(router, tracker.clone()).merge(routes(tracker.clone()));
+        for uri in ["/todos/1", "/todos/2", "/label"] {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            app.clone().oneshot(req).await.unwrap();
+        }
+
+        let req = Request::builder().uri("/slo").body(Body::empty()).unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let summary: SloSummary = serde_json::from_slice(&body).unwrap();
+        let counts = summary
+            .routes
+            .iter()
+            .map(|route| (route.path.as_str(), route.requests, route.errors))
+            .collect::<Vec<_>>();
+        assert_eq!(counts, [("/label", 1, 1), ("/todos/:id", 2, 0)]);
+    }
+}
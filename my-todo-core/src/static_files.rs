@@ -0,0 +1,177 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use axum::extract::Request;
+use axum::http::header::{CACHE_CONTROL, CONTENT_TYPE};
+use axum::http::{Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use percent_encoding::percent_decode_str;
+
+const INDEX: &str = "index.html";
+
+/// Serve the built frontend in `dir` for every request no route matched.
+///
+/// Paths without a file extension that don't exist get `index.html`, so client-side routes
+/// survive a reload; missing assets stay 404.
+pub fn serve(router: Router, dir: PathBuf) -> Router {
+    let dir = Arc::new(dir);
+    router.fallback(move |req: Request| serve_file(dir.clone(), req))
+}
+
+async fn serve_file(dir: Arc<PathBuf>, req: Request) -> Response {
+    if !matches!(*req.method(), Method::GET | Method::HEAD) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let Some(relative) = relative_path(req.uri().path()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let spa_route = relative.extension().is_none();
+    let path = if relative.as_os_str().is_empty() {
+        dir.join(INDEX)
+    } else {
+        dir.join(&relative)
+    };
+
+    match read_file(&path).await {
+        Ok(Some(content)) => file_response(&path, content),
+        Ok(None) if spa_route => match read_file(&dir.join(INDEX)).await {
+            Ok(Some(content)) => file_response(Path::new(INDEX), content),
+            Ok(None) => StatusCode::NOT_FOUND.into_response(),
+            Err(e) => internal_error(e),
+        },
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// The request path relative to the served directory; `None` when it tries to leave it.
+fn relative_path(uri_path: &str) -> Option<PathBuf> {
+    let decoded = percent_decode_str(uri_path).decode_utf8().ok()?;
+    let mut relative = PathBuf::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => return None,
+            _ if segment.contains('\\') || segment.contains('\0') => return None,
+            _ => relative.push(segment),
+        }
+    }
+    Some(relative)
+}
+
+/// `None` when there is no regular file at `path`.
+async fn read_file(path: &Path) -> std::io::Result<Option<Vec<u8>>> {
+    match tokio::fs::metadata(path).await {
+        Ok(metadata) if metadata.is_file() => tokio::fs::read(path).await.map(Some),
+        Ok(_) => Ok(None),
+        Err(e) if e.kind() == ErrorKind::NotFound || e.kind() == ErrorKind::NotADirectory => {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn file_response(path: &Path, content: Vec<u8>) -> Response {
+    let is_index = path.file_name().is_some_and(|name| name == INDEX);
+    // Bundlers put a hash into asset names, but index.html must be re-fetched to pick them up.
+    let cache_control = if is_index {
+        "no-cache"
+    } else {
+        "public, max-age=3600"
+    };
+    (
+        [
+            (CONTENT_TYPE, content_type(path)),
+            (CACHE_CONTROL, cache_control),
+        ],
+        content,
+    )
+        .into_response()
+}
+
+fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "html" => "text/html; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "json" | "map" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "wasm" => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
+
+fn internal_error(e: std::io::Error) -> Response {
+    tracing::error!("serving static file failed: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use axum::body::Body;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[test]
+    fn reject_traversal() {
+        assert_eq!(
+            relative_path("/assets/app.js"),
+            Some(PathBuf::from("assets/app.js"))
+        );
+        assert_eq!(relative_path("/"), Some(PathBuf::new()));
+        assert_eq!(relative_path("/../etc/passwd"), None);
+        assert_eq!(relative_path("/assets/%2e%2e/%2e%2e/etc/passwd"), None);
+    }
+
+    #[tokio::test]
+    async fn spa_fallback() {
+        let dir = std::env::temp_dir().join(format!("my-todo-static-{}", std::process::id()));
+        fs::create_dir_all(dir.join("assets")).unwrap();
+        fs::write(dir.join(INDEX), "<html></html>").unwrap();
+        fs::write(dir.join("assets/app.js"), "console.log(1)").unwrap();
+        let app = serve(
+            Router::new().route("/api", get(|| async { "api" })),
+            dir.clone(),
+        );
+        let get = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        let res = app.clone().oneshot(get("/api")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = app.clone().oneshot(get("/assets/app.js")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers()[CONTENT_TYPE],
+            "text/javascript; charset=utf-8"
+        );
+
+        // a client-side route
+        let res = app.clone().oneshot(get("/todos/3/edit")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+
+        let res = app.oneshot(get("/assets/missing.js")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::{MatchedPath, Request};
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use axum::routing::get;
+use axum::Router;
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use sqlx::PgPool;
+
+use crate::health::Health;
+
+/// Install the global Prometheus recorder. Must be called once per process.
+pub fn install_recorder() -> PrometheusHandle {
+    PrometheusBuilder::new()
+        .install_recorder()
+        .expect("failed to install Prometheus recorder")
+}
+
+/// Serve the Prometheus text exposition at `GET /metrics` and count every request
+/// (`http_requests_total`, `http_request_duration_seconds`) by method, route and status.
+pub fn instrument(router: Router, handle: PrometheusHandle) -> Router {
+    track(metrics_route(router, handle))
+}
+
+/// Serve the Prometheus text exposition at `GET /metrics`.
+pub fn metrics_route(router: Router, handle: PrometheusHandle) -> Router {
+    router.route("/metrics", get(move || std::future::ready(handle.render())))
+}
+
+/// Count every request (`http_requests_total`, `http_request_duration_seconds`) by method,
+/// route and status.
+pub fn track(router: Router) -> Router {
+    router.layer(middleware::from_fn(track_http))
+}
+
+async fn track_http(req: Request, next: Next) -> Response {
+    // The matched route keeps the label cardinality bounded, e.g. `/todos/:id`.
+    let path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = req.method().to_string();
+    let started = Instant::now();
+
+    let res = next.run(req).await;
+
+    let labels = [
+        ("method", method),
+        ("path", path),
+        ("status", res.status().as_u16().to_string()),
+    ];
+    metrics::counter!("http_requests_total", &labels).increment(1);
+    metrics::histogram!("http_request_duration_seconds", &labels)
+        .record(started.elapsed().as_secs_f64());
+    res
+}
+
+/// Recount todos and labels every `interval` and publish them as gauges (`todos_open`,
+/// `todos_completed`, `labels`), so dashboards don't have to query the database.
+///
+/// `todos_created_total` and `todos_completed_total` are counted as the changes are made,
+/// see [`crate::repositories::todo::TodoRepositoryForDb`].
+pub async fn report_counts(pool: PgPool, health: Arc<Health>, interval: Duration) {
+    const WORKER: &str = "metrics";
+    health.register_worker(WORKER, interval);
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        health.tick(WORKER);
+        if let Err(e) = record_counts(&pool).await {
+            tracing::warn!("counting todos for metrics failed: {}", e);
+        }
+    }
+}
+
+async fn record_counts(pool: &PgPool) -> sqlx::Result<()> {
+    let (open, completed, labels) = sqlx::query_as::<_, (i64, i64, i64)>(
+        r#"
+        select count(*) filter (where not completed),
+               count(*) filter (where completed),
+               (select count(*) from labels)
+        from todos
+        "#,
+    )
+    .fetch_one(pool)
+    .await?;
+    metrics::gauge!("todos_open").set(open as f64);
+    metrics::gauge!("todos_completed").set(completed as f64);
+    metrics::gauge!("labels").set(labels as f64);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[test]
+    fn metrics_endpoint_renders_http_metrics() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let app = instrument(
+            Router::new().route("/todos/:id", get(|| async { "ok" })),
+            recorder.handle(),
+        );
+        // the recorder is only set for this thread, so run the requests on it
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let body = metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                let req = Request::builder()
+                    .uri("/todos/1")
+                    .body(Body::empty())
+                    .unwrap();
+                app.clone().oneshot(req).await.unwrap();
+
+                let req = Request::builder()
+                    .uri("/metrics")
+                    .body(Body::empty())
+                    .unwrap();
+                let res = app.oneshot(req).await.unwrap();
+                axum::body::to_bytes(res.into_body(), 100_000)
+                    .await
+                    .unwrap()
+            })
+        });
+
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(
+            body.contains(r#"http_requests_total{method="GET",path="/todos/:id",status="200"} 1"#)
+        );
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "db-test")]
+mod test_psql_repo {
+    use metrics_exporter_prometheus::PrometheusBuilder;
+
+    use super::*;
+    use crate::repositories::label::{CreateLabel, LabelRepository};
+    use crate::repositories::test_db::TestDb;
+    use crate::repositories::todo::{CreateTodo, TodoRepository, UpdateTodo};
+
+    #[test]
+    fn business_metrics() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                let db = TestDb::new().await;
+                let todo_repo = db.todo_repo();
+                db.label_repo()
+                    .create(CreateLabel {
+                        name: "home".to_string(),
+                    })
+                    .await
+                    .unwrap();
+                let todo = todo_repo
+                    .create(CreateTodo::builder("water plants").build())
+                    .await
+                    .unwrap();
+                todo_repo
+                    .create(CreateTodo::builder("buy milk").build())
+                    .await
+                    .unwrap();
+                let done = UpdateTodo::builder().completed(true).build();
+                todo_repo.update(todo.id, done.clone()).await.unwrap();
+                // already completed, not counted again
+                todo_repo.update(todo.id, done).await.unwrap();
+                record_counts(&db.pool).await.unwrap();
+            })
+        });
+
+        let body = recorder.handle().render();
+        for line in [
+            "todos_created_total 2",
+            "todos_completed_total 1",
+            "todos_open 1",
+            "todos_completed 1",
+            "labels 1",
+        ] {
+            assert!(body.contains(line), "[{}] missing in\n{}", line, body);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Extension, Json, Router};
+use sqlx::PgPool;
+
+use crate::fixtures::{self, Fixture};
+use crate::repositories::label::LabelRepository;
+use crate::repositories::todo::TodoRepository;
+
+/// `POST /__test__/reset` and `POST /__test__/seed` for end-to-end suites of the frontend, so
+/// every run starts from the same data. Only compiled with the `test-support` feature; never
+/// enable it in production, the endpoints wipe everything without authentication.
+pub fn routes<TR, LR>(pool: PgPool, todo_repo: Arc<TR>, label_repo: Arc<LR>) -> Router
+where
+    TR: TodoRepository,
+    LR: LabelRepository,
+{
+    let test = Router::new()
+        .route("/reset", post(reset))
+        .route("/seed", post(seed::<TR, LR>))
+        .layer(Extension(pool))
+        .layer(Extension(todo_repo))
+        .layer(Extension(label_repo));
+    Router::new().nest("/__test__", test)
+}
+
+/// Delete all todos and labels with their history, share links and ingest hooks, and start
+/// ids from 1 again.
+async fn reset(Extension(pool): Extension<PgPool>) -> Response {
+    match truncate(&pool).await {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => {
+            tracing::error!("resetting the database failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+async fn truncate(pool: &PgPool) -> sqlx::Result<()> {
+    let mut tx = pool.begin().await?;
+    // the history is append-only otherwise; the trigger is back on when the transaction commits
+    sqlx::query(r#"alter table todo_events disable trigger todo_events_append_only"#)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+        truncate todos, labels, todo_labels, todo_events, todo_list_view, todo_nudges, outbox,
+                 share_links, ingest_hooks
+        restart identity
+        "#,
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(r#"alter table todo_events enable trigger todo_events_append_only"#)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+/// Load a [`Fixture`] given as the body, like `my-todo seed`, and return what was created.
+async fn seed<TR: TodoRepository, LR: LabelRepository>(
+    Extension(todo_repo): Extension<Arc<TR>>,
+    Extension(label_repo): Extension<Arc<LR>>,
+    Json(fixture): Json<Fixture>,
+) -> Response {
+    match fixtures::load(&fixture, &todo_repo, &label_repo).await {
+        Ok(loaded) => (StatusCode::CREATED, Json(loaded)).into_response(),
+        Err(e) => (StatusCode::UNPROCESSABLE_ENTITY, format!("{:#}", e)).into_response(),
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "db-test")]
+mod test_psql_repo {
+    use axum::body::Body;
+    use axum::http::header::CONTENT_TYPE;
+    use axum::http::{Method, Request};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::repositories::ingest_hook::{self, IngestMapping};
+    use crate::repositories::share_link::{self, ShareTarget};
+    use crate::repositories::test_db::TestDb;
+    use crate::repositories::todo::TodoQuery;
+
+    fn post(uri: &str, body: &str) -> Request<Body> {
+        Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn seed_then_reset() {
+        let db = TestDb::new().await;
+        let todo_repo = Arc::new(db.todo_repo());
+        let app = routes(
+            db.pool.clone(),
+            todo_repo.clone(),
+            Arc::new(db.label_repo()),
+        );
+        let fixture = r#"{
+            "labels": [{"name": "home"}],
+            "todos": [{"text": "water plants", "labels": ["home"]}]
+        }"#;
+
+        let res = app
+            .clone()
+            .oneshot(post("/__test__/seed", fixture))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let todos = todo_repo.all(TodoQuery::default()).await.unwrap();
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].labels[0].name, "home");
+        // rows referring to todos and labels go too
+        share_link::create(&db.pool, &ShareTarget::Todo(todos[0].id))
+            .await
+            .unwrap();
+        let mapping = IngestMapping {
+            text: "$.title".to_string(),
+            description: None,
+            labels: None,
+        };
+        ingest_hook::create(&db.pool, "alerts", &mapping)
+            .await
+            .unwrap();
+
+        let res = app
+            .clone()
+            .oneshot(post("/__test__/reset", ""))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert!(todo_repo
+            .all(TodoQuery::default())
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(ingest_hook::all(&db.pool).await.unwrap().is_empty());
+        let share_links = sqlx::query_scalar::<_, i64>("select count(*) from share_links")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(share_links, 0);
+
+        // ids start over, so suites can rely on them
+        app.clone()
+            .oneshot(post("/__test__/seed", fixture))
+            .await
+            .unwrap();
+        let todos = todo_repo.all(TodoQuery::default()).await.unwrap();
+        assert_eq!((todos[0].id, todos[0].labels[0].id), (1, 1));
+
+        let res = app
+            .oneshot(post("/__test__/seed", r#"{"todos": [{"text": ""}]}"#))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
@@ -0,0 +1,293 @@
+use std::fmt::Write;
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::header::{CONTENT_LANGUAGE, LOCATION, SET_COOKIE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Form, Router};
+use serde::Deserialize;
+use validator::Validate;
+
+use crate::handlers::error_status;
+use crate::i18n::{Locale, Message};
+use crate::middleware::csrf::{self, constant_time_eq, CSRF_COOKIE};
+use crate::repositories::todo::{CreateTodo, TodoEntity, TodoQuery, TodoRepository, UpdateTodo};
+
+struct Ui<R> {
+    todo_repo: Arc<R>,
+    cookie_secure: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct AddForm {
+    text: String,
+    csrf_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompleteForm {
+    csrf_token: String,
+}
+
+/// Plain HTML pages under `/ui` to list, add and complete todos without any JavaScript.
+///
+/// Forms can't send the `X-CSRF-Token` header, so these routes are merged after
+/// [`csrf::protect`] and instead compare a hidden `csrf_token` field with the cookie.
+pub fn routes<R: TodoRepository>(todo_repo: Arc<R>, cookie_secure: bool) -> Router {
+    let ui = Router::new()
+        .route("/", get(list::<R>))
+        .route("/todos", post(add::<R>))
+        .route("/todos/:id/complete", post(complete::<R>))
+        .with_state(Arc::new(Ui {
+            todo_repo,
+            cookie_secure,
+        }));
+    Router::new().nest("/ui", ui)
+}
+
+async fn list<R: TodoRepository>(State(ui): State<Arc<Ui<R>>>, headers: HeaderMap) -> Response {
+    match csrf::find_cookie(&headers, CSRF_COOKIE) {
+        Some(token) => render_list(&ui, token, None).await,
+        None => {
+            let token = csrf::new_token();
+            let cookie = csrf::token_cookie(&token, ui.cookie_secure);
+            ([(SET_COOKIE, cookie)], render_list(&ui, &token, None).await).into_response()
+        }
+    }
+}
+
+async fn add<R: TodoRepository>(
+    State(ui): State<Arc<Ui<R>>>,
+    headers: HeaderMap,
+    Form(form): Form<AddForm>,
+) -> Response {
+    if !token_matches(&headers, &form.csrf_token) {
+        return forbidden(&headers);
+    }
+    let todo = CreateTodo::builder(form.text.trim()).build();
+    if todo.validate().is_err() {
+        let locale = Locale::from_headers(&headers);
+        let error = Message::TextLength.text(locale);
+        let page = render_list(&ui, &form.csrf_token, Some(error)).await;
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            [(CONTENT_LANGUAGE, locale.tag())],
+            page,
+        )
+            .into_response();
+    }
+    match ui.todo_repo.create(todo).await {
+        Ok(_) => back_to_list(),
+        Err(e) => error_status(&e, StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+    }
+}
+
+async fn complete<R: TodoRepository>(
+    State(ui): State<Arc<Ui<R>>>,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    Form(form): Form<CompleteForm>,
+) -> Response {
+    if !token_matches(&headers, &form.csrf_token) {
+        return forbidden(&headers);
+    }
+    let update = UpdateTodo::builder().completed(true).build();
+    match ui.todo_repo.update(id, update).await {
+        Ok(_) => back_to_list(),
+        Err(e) => error_status(&e, StatusCode::NOT_FOUND).into_response(),
+    }
+}
+
+fn token_matches(headers: &HeaderMap, form_token: &str) -> bool {
+    csrf::find_cookie(headers, CSRF_COOKIE)
+        .is_some_and(|cookie| constant_time_eq(cookie.as_bytes(), form_token.as_bytes()))
+}
+
+fn forbidden(headers: &HeaderMap) -> Response {
+    let locale = Locale::from_headers(headers);
+    (
+        StatusCode::FORBIDDEN,
+        [(CONTENT_LANGUAGE, locale.tag())],
+        Message::CsrfInvalid.text(locale),
+    )
+        .into_response()
+}
+
+/// Post/redirect/get, so reloading the list doesn't resubmit the form.
+fn back_to_list() -> Response {
+    (StatusCode::SEE_OTHER, [(LOCATION, "/ui")]).into_response()
+}
+
+async fn render_list<R: TodoRepository>(ui: &Ui<R>, token: &str, error: Option<&str>) -> Response {
+    match ui.todo_repo.all(TodoQuery::default()).await {
+        Ok(todos) => Html(list_page(&todos, token, error)).into_response(),
+        Err(e) => {
+            tracing::error!("listing todos for the ui failed: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+fn list_page(todos: &[TodoEntity], token: &str, error: Option<&str>) -> String {
+    let token = escape(token);
+    let mut page = String::from(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Todos</title></head>\n<body>\n<h1>Todos</h1>\n",
+    );
+    if let Some(error) = error {
+        let _ = writeln!(page, "<p role=\"alert\">{}</p>", escape(error));
+    }
+    let _ = writeln!(
+        page,
+        "<form method=\"post\" action=\"/ui/todos\">\
+         <input type=\"hidden\" name=\"csrf_token\" value=\"{}\">\
+         <input name=\"text\" maxlength=\"288\" required autofocus> \
+         <button>Add</button></form>",
+        token
+    );
+    page.push_str("<ul>\n");
+    for todo in todos {
+        page.push_str("<li>");
+        if todo.completed {
+            let _ = write!(page, "<s>{}</s>", escape(&todo.text));
+        } else {
+            let _ = write!(
+                page,
+                "{} <form method=\"post\" action=\"/ui/todos/{}/complete\" style=\"display:inline\">\
+                 <input type=\"hidden\" name=\"csrf_token\" value=\"{}\">\
+                 <button>Complete</button></form>",
+                escape(&todo.text),
+                todo.id,
+                token
+            );
+        }
+        for label in &todo.labels {
+            let _ = write!(page, " <small>#{}</small>", escape(&label.name));
+        }
+        page.push_str("</li>\n");
+    }
+    page.push_str("</ul>\n</body>\n</html>\n");
+    page
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::extract::Request;
+    use axum::http::header::{CONTENT_TYPE, COOKIE};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::repositories::todo::test_inmemory_repo::TodoRepositoryMemory;
+
+    fn post_form(uri: &str, cookie: &str, body: &str) -> Request {
+        Request::post(uri)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(COOKIE, cookie)
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn body_string(res: Response) -> String {
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn add_and_complete() {
+        let repo = Arc::new(TodoRepositoryMemory::new());
+        let app = routes(repo.clone(), false);
+
+        let res = app
+            .clone()
+            .oneshot(Request::get("/ui").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let set_cookie = res.headers()[SET_COOKIE].to_str().unwrap().to_string();
+        let cookie = set_cookie.split(';').next().unwrap().to_string();
+        let token = cookie.strip_prefix("csrf_token=").unwrap().to_string();
+
+        let res = app
+            .clone()
+            .oneshot(post_form(
+                "/ui/todos",
+                &cookie,
+                "text=%3Cb%3Epay%3C%2Fb%3E&csrf_token=wrong",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let res = app
+            .clone()
+            .oneshot(post_form(
+                "/ui/todos",
+                &cookie,
+                &format!("text=%3Cb%3Epay%3C%2Fb%3E&csrf_token={}", token),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        let todo = repo.find(1).await.expect("todo not created");
+        assert_eq!(todo.text, "<b>pay</b>");
+
+        let res = app
+            .clone()
+            .oneshot(post_form(
+                "/ui/todos/1/complete",
+                &cookie,
+                &format!("csrf_token={}", token),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        assert!(repo.find(1).await.unwrap().completed);
+
+        let res = app
+            .oneshot(
+                Request::get("/ui")
+                    .header(COOKIE, &cookie)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(res.headers().get(SET_COOKIE).is_none());
+        let page = body_string(res).await;
+        assert!(page.contains("<s>&lt;b&gt;pay&lt;/b&gt;</s>"));
+    }
+
+    #[tokio::test]
+    async fn empty_text_is_rejected() {
+        let app = routes(Arc::new(TodoRepositoryMemory::new()), false);
+        let res = app
+            .oneshot(post_form(
+                "/ui/todos",
+                "csrf_token=abc",
+                "text=+&csrf_token=abc",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body_string(res).await.contains("role=\"alert\""));
+    }
+}
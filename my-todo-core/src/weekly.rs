@@ -0,0 +1,383 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+use axum::extract::Query;
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use chrono::{DateTime, Datelike, NaiveDate, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::repositories::todo_events::{self, TodoChange};
+use crate::ui::escape;
+
+/// An ISO 8601 week like `2024-W22`, Monday to Sunday in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IsoWeek {
+    monday: NaiveDate,
+}
+
+impl IsoWeek {
+    pub fn current() -> Self {
+        let week = Utc::now().iso_week();
+        IsoWeek::new(week.year(), week.week()).expect("the current week exists")
+    }
+
+    pub fn new(year: i32, week: u32) -> Option<Self> {
+        NaiveDate::from_isoywd_opt(year, week, Weekday::Mon).map(|monday| IsoWeek { monday })
+    }
+
+    pub fn starts_at(&self) -> DateTime<Utc> {
+        self.monday.and_time(Default::default()).and_utc()
+    }
+
+    pub fn ends_at(&self) -> DateTime<Utc> {
+        self.starts_at() + chrono::Duration::weeks(1)
+    }
+}
+
+impl FromStr for IsoWeek {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid week [{}], expected e.g. 2024-W22", value);
+        let (year, week) = value.split_once("-W").ok_or_else(invalid)?;
+        let year = year.parse::<i32>().map_err(|_| invalid())?;
+        let week = week.parse::<u32>().map_err(|_| invalid())?;
+        IsoWeek::new(year, week).ok_or_else(invalid)
+    }
+}
+
+impl fmt::Display for IsoWeek {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let week = self.monday.iso_week();
+        write!(f, "{}-W{:02}", week.year(), week.week())
+    }
+}
+
+/// What happened to the todos in a week, see [`routes`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeeklyReport {
+    pub week: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub created: usize,
+    pub completed: usize,
+    /// Open when the week started and still open when it ended.
+    pub carried_over: usize,
+    /// Labels with any activity, by name. A todo counts for the labels it carried at the end
+    /// of the week, or when it was deleted.
+    pub labels: Vec<LabelActivity>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LabelActivity {
+    pub label_id: i32,
+    pub name: String,
+    pub created: usize,
+    pub completed: usize,
+    pub carried_over: usize,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ReportFormat {
+    #[default]
+    Json,
+    /// A page ready to be sent as the body of an email.
+    Html,
+}
+
+#[derive(Debug, Deserialize)]
+struct WeeklyQuery {
+    /// The current week when missing.
+    week: Option<String>,
+    #[serde(default)]
+    format: ReportFormat,
+}
+
+/// `GET /reports/weekly?week=2024-W22` reports the todos created and completed in the week,
+/// those carried over from before it, and the same per label. `format=html` renders it as a
+/// page. Built from the history, so these are served by the database only.
+pub fn routes(pool: PgPool) -> Router {
+    Router::new()
+        .route("/reports/weekly", get(weekly))
+        .layer(Extension(pool))
+}
+
+async fn weekly(
+    Extension(pool): Extension<PgPool>,
+    Query(query): Query<WeeklyQuery>,
+) -> Result<Response, Response> {
+    let week = match query.week {
+        Some(week) => week
+            .parse::<IsoWeek>()
+            .map_err(|message| (StatusCode::BAD_REQUEST, message).into_response())?,
+        None => IsoWeek::current(),
+    };
+    let report = load(&pool, week).await.map_err(|e| {
+        tracing::error!("failed to build the weekly report: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
+    Ok(match query.format {
+        ReportFormat::Json => Json(report).into_response(),
+        ReportFormat::Html => Html(render(&report)).into_response(),
+    })
+}
+
+async fn load(pool: &PgPool, week: IsoWeek) -> anyhow::Result<WeeklyReport> {
+    let history = todo_events::history_until(pool, week.ends_at()).await?;
+    let labels = sqlx::query_as::<_, (i32, String)>(r#"select id, name from labels"#)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .collect();
+    Ok(build(week, history, &labels))
+}
+
+/// Replay `history`, every change up to the end of `week`. Labels deleted since are left out
+/// of the breakdown.
+fn build(
+    week: IsoWeek,
+    history: Vec<(i32, TodoChange, DateTime<Utc>)>,
+    label_names: &BTreeMap<i32, String>,
+) -> WeeklyReport {
+    let starts_at = week.starts_at();
+    // whether each existing todo is completed, as of the change being replayed
+    let mut todos = BTreeMap::<i32, bool>::new();
+    let open = |todos: &BTreeMap<i32, bool>| {
+        todos
+            .iter()
+            .filter(|(_, completed)| !**completed)
+            .map(|(id, _)| *id)
+            .collect::<BTreeSet<i32>>()
+    };
+    let mut label_ids = BTreeMap::<i32, Vec<i32>>::new();
+    let mut open_at_start = None;
+    let mut created = BTreeSet::new();
+    let mut completed = BTreeSet::new();
+
+    for (todo_id, change, occurred_at) in history {
+        let during_week = occurred_at >= starts_at;
+        if during_week && open_at_start.is_none() {
+            open_at_start = Some(open(&todos));
+        }
+        match change {
+            TodoChange::Created(todo) | TodoChange::Updated(todo) => {
+                let was_completed = todos.insert(todo_id, todo.completed);
+                if during_week && was_completed.is_none() {
+                    created.insert(todo_id);
+                }
+                if during_week && todo.completed && was_completed != Some(true) {
+                    completed.insert(todo_id);
+                }
+                label_ids.insert(todo_id, todo.label_ids);
+            }
+            TodoChange::Deleted {} => {
+                todos.remove(&todo_id);
+            }
+            TodoChange::LabelAttached { label_id } => {
+                let ids = label_ids.entry(todo_id).or_default();
+                if !ids.contains(&label_id) {
+                    ids.push(label_id);
+                }
+            }
+            TodoChange::LabelDetached { label_id } => {
+                label_ids
+                    .entry(todo_id)
+                    .or_default()
+                    .retain(|id| *id != label_id);
+            }
+        }
+    }
+    let open_at_end = open(&todos);
+    let carried_over = open_at_start
+        .unwrap_or_else(|| open_at_end.clone())
+        .intersection(&open_at_end)
+        .copied()
+        .collect::<BTreeSet<i32>>();
+
+    let mut labels = BTreeMap::<i32, LabelActivity>::new();
+    let mut count = |ids: &BTreeSet<i32>, field: fn(&mut LabelActivity) -> &mut usize| {
+        for id in ids {
+            for label_id in label_ids.get(id).into_iter().flatten() {
+                let Some(name) = label_names.get(label_id) else {
+                    continue;
+                };
+                let activity = labels.entry(*label_id).or_insert_with(|| LabelActivity {
+                    label_id: *label_id,
+                    name: name.clone(),
+                    created: 0,
+                    completed: 0,
+                    carried_over: 0,
+                });
+                *field(activity) += 1;
+            }
+        }
+    };
+    count(&created, |activity| &mut activity.created);
+    count(&completed, |activity| &mut activity.completed);
+    count(&carried_over, |activity| &mut activity.carried_over);
+    let mut labels = labels.into_values().collect::<Vec<_>>();
+    labels.sort_by(|a, b| a.name.cmp(&b.name).then(a.label_id.cmp(&b.label_id)));
+
+    WeeklyReport {
+        week: week.to_string(),
+        starts_at,
+        ends_at: week.ends_at(),
+        created: created.len(),
+        completed: completed.len(),
+        carried_over: carried_over.len(),
+        labels,
+    }
+}
+
+fn render(report: &WeeklyReport) -> String {
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Week {week}</title></head>\n<body>\n<h1>Week {week}</h1>\n",
+        week = escape(&report.week)
+    );
+    let _ = writeln!(
+        page,
+        "<p>{} created, {} completed, {} carried over.</p>",
+        report.created, report.completed, report.carried_over
+    );
+    if !report.labels.is_empty() {
+        page.push_str(
+            "<table>\n<tr><th>Label</th><th>Created</th><th>Completed</th><th>Carried over</th></tr>\n",
+        );
+        for label in &report.labels {
+            let _ = writeln!(
+                page,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&label.name),
+                label.created,
+                label.completed,
+                label.carried_over
+            );
+        }
+        page.push_str("</table>\n");
+    }
+    page.push_str("</body>\n</html>\n");
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::todo_events::TodoSnapshot;
+
+    fn snapshot(completed: bool, label_ids: Vec<i32>) -> TodoSnapshot {
+        TodoSnapshot {
+            public_id: None,
+            text: "text".to_string(),
+            description: None,
+            completed,
+            due_at: None,
+            priority: None,
+            starred: false,
+            color: None,
+            icon: None,
+            estimate_minutes: None,
+            completed_at: None,
+            archived: false,
+            label_ids,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn parse_iso_weeks() {
+        let week = "2024-W22".parse::<IsoWeek>().unwrap();
+        assert_eq!(week.to_string(), "2024-W22");
+        assert_eq!(week.starts_at().to_rfc3339(), "2024-05-27T00:00:00+00:00");
+        assert_eq!(week.ends_at().to_rfc3339(), "2024-06-03T00:00:00+00:00");
+        // 2020 had 53 weeks, 2021 did not
+        assert!("2020-W53".parse::<IsoWeek>().is_ok());
+        assert!("2021-W53".parse::<IsoWeek>().is_err());
+        assert!("2024-22".parse::<IsoWeek>().is_err());
+        assert!("2024-W0".parse::<IsoWeek>().is_err());
+    }
+
+    #[test]
+    fn build_a_weekly_report() {
+        let week = "2024-W22".parse::<IsoWeek>().unwrap();
+        let before = week.starts_at() - chrono::Duration::days(1);
+        let during = week.starts_at() + chrono::Duration::days(2);
+        let history = vec![
+            // open before and after the week
+            (1, TodoChange::Created(snapshot(false, vec![10])), before),
+            // open before, completed during
+            (2, TodoChange::Created(snapshot(false, vec![])), before),
+            (2, TodoChange::Updated(snapshot(true, vec![])), during),
+            (2, TodoChange::Updated(snapshot(true, vec![])), during),
+            // created during, labelled later
+            (3, TodoChange::Created(snapshot(false, vec![])), during),
+            (3, TodoChange::LabelAttached { label_id: 10 }, during),
+            (3, TodoChange::LabelAttached { label_id: 99 }, during),
+            // created and completed during, then deleted
+            (4, TodoChange::Created(snapshot(true, vec![10])), during),
+            (4, TodoChange::Deleted {}, during),
+        ];
+        let labels = BTreeMap::from([(10, "work".to_string())]);
+        let report = build(week, history, &labels);
+        assert_eq!(
+            report,
+            WeeklyReport {
+                week: "2024-W22".to_string(),
+                starts_at: week.starts_at(),
+                ends_at: week.ends_at(),
+                created: 2,
+                completed: 2,
+                carried_over: 1,
+                labels: vec![LabelActivity {
+                    label_id: 10,
+                    name: "work".to_string(),
+                    created: 2,
+                    completed: 1,
+                    carried_over: 1,
+                }],
+            }
+        );
+        let page = render(&report);
+        assert!(page.contains("<h1>Week 2024-W22</h1>"));
+        assert!(page.contains("<tr><td>work</td><td>2</td><td>1</td><td>1</td></tr>"));
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "db-test")]
+mod test_psql_repo {
+    use super::*;
+    use crate::repositories::test_db::TestDb;
+    use crate::repositories::todo::{CreateTodo, TodoRepository, UpdateTodo};
+
+    #[tokio::test]
+    async fn report_the_current_week() {
+        let db = TestDb::new().await;
+        let repo = db.todo_repo();
+        for text in ["[weekly] open", "[weekly] done"] {
+            repo.create(CreateTodo::builder(text).build())
+                .await
+                .expect("[create] returned Err");
+        }
+        repo.update(2, UpdateTodo::builder().completed(true).build())
+            .await
+            .expect("[update] returned Err");
+
+        let report = load(&db.pool, IsoWeek::current())
+            .await
+            .expect("[load] returned Err");
+        assert_eq!(
+            (report.created, report.completed, report.carried_over),
+            (2, 1, 0)
+        );
+        let last_week = IsoWeek::new(2000, 1).unwrap();
+        let report = load(&db.pool, last_week).await.unwrap();
+        assert_eq!(report.created, 0);
+    }
+}
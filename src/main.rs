@@ -1,316 +1,632 @@
 use std::env;
 use std::net::SocketAddr;
+use std::process::ExitCode;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use axum::extract::Extension;
-use axum::http::HeaderValue;
-use axum::routing::delete;
-use axum::{
-    http,
-    routing::{get, post},
-    Router,
-};
+use axum::http::Method;
+use axum::{Extension, Router};
 use dotenvy::dotenv;
-use http::method::Method;
 use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
-use sqlx::PgPool;
-use tower_http::cors::CorsLayer;
-
-use handlers::label::{all_label, create_label, delete_label};
-use handlers::todo::{create_todo, delete_todo, find_todo, update_todo};
-
-use crate::handlers::todo::all_todo;
-use crate::repositories::label::{LabelRepository, LabelRepositoryForDb};
-use crate::repositories::todo::{TodoRepository, TodoRepositoryForDb};
-
-mod handlers;
-mod repositories;
-
-async fn root() -> &'static str {
-    "Hello, world!"
-}
+use sqlx::{Connection, PgConnection, PgPool};
+use tokio::net::TcpListener;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use my_todo_core::config::{self, AppConfig, CorsConfig, DEFAULT_LOG_LEVEL};
+use my_todo_core::events::nats::NatsPublisher;
+use my_todo_core::handlers::todo;
+use my_todo_core::health::{self, Health};
+use my_todo_core::jobs::Jobs;
+use my_todo_core::middleware::load_shed::{self, PoolPressure};
+use my_todo_core::middleware::read_only::{self, ReadOnlyMode};
+use my_todo_core::middleware::{access_log, concurrency, csrf, dedupe, options, rate_limit};
+use my_todo_core::reload::{self as config_reload, LiveConfig, Reloader};
+use my_todo_core::repositories::cipher::EncryptionKey;
+use my_todo_core::repositories::instrumented::InstrumentedRepository;
+use my_todo_core::repositories::label::LabelRepositoryForDb;
+use my_todo_core::repositories::label_cache::CachedLabelRepository;
+use my_todo_core::repositories::report::ReportRepositoryForDb;
+use my_todo_core::repositories::todo::TodoRepositoryForDb;
+use my_todo_core::repositories::todo_events;
+use my_todo_core::{
+    admin, create_app, diagnostics, events, fixtures, inbound, ingest, nudge, preflight, purge,
+    reports, revisions, share, slo, static_files, telemetry, ui, weekly,
+};
 
-fn create_cors_layer(allow_origins: impl IntoIterator<Item = String>) -> CorsLayer {
-    let allow_origins = allow_origins
-        .into_iter()
-        .map(|origin: String| {
-            origin
-                .parse::<HeaderValue>()
-                .unwrap_or_else(|_| panic!("Invalid client url {}", origin))
-        })
-        .collect::<Vec<HeaderValue>>();
-    CorsLayer::new()
-        .allow_origin(allow_origins)
+/// Allowed origins follow reloads, the other settings are fixed at startup.
+fn create_cors_layer(config: &CorsConfig, live: Arc<LiveConfig>) -> CorsLayer {
+    let layer = CorsLayer::new()
+        .allow_origin(AllowOrigin::predicate(move |origin, _| {
+            live.current()
+                .cors
+                .allow_origins
+                .iter()
+                .any(|matcher| matcher.matches(origin))
+        }))
         .allow_methods(vec![
             Method::GET,
             Method::POST,
+            Method::PUT,
             Method::DELETE,
             Method::PATCH,
         ])
-        .allow_headers(vec![CONTENT_TYPE, AUTHORIZATION])
+        .allow_headers(vec![
+            CONTENT_TYPE,
+            AUTHORIZATION,
+            csrf::CSRF_HEADER.clone(),
+            dedupe::CLIENT_ID_HEADER.clone(),
+        ])
+        .expose_headers(vec![
+            rate_limit::X_RATELIMIT_LIMIT.clone(),
+            rate_limit::X_RATELIMIT_REMAINING.clone(),
+            rate_limit::X_RATELIMIT_RESET.clone(),
+            todo::X_TOTAL_COUNT.clone(),
+            todo::X_TOTAL_COMPLETED.clone(),
+            todo::X_TOTAL_OVERDUE.clone(),
+            todo::X_TRUNCATED.clone(),
+        ])
+        .allow_credentials(config.allow_credentials);
+    match config.max_age {
+        Some(max_age) => layer.max_age(max_age),
+        None => layer,
+    }
 }
 
-fn setup_logging() {
-    let log_level = env::var("RUST_LOG").unwrap_or("info".to_string());
-    env::set_var("RUST_LOG", log_level);
-    tracing_subscriber::fmt::init();
+/// The returned handle swaps the filter when the configuration is reloaded.
+fn setup_logging() -> reload::Handle<EnvFilter, Registry> {
+    let log_level = env::var("RUST_LOG").unwrap_or(DEFAULT_LOG_LEVEL.to_string());
+    let (filter, handle) = reload::Layer::new(EnvFilter::new(log_level));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .init();
+    handle
 }
 
 fn set_dotenv_vars() {
     dotenv().ok();
 }
 
-async fn create_db_conn(db_url: &str) -> PgPool {
-    PgPool::connect(db_url)
-        .await
-        .expect("Can not connect to database")
+/// Why the process stopped. Each cause exits with its own status (from sysexits.h), so
+/// orchestrators and operators can tell a bad deployment from an outage.
+#[derive(Debug)]
+enum Failure {
+    Usage(String),
+    Config(String),
+    Database(String),
+    Bind(String),
+    Command(String),
+    Schema(String),
+    Server(String),
 }
 
-fn create_app<TR, LR>(todo_repo: TR, label_repo: LR) -> Router
-where
-    TR: TodoRepository,
-    LR: LabelRepository,
-{
-    Router::new()
-        .route("/", get(root))
-        .route("/todos", post(create_todo::<TR>).get(all_todo::<TR>))
-        .route(
-            "/todos/:id",
-            get(find_todo::<TR>)
-                .delete(delete_todo::<TR>)
-                .patch(update_todo::<TR>),
-        )
-        .route("/label", post(create_label::<LR>).get(all_label::<LR>))
-        .route("/label/:id", delete(delete_label::<LR>))
-        .layer(Extension(Arc::new(todo_repo)))
-        .layer(Extension(Arc::new(label_repo)))
-}
+impl Failure {
+    fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            Failure::Usage(_) => 64,
+            Failure::Config(_) => 78,
+            Failure::Database(_) => 69,
+            Failure::Bind(_) => 71,
+            Failure::Command(_) => 1,
+            Failure::Schema(_) => 65,
+            Failure::Server(_) => 70,
+        })
+    }
 
-async fn run_server(socket_addr: &SocketAddr, app: Router) {
-    tracing::debug!("listening on {}", socket_addr);
-    let listener = tokio::net::TcpListener::bind(socket_addr)
-        .await
-        .expect("failed to bind");
-    axum::serve(listener, app.into_make_service())
-        .await
-        .unwrap();
+    fn log(&self) {
+        match self {
+            Failure::Usage(e) | Failure::Command(e) => tracing::error!("{}", e),
+            Failure::Config(e) => tracing::error!("Invalid configuration: {}", e),
+            Failure::Database(e) => tracing::error!("Can not connect to database: {}", e),
+            Failure::Bind(e) => tracing::error!("Can not listen on {}", e),
+            Failure::Schema(e) => tracing::error!("Database schema is out of date: {}", e),
+            Failure::Server(e) => tracing::error!("Server failed: {}", e),
+        }
+    }
 }
 
-#[tokio::main]
-async fn main() {
-    setup_logging();
-    set_dotenv_vars();
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let db_conn = create_db_conn(&database_url).await;
-    // get front end url from env
-    let client_url = env::var("CLIENT_URL").expect("CLIENT_URL must be set");
-    let cors_layer = create_cors_layer(vec![client_url]);
-    // init logging
-
-    let todo_repo = TodoRepositoryForDb::new(db_conn.clone());
-    let label_repo = LabelRepositoryForDb::new(db_conn.clone());
-
-    let router = create_app::<TodoRepositoryForDb, LabelRepositoryForDb>(todo_repo, label_repo)
-        .layer(cors_layer);
-    let addr = SocketAddr::from(([127, 0, 0, 1], 8078));
-    run_server(&addr, router).await;
+/// The first retry of [`create_db_conn`], doubled after each failure up to the maximum.
+const CONNECT_RETRY_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_CONNECT_RETRY_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Connect to `db_url`, retrying for up to `max_wait` while the database can't be reached or
+/// is still starting up. Anything else, like a wrong password, fails right away.
+async fn create_db_conn(db_url: &str, max_wait: Duration) -> Result<PgPool, Failure> {
+    let started = Instant::now();
+    let mut backoff = CONNECT_RETRY_BACKOFF;
+    // a single connection fails right away, the pool would keep trying for its acquire timeout
+    loop {
+        let e = match PgConnection::connect(db_url).await {
+            Ok(conn) => {
+                let _ = conn.close().await;
+                return PgPool::connect(db_url)
+                    .await
+                    .map_err(|e| Failure::Database(e.to_string()));
+            }
+            Err(e) => e,
+        };
+        let retryable = match &e {
+            sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut => true,
+            // cannot_connect_now: starting up, shutting down or in recovery
+            sqlx::Error::Database(db) => db.code().as_deref() == Some("57P03"),
+            _ => false,
+        };
+        let remaining = max_wait.saturating_sub(started.elapsed());
+        if !retryable || remaining.is_zero() {
+            return Err(Failure::Database(e.to_string()));
+        }
+        let delay = backoff.min(remaining);
+        tracing::warn!("database not available yet, retrying in {:?}: {}", delay, e);
+        tokio::time::sleep(delay).await;
+        backoff = (backoff * 2).min(MAX_CONNECT_RETRY_BACKOFF);
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use axum::response::Response;
-    use axum::{
-        body::Body,
-        http::{Method, Request},
-    };
-    use hyper::header::CONTENT_TYPE;
-    use hyper::StatusCode;
-    use mime::APPLICATION_JSON;
-    use tower::ServiceExt;
-
-    use crate::create_app;
-    use crate::repositories::label::test_inmemory_repo::LabelRepositoryForMemory;
-    use crate::repositories::todo::{
-        test_inmemory_repo::TodoRepositoryMemory, CreateTodo, TodoEntity, TodoRepository,
+/// Listen on `socket_addr`, or on the socket passed in `LISTEN_FDS` when `inherit` is set.
+async fn bind(socket_addr: &SocketAddr, inherit: bool) -> Result<TcpListener, Failure> {
+    let bind_failed = |e: std::io::Error| Failure::Bind(format!("{}: {}", socket_addr, e));
+    let inherited = if inherit {
+        inherited_listener().map_err(bind_failed)?
+    } else {
+        None
     };
+    match inherited {
+        Some(listener) => TcpListener::from_std(listener).map_err(bind_failed),
+        None => TcpListener::bind(socket_addr).await.map_err(bind_failed),
+    }
+}
 
-    // Test utilities
+async fn run_server(name: &str, listener: TcpListener, app: Router) -> Result<(), Failure> {
+    let local_addr = listener
+        .local_addr()
+        .map_err(|e| Failure::Bind(format!("{}: {}", name, e)))?;
+    tracing::info!("{} listening on {}", name, local_addr);
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .map_err(|e| Failure::Server(e.to_string()))?;
+    tracing::info!("{} shut down", name);
+    Ok(())
+}
 
-    struct RequestBuilder {
-        uri: String,
-        method: Method,
+/// The socket handed over by `systemfd` (or systemd socket activation), so restarts during
+/// development keep the port open and the SPA doesn't see refused connections:
+/// `systemfd --no-pid -s http::8078 -- cargo watch -x run`.
+#[cfg(unix)]
+fn inherited_listener() -> std::io::Result<Option<std::net::TcpListener>> {
+    use std::os::unix::io::FromRawFd;
+
+    /// The first passed descriptor, after stdin, stdout and stderr.
+    const LISTEN_FDS_START: i32 = 3;
+    let passed = env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|fds| fds.parse::<u32>().ok())
+        .is_some_and(|fds| fds > 0);
+    // `--no-pid` leaves LISTEN_PID unset, as the server is a grandchild of systemfd
+    let for_us = env::var("LISTEN_PID").map_or(true, |pid| pid == std::process::id().to_string());
+    if !passed || !for_us {
+        return Ok(None);
     }
+    // SAFETY: LISTEN_FDS says the descriptor was passed to this process, and it is only
+    // taken here, once
+    let listener = unsafe { std::net::TcpListener::from_raw_fd(LISTEN_FDS_START) };
+    listener.set_nonblocking(true)?;
+    tracing::info!("using the socket passed in LISTEN_FDS");
+    Ok(Some(listener))
+}
+
+#[cfg(not(unix))]
+fn inherited_listener() -> std::io::Result<Option<std::net::TcpListener>> {
+    Ok(None)
+}
 
-    impl RequestBuilder {
-        fn new(uri: &str, method: Method) -> RequestBuilder {
-            RequestBuilder {
-                uri: uri.to_string(),
-                method,
+/// SIGTERM from an orchestrator or Ctrl-C. Requests in flight are finished before exiting.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("can't listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("can't listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
             }
         }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("shutting down, finishing requests in flight");
+}
 
-        fn with_json_string(self, json_string: String) -> Request<Body> {
-            Request::builder()
-                .uri(self.uri)
-                .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
-                .method(self.method)
-                .body(Body::from(json_string))
-                .unwrap()
+/// One-off maintenance commands, e.g. `my-todo rebuild-projection` or
+/// `my-todo seed fixtures/demo.json`. They only need `DATABASE_URL`.
+async fn run_command(args: &[String]) -> Result<(), Failure> {
+    let database_url = config::env_secret("DATABASE_URL")
+        .map_err(|e| Failure::Config(e.to_string()))?
+        .ok_or_else(|| Failure::Config("DATABASE_URL must be set".to_string()))?;
+    let result = match args {
+        [command] if command == "rebuild-projection" => {
+            todo_events::rebuild_projection(&create_db_conn(&database_url, Duration::ZERO).await?)
+                .await
+                .map(|count| tracing::info!("rebuilt {} todos from todo_events", count))
+        }
+        [command, path] if command == "seed" => seed(&database_url, path).await?,
+        _ => {
+            return Err(Failure::Usage(format!(
+                "unknown command {:?}, expected `--check`, `rebuild-projection` or \
+                 `seed <fixture.json>`",
+                args
+            )));
         }
+    };
+    result.map_err(|e| Failure::Command(format!("{} failed: {:#}", args[0], e)))
+}
 
-        fn with_empty(&self) -> Request<Body> {
-            Request::builder()
-                .uri(self.uri.as_str())
-                .method(self.method.as_ref())
-                .body(Body::empty())
-                .unwrap()
+/// `--check` goes through what starting the server would, without serving: it loads the
+/// configuration, connects to the database and compares its migrations with those of this
+/// build. The report goes to stdout, and the exit status is that of the first failure.
+async fn check() -> Result<(), Failure> {
+    let mut report = preflight::Report::default();
+    let config = match AppConfig::from_env() {
+        Ok(config) => {
+            let detail = format!("listening on {}", config.listen_addr);
+            report.record("config", preflight::Outcome::Passed(detail));
+            Some(config)
+        }
+        Err(e) => {
+            report.record("config", preflight::Outcome::Failed(e.to_string()));
+            None
+        }
+    };
+    let db_conn = match &config {
+        Some(config) => match create_db_conn(&config.database_url, Duration::ZERO).await {
+            Ok(db_conn) => {
+                report.record(
+                    "database",
+                    preflight::Outcome::Passed("connected".to_string()),
+                );
+                Some(db_conn)
+            }
+            Err(Failure::Database(e)) => {
+                report.record("database", preflight::Outcome::Failed(e));
+                None
+            }
+            Err(failure) => return Err(failure),
+        },
+        None => {
+            report.record("database", preflight::Outcome::Skipped);
+            None
         }
+    };
+    match &db_conn {
+        Some(db_conn) => {
+            let outcome = match preflight::migration_status(db_conn).await {
+                Ok(status) if status.is_current() => preflight::Outcome::Passed(status.to_string()),
+                Ok(status) => preflight::Outcome::Failed(status.to_string()),
+                Err(e) => preflight::Outcome::Failed(format!("{:#}", e)),
+            };
+            report.record("migrations", outcome);
+        }
+        None => report.record("migrations", preflight::Outcome::Skipped),
     }
+    print!("{}", report);
 
-    async fn res_to_todo(res: Response) -> TodoEntity {
-        let bytes = axum::body::to_bytes(res.into_body(), 10_000).await.unwrap();
-        let body: String = String::from_utf8(bytes.to_vec()).unwrap();
-        let todo: TodoEntity = serde_json::from_str(&body)
-            .unwrap_or_else(|_| panic!("cannot convert Todo instance. body: {}", body));
-        todo
+    match report.failure() {
+        None => Ok(()),
+        Some(("config", reason)) => Err(Failure::Config(reason.to_string())),
+        Some(("database", reason)) => Err(Failure::Database(reason.to_string())),
+        Some((_, reason)) => Err(Failure::Schema(reason.to_string())),
     }
+}
 
-    async fn res_to_todos(res: Response) -> Vec<TodoEntity> {
-        let bytes = axum::body::to_bytes(res.into_body(), 10_000).await.unwrap();
-        let body = String::from_utf8(bytes.to_vec()).unwrap();
-
-        let todos: Vec<TodoEntity> = serde_json::from_str(&body)
-            .unwrap_or_else(|_| panic!("failed to parse json: {}", body));
-        todos
-    }
+/// The outer error is about the environment, the inner one about seeding itself.
+async fn seed(database_url: &str, path: &str) -> Result<anyhow::Result<()>, Failure> {
+    let encryption_key = config::env_secret("TODO_ENCRYPTION_KEY")
+        .map_err(|e| Failure::Config(e.to_string()))?
+        .filter(|key| !key.is_empty())
+        .map(|key| key.parse::<EncryptionKey>())
+        .transpose()
+        .map_err(|e| Failure::Config(format!("TODO_ENCRYPTION_KEY: {}", e)))?;
+    let db_conn = create_db_conn(database_url, Duration::ZERO).await?;
+    Ok(load_fixture(db_conn, encryption_key, path).await)
+}
 
-    // Tests
+async fn load_fixture(
+    db_conn: PgPool,
+    encryption_key: Option<EncryptionKey>,
+    path: &str,
+) -> anyhow::Result<()> {
+    let fixture = fixtures::Fixture::read(path)?;
+    let loaded = fixtures::load(
+        &fixture,
+        &TodoRepositoryForDb::new(db_conn.clone()).with_encryption(encryption_key.as_ref()),
+        &LabelRepositoryForDb::new(db_conn),
+    )
+    .await?;
+    tracing::info!(
+        "seeded {} labels and {} todos from {}",
+        loaded.labels.len(),
+        loaded.todos.len(),
+        path
+    );
+    Ok(())
+}
 
-    #[tokio::test]
-    async fn test_root() {
-        let req = RequestBuilder::new("/", Method::GET).with_empty();
-        let app = create_app(TodoRepositoryMemory::new(), LabelRepositoryForMemory::new());
-        let res = app.oneshot(req).await.unwrap();
-        let body = axum::body::to_bytes(res.into_body(), 10_000).await.unwrap();
-        assert_eq!(body, "Hello, world!");
+#[tokio::main]
+async fn main() -> ExitCode {
+    let log_handle = setup_logging();
+    set_dotenv_vars();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.as_slice() {
+        [] => serve(log_handle).await,
+        [flag] if flag == "--check" => check().await,
+        _ => run_command(&args).await,
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => {
+            failure.log();
+            failure.exit_code()
+        }
     }
+}
 
-    #[tokio::test]
-    async fn test_create_todo_route() {
-        let req = RequestBuilder::new("/todos", Method::POST)
-            .with_json_string(r#"{"text": "test todo","labels": []}"#.to_string());
-        let todo_repo = TodoRepositoryMemory::new();
-        let label_repo = LabelRepositoryForMemory::new();
-        let app = create_app(todo_repo, label_repo);
-        let res = app.oneshot(req).await.unwrap();
-
-        let sut = res_to_todo(res).await;
-
-        let expected = TodoEntity::new(1, "test todo".to_string());
-        assert_eq!(sut, expected);
+async fn serve(log_handle: reload::Handle<EnvFilter, Registry>) -> Result<(), Failure> {
+    let config = AppConfig::from_env().map_err(|e| Failure::Config(e.to_string()))?;
+    let db_conn = create_db_conn(&config.database_url, config.db_connect_timeout).await?;
+    let live_config = Arc::new(LiveConfig::new(config.clone()));
+    let cors_layer = create_cors_layer(&config.cors, live_config.clone());
+
+    let todo_repo = TodoRepositoryForDb::new(db_conn.clone())
+        .with_slow_query_threshold(config.slow_query_threshold)
+        .with_next_todo_scoring(config.next_todo_scoring.clone())
+        .with_outbox(config.nats_url.is_some())
+        .with_encryption(config.encryption_key.as_ref())
+        .with_max_search_matches(config.max_search_matches);
+    let label_repo = LabelRepositoryForDb::new(db_conn.clone())
+        .with_slow_query_threshold(config.slow_query_threshold)
+        .with_outbox(config.nats_url.is_some());
+    let health = Arc::new(Health::default());
+    if let Some(nats_url) = &config.nats_url {
+        let publisher = NatsPublisher::new(nats_url)
+            .map_err(|e| Failure::Config(format!("NATS_URL: {}", e)))?;
+        tokio::spawn(events::relay(
+            db_conn.clone(),
+            Arc::new(publisher),
+            config.outbox.clone(),
+            health.clone(),
+        ));
     }
 
-    #[tokio::test]
-    async fn test_find_todo_by_id_route() {
-        // Given a todo in the repository as memory
-        let todo_repo = TodoRepositoryMemory::new();
-        let c_todo = CreateTodo::new("test todo".to_string(), vec![]);
-        let todo_registered = todo_repo
-            .create(c_todo)
-            .await
-            .expect("failed to create todo");
-        let label_repo = LabelRepositoryForMemory::new();
-
-        // When a request is made to find the todo by id
-        let req = RequestBuilder::new("/todos/1", Method::GET).with_empty();
-        let app = create_app(todo_repo, label_repo);
-        let res = app.oneshot(req).await.unwrap();
-        let result_response = res_to_todo(res).await;
-
-        // then
-        assert_eq!(result_response, todo_registered)
+    let metrics_handle = telemetry::install_recorder();
+    let jobs = Jobs::new(db_conn.clone(), config.jobs.clone());
+    let jobs = purge::schedule(jobs, db_conn.clone(), todo_repo.clone(), &config.purge);
+    let jobs = nudge::schedule(
+        jobs,
+        db_conn.clone(),
+        &config.stale,
+        config.nats_url.is_some(),
+    );
+    jobs.start(health.clone());
+    tokio::spawn(health::monitor(
+        db_conn.clone(),
+        health.clone(),
+        config.health_check_interval,
+    ));
+    tokio::spawn(telemetry::report_counts(
+        db_conn.clone(),
+        health.clone(),
+        config.metrics_refresh_interval,
+    ));
+
+    // `/__test__/reset` empties the labels behind the cache's back
+    let label_cache_ttl = if cfg!(feature = "test-support") {
+        Duration::ZERO
+    } else {
+        config.label_cache_ttl
+    };
+    let todos = InstrumentedRepository::new(todo_repo.clone(), "todo");
+    let labels = InstrumentedRepository::new(
+        CachedLabelRepository::new(label_repo.clone(), label_cache_ttl),
+        "label",
+    );
+    let mut router = create_app(todos.clone(), labels.clone());
+    router = router.merge(revisions::routes(todo_repo.clone()));
+    router = router.merge(weekly::routes(db_conn.clone()));
+    router = router.merge(share::routes(
+        db_conn.clone(),
+        todo_repo.clone(),
+        label_repo.clone(),
+    ));
+    router = router.merge(ingest::routes(db_conn.clone()));
+    router = router.layer(Extension(config.page_sizes));
+    let read_only_mode = Arc::new(ReadOnlyMode::new(config.read_only));
+    let reloader = Arc::new(Reloader::new(
+        || {
+            // pick up edits to .env as well
+            dotenvy::dotenv_override().ok();
+            AppConfig::from_env()
+        },
+        live_config,
+        read_only_mode.clone(),
+        move |level| Ok(log_handle.reload(EnvFilter::try_new(level)?)?),
+    ));
+    tokio::spawn(config_reload::on_sighup(reloader.clone()));
+    router = read_only::guard(router, read_only_mode.clone());
+    // either on the API, or on the ops listener along with `/metrics`
+    let mut ops = Router::new();
+    if config.ops_listen_addr.is_some() {
+        ops = health::routes(ops, health);
+    } else {
+        router = health::routes(router, health);
     }
-
-    #[tokio::test]
-    async fn test_all_todos_route() {
-        // Given a todo in the repository as memory
-        let todo_repo = TodoRepositoryMemory::new();
-        let c_todo = CreateTodo::new("test todo".to_string(), vec![]);
-        let todo_registered = todo_repo
-            .create(c_todo)
-            .await
-            .expect("Failed to create todo");
-        let c_todo2 = CreateTodo::new("test todo2".to_string(), vec![]);
-        let todo_registered2 = todo_repo
-            .create(c_todo2)
-            .await
-            .expect("Failed to create todo");
-
-        let label_repo = LabelRepositoryForMemory::new();
-
-        // When a request is made to find the todo by id
-        let req = RequestBuilder::new("/todos", Method::GET).with_empty();
-        let app = create_app(todo_repo, label_repo);
-        let res = app.oneshot(req).await.unwrap();
-        let result_response = res_to_todos(res).await;
-
-        // then
-        assert_eq!(result_response, vec![todo_registered, todo_registered2]);
+    if config.csrf.enabled {
+        router = csrf::protect(router, &config.csrf);
     }
-
-    #[tokio::test]
-    async fn test_delete_todo_route() {
-        // Given a todo in the repository as memory
-        let todo_repo = TodoRepositoryMemory::new();
-        let c_todo = CreateTodo::new("test todo".to_string(), vec![]);
-        let _todo_registered = todo_repo
-            .create(c_todo)
-            .await
-            .expect("Failed to create todo");
-
-        let label_repo = LabelRepositoryForMemory::new();
-
-        // When a delete request made with path param id=1
-        let req = RequestBuilder::new("/todos/1", Method::DELETE).with_empty();
-        let app = create_app(todo_repo, label_repo);
-        let res = app.clone().oneshot(req).await.unwrap();
-
-        // then
-        assert_eq!(StatusCode::NO_CONTENT, res.status());
-
-        // and with not found request
-        let req = RequestBuilder::new("/todos/2", Method::DELETE).with_empty();
-        let res = app.oneshot(req).await.unwrap();
-        // then
-        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, res.status());
+    // webhooks can't obtain a CSRF token, so they are merged after `csrf::protect`
+    if let Some(inbound_token) = config.inbound_email_token.clone() {
+        let inbound = inbound::routes(inbound_token, Arc::new(todos.clone()));
+        router = router.merge(read_only::guard(inbound, read_only_mode.clone()));
     }
+    let ingest = ingest::webhook(db_conn.clone(), Arc::new(todos.clone()), Arc::new(labels));
+    router = router.merge(read_only::guard(ingest, read_only_mode.clone()));
+    #[cfg(feature = "test-support")]
+    {
+        tracing::warn!("test support endpoints under /__test__ are enabled");
+        let test_support = my_todo_core::test_support::routes(
+            db_conn.clone(),
+            Arc::new(todo_repo.clone()),
+            Arc::new(label_repo),
+        );
+        router = router.merge(test_support);
+    }
+    // the pages check a CSRF token posted in their forms themselves
+    if config.ui_enabled {
+        let ui = ui::routes(Arc::new(todos), config.csrf.cookie_secure);
+        router = router.merge(read_only::guard(ui, read_only_mode.clone()));
+    }
+    // only reported under /admin, so not tracked without it
+    let mut slo_tracker = None;
+    if let Some(admin_token) = config.admin_token.clone() {
+        let tracker = Arc::new(slo::SloTracker::new(config.slo.clone()));
+        let (report_conn, ad_hoc_queries) = match &config.report_database_url {
+            Some(url) => (create_db_conn(url, config.db_connect_timeout).await?, true),
+            None => {
+                tracing::info!("ad hoc reports are off without REPORT_DATABASE_URL");
+                (db_conn.clone(), false)
+            }
+        };
+        let more = purge::routes(db_conn.clone(), &config.purge)
+            .merge(diagnostics::routes(db_conn.clone()))
+            .merge(reports::routes(
+                ReportRepositoryForDb::new(report_conn),
+                ad_hoc_queries,
+            ))
+            .merge(slo::routes(tracker.clone()));
+        slo_tracker = Some(tracker);
+        router = router.merge(admin::routes(admin_token, read_only_mode, reloader, more));
+    }
+    if let Some(static_dir) = config.static_dir.clone() {
+        tracing::info!("serving the frontend from {}", static_dir.display());
+        router = static_files::serve(router, static_dir);
+    }
+    if let Some(shedding) = &config.load_shedding {
+        let pressure = Arc::new(PoolPressure::default());
+        tokio::spawn(load_shed::monitor(
+            db_conn.clone(),
+            pressure.clone(),
+            shedding.max_wait,
+        ));
+        router = load_shed::shed(router, pressure, shedding);
+    }
+    if !config.concurrency_limits.is_empty() {
+        router = concurrency::limit(router, config.concurrency_limits.clone());
+    }
+    if !config.dedupe_routes.is_empty() {
+        router = dedupe::collapse(router, config.dedupe_routes.clone());
+    }
+    if let Some(limit) = config.rate_limit {
+        router = rate_limit::annotate(router, limit);
+    }
+    if let Some(slo_tracker) = slo_tracker {
+        router = slo::track(router, slo_tracker);
+    }
+    let ops = match config.ops_listen_addr {
+        Some(_) => {
+            router = telemetry::track(router);
+            Some(telemetry::metrics_route(ops, metrics_handle))
+        }
+        None => {
+            router = telemetry::instrument(router, metrics_handle);
+            None
+        }
+    };
+    let router = access_log::trace(options::answer_options(router, cors_layer));
+    tracing::info!(
+        read_only = config.read_only,
+        outbox = config.nats_url.is_some(),
+        csrf = config.csrf.enabled,
+        ui = config.ui_enabled,
+        admin = config.admin_token.is_some(),
+        inbound_email = config.inbound_email_token.is_some(),
+        static_dir = ?config.static_dir,
+        encryption = config.encryption_key.is_some(),
+        job_workers = config.jobs.workers,
+        "starting my-todo {}",
+        env!("CARGO_PKG_VERSION")
+    );
+    let api = bind(&config.listen_addr, true).await?;
+    match (config.ops_listen_addr, ops) {
+        (Some(ops_addr), Some(ops)) => {
+            let ops_listener = bind(&ops_addr, false).await?;
+            tokio::try_join!(
+                run_server("api", api, router),
+                run_server("ops", ops_listener, ops)
+            )?;
+            Ok(())
+        }
+        _ => run_server("api", api, router).await,
+    }
+}
 
-    #[tokio::test]
-    async fn update_todo_route() {
-        // Given a todo in the repository as memory
-        let todo_repo = TodoRepositoryMemory::new();
-        let c_todo = CreateTodo::new("test todo".to_string(), vec![]);
-        let _todo_registered = todo_repo
-            .create(c_todo)
-            .await
-            .expect("Failed to create todo");
-
-        let label_repo = LabelRepositoryForMemory::new();
-
-        // When a delete request made with path param id=1
-        let req = RequestBuilder::new("/todos/1", Method::PATCH)
-            .with_json_string(r#"{"text": "test todo updated"}"#.to_string());
-        let app = create_app(todo_repo, label_repo);
-        let res = app.clone().oneshot(req).await.unwrap();
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::header::{ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN};
+    use axum::http::{Request, StatusCode};
+    use axum::routing::put;
+    use tower::ServiceExt;
 
-        // then
-        assert_eq!(StatusCode::CREATED, res.status());
+    use super::*;
 
-        // and with not found request
-        let req = RequestBuilder::new("/todos/2", Method::PATCH)
-            .with_json_string(r#"{"text": "test todo updated"}"#.to_string());
+    #[tokio::test]
+    async fn preflight_allows_put() {
+        let config = AppConfig::from_lookup(|key| match key {
+            "DATABASE_URL" => Some("postgres://localhost/todos".to_string()),
+            "CLIENT_URL" => Some("http://localhost:3000".to_string()),
+            _ => None,
+        })
+        .unwrap();
+        let cors = create_cors_layer(&config.cors, Arc::new(LiveConfig::new(config.clone())));
+        let app = options::answer_options(
+            Router::new().route("/label/:id/defaults", put(|| async { "{}" })),
+            cors,
+        );
+
+        let req = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/label/1/defaults")
+            .header(ORIGIN, "http://localhost:3000")
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+            .body(Body::empty())
+            .unwrap();
         let res = app.oneshot(req).await.unwrap();
-        // then
-        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        assert_eq!(res.status(), StatusCode::OK);
+        let allowed = res.headers()[ACCESS_CONTROL_ALLOW_METHODS]
+            .to_str()
+            .unwrap();
+        assert!(
+            allowed.split(',').any(|method| method.trim() == "PUT"),
+            "{}",
+            allowed
+        );
     }
 }